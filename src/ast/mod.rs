@@ -1,3 +1,6 @@
 pub mod types;
 
-pub use types::{BinaryOperator, Expression, Literal, Program, Statement, UnaryOperator};
+pub use types::{
+    BinaryOperator, Expression, Field, Literal, MatchArm, Param, Pattern, Program, Statement,
+    UnaryOperator,
+};
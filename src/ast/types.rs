@@ -1,11 +1,15 @@
 use serde::{Deserialize, Serialize};
 
+/// A literal value. Generated scripts hold every value as a string:
+/// booleans are `true` and `false`, and arrays are native arrays where the
+/// target has them and lines otherwise.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Literal {
     Integer(i64),
     Float(f64),
     Boolean(bool),
+    /// No value, held as the empty string.
     Null,
     String(String),
     Array(Vec<Expression>),
@@ -38,16 +42,59 @@ pub enum Expression {
         name: String,
         args: Vec<Expression>,
     },
+    /// `name: value` among the arguments of a call, passing the parameter
+    /// `name`.
+    NamedArgument {
+        name: String,
+        value: Box<Expression>,
+    },
     InterpolatedString {
         parts: Vec<Expression>,
     },
+    /// `start..end`, or `start..=end` when `inclusive`, counting by
+    /// `step` or 1.
     Range {
         start: Box<Expression>,
         end: Box<Expression>,
+        inclusive: bool,
+        step: Option<Box<Expression>>,
     },
     Length {
         expr: Box<Expression>,
     },
+    /// `fn(params) { body }`, an anonymous function whose value is
+    /// `return_value`.
+    Lambda {
+        params: Vec<String>,
+        body: Vec<Statement>,
+        return_value: Option<Box<Expression>>,
+    },
+    /// A command in a condition, true when it succeeds.
+    Command {
+        name: String,
+        args: Vec<Expression>,
+    },
+    /// `Name { field: value, ... }`, a value of the struct `Name`.
+    StructLiteral {
+        name: String,
+        fields: Vec<(String, Expression)>,
+    },
+    /// `object.field`, a field of a struct.
+    Field {
+        object: Box<Expression>,
+        field: String,
+    },
+}
+
+impl Expression {
+    /// The variable or field this expression reads, such as `s.owner.name`.
+    pub fn path(&self) -> Option<String> {
+        match self {
+            Expression::Identifier(name) => Some(name.clone()),
+            Expression::Field { object, field } => Some(format!("{}.{}", object.path()?, field)),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -78,10 +125,23 @@ pub enum UnaryOperator {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum Statement {
+    /// `name = value`. The name may be a field of a struct, such as
+    /// `s.port`.
     Assignment {
         name: String,
         value: Expression,
     },
+    /// `let name = value`: a variable of the block it is declared in,
+    /// shadowing any of the same name outside the block.
+    Let {
+        name: String,
+        value: Expression,
+    },
+    /// `const NAME = value`: like `let`, but never assigned again.
+    Const {
+        name: String,
+        value: Expression,
+    },
     Command {
         name: String,
         args: Vec<Expression>,
@@ -95,28 +155,113 @@ pub enum Statement {
         condition: Expression,
         body: Vec<Statement>,
     },
+    /// `for variable in iterable`, or `for index, variable in iterable`
+    /// to also count the iterations from 0.
     For {
+        index: Option<String>,
         variable: String,
         iterable: Expression,
         body: Vec<Statement>,
     },
     FunctionDef {
         name: String,
-        params: Vec<String>,
+        params: Vec<Param>,
         body: Vec<Statement>,
         return_value: Option<Expression>,
     },
     Return {
         value: Option<Expression>,
     },
+    /// `global a, b`: in a function, `a` and `b` are the script's
+    /// variables. Any other variable a function assigns is its own.
+    Global {
+        names: Vec<String>,
+    },
+    /// `struct Name { field: type, ... }`. A field has the type of a
+    /// value, such as `string` or `int`, or of another struct.
+    Struct {
+        name: String,
+        fields: Vec<Field>,
+    },
+    /// `enum Name { A, B, ... }`. `Name.A` is the string `A`, and
+    /// `Name(text)` is `text` when it is one of the variants.
+    Enum {
+        name: String,
+        variants: Vec<String>,
+    },
     Try {
         body: Vec<Statement>,
         catch_var: String,
         catch_body: Vec<Statement>,
     },
+    /// Runs the body of the first arm with a pattern matching `value`.
+    Match {
+        value: Expression,
+        arms: Vec<MatchArm>,
+    },
     Break,
     Continue,
     ExpressionStatement(Expression),
+    /// Marks where the next statement starts in the Shard source. Only
+    /// present in programs built by `parse_with_locations`.
+    Location {
+        line: usize,
+        column: usize,
+    },
+}
+
+/// A parameter of a function definition.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Param {
+    pub name: String,
+    /// The value used when the argument is missing or empty.
+    pub default: Option<Expression>,
+    /// `...name`, an array of the remaining arguments. Only the last
+    /// parameter may be one.
+    pub rest: bool,
+    /// The type written after the name, as in `server: Server`.
+    pub ty: Option<String>,
+}
+
+impl Param {
+    pub fn new(name: impl Into<String>) -> Self {
+        Param {
+            name: name.into(),
+            default: None,
+            rest: false,
+            ty: None,
+        }
+    }
+}
+
+/// A field of a struct declaration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Field {
+    pub name: String,
+    pub ty: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchArm {
+    /// Alternatives separated by `|`.
+    pub patterns: Vec<Pattern>,
+    pub body: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pattern {
+    /// A value compared as a shell glob: `*`, `?` and `[...]` match as in
+    /// file names, everything else literally.
+    Glob(String),
+    /// `_`, which matches any value.
+    Wildcard,
+    /// `Name.Variant`, a variant of an enum. A match on an enum covers
+    /// all its variants or has a `_` arm.
+    Variant {
+        enumeration: String,
+        variant: String,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
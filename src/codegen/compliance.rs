@@ -0,0 +1,88 @@
+use crate::codegen::target::Target;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// A shell construct beyond POSIX `sh` that generated code may rely on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// `local name=value` inside functions.
+    Local,
+    /// Indexed arrays: `a=(x y)`, `${a[i]}`, `${#a[@]}`.
+    IndexedArrays,
+    /// Associative arrays: `declare -A` / `typeset -A`.
+    AssocArrays,
+    /// The `[[ ... ]]` conditional command.
+    DoubleBrackets,
+    /// `set -o pipefail`.
+    Pipefail,
+    /// Function names containing characters outside `[A-Za-z0-9_]`.
+    ExtendedFunctionNames,
+    /// `trap ... ERR`, run whenever a command fails.
+    ErrTrap,
+}
+
+impl Feature {
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Local => "local variables",
+            Feature::IndexedArrays => "indexed arrays",
+            Feature::AssocArrays => "associative arrays",
+            Feature::DoubleBrackets => "[[ ]] tests",
+            Feature::Pipefail => "pipefail",
+            Feature::ExtendedFunctionNames => "non-POSIX function names",
+            Feature::ErrTrap => "ERR traps",
+        }
+    }
+
+    pub fn supported_by(self, target: Target) -> bool {
+        match target {
+            Target::Posix => false,
+            Target::Bash | Target::Zsh => true,
+            Target::Busybox => matches!(self, Feature::Local | Feature::Pipefail),
+        }
+    }
+}
+
+impl std::fmt::Display for Feature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What to do when a construct needs a feature the target lacks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Compliance {
+    /// Fail code generation.
+    #[default]
+    Strict,
+    /// Emit the construct anyway and report a warning.
+    Warn,
+}
+
+/// A construct that was emitted despite needing an unsupported feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComplianceWarning {
+    pub feature: Feature,
+    pub construct: String,
+    pub target: Target,
+}
+
+impl std::fmt::Display for ComplianceWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} needs {}, which target {} does not support",
+            self.construct, self.feature, self.target
+        )
+    }
+}
+
+/// Whether `name` is a valid POSIX function name.
+pub fn is_posix_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -7,6 +7,37 @@ pub enum CodegenError {
     #[error("Unsupported AST node: {node_type}")]
     UnsupportedNode { node_type: String },
 
+    #[error("{construct} needs {feature}, which target {target} does not support")]
+    #[diagnostic(help("Choose a richer --target or pass --compliance warn"))]
+    UnsupportedFeature {
+        feature: String,
+        construct: String,
+        target: String,
+    },
+
+    #[error("'{name}' is not a struct")]
+    NotAStruct { name: String },
+
+    #[error("Struct {structure} has no field '{field}'")]
+    UnknownField { structure: String, field: String },
+
+    #[error("'{name}' is a struct and has no value of its own")]
+    #[diagnostic(help("Use one of its fields, such as {name}.{field}"))]
+    StructValue { name: String, field: String },
+
+    #[error("Argument {position} of {function}() must be a {structure}")]
+    StructArgument {
+        function: String,
+        position: usize,
+        structure: String,
+    },
+
+    #[error("Enum {enumeration} has no variant '{variant}'")]
+    UnknownVariant {
+        enumeration: String,
+        variant: String,
+    },
+
     #[error("Codegen error: {0}")]
     ParseError(String),
 }
@@ -1,309 +1,1442 @@
-use crate::ast::{Expression, Literal, Program, Statement};
-use crate::codegen::error::CodegenResult;
+use crate::ast::{
+    BinaryOperator, Expression, Field, Literal, Param, Pattern, Program, Statement, UnaryOperator,
+};
+use crate::codegen::compliance::{Compliance, ComplianceWarning, Feature};
+use crate::codegen::error::{CodegenError, CodegenResult};
+use crate::codegen::ir::{
+    Arith, ArithOp, Command, Redirect, Segment, Subscript, Test, Value, Word,
+};
+use crate::codegen::options::CodegenOptions;
+use crate::codegen::printer::{Printer, LOCATION_MARKER};
+use crate::codegen::runtime::{self, Helper, Runtime, HELPERS, RUNTIME_FILE};
+use crate::codegen::source_map::{Mapping, SourceMap};
+use crate::codegen::target::Target;
+use crate::sema::{self, Type};
+use std::collections::{BTreeSet, HashMap};
+
+mod builtins;
+mod enums;
+mod functions;
+mod json;
+mod lambdas;
+mod loops;
+mod numeric;
+mod structs;
+
+use builtins::Call;
+
+/// A generated script together with what was learned while generating it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Script {
+    pub text: String,
+    /// Non-POSIX features the script relies on.
+    pub features: BTreeSet<Feature>,
+    /// Constructs emitted under `Compliance::Warn` that the target lacks.
+    pub warnings: Vec<ComplianceWarning>,
+    /// Runtime helpers the script calls, with their dependencies, in the
+    /// order they are defined.
+    pub helpers: Vec<&'static str>,
+    /// Where each generated line came from, when `CodegenOptions::source_map`
+    /// is set.
+    pub source_map: Option<SourceMap>,
+}
 
 pub fn generate(program: &Program) -> CodegenResult<String> {
+    generate_with_options(program, &CodegenOptions::default())
+}
+
+pub fn generate_with_options(program: &Program, options: &CodegenOptions) -> CodegenResult<String> {
+    Ok(generate_script(program, options)?.text)
+}
+
+pub fn generate_script(program: &Program, options: &CodegenOptions) -> CodegenResult<Script> {
+    let mut generator = Generator::new(options);
+    let printer = Printer::for_options(options);
+
+    let mut prelude = Vec::new();
+    if options.target == Target::Zsh {
+        prelude.push(Command::simple(vec![
+            Word::bare("setopt"),
+            Word::bare("KSH_ARRAYS"),
+        ]));
+    }
+    if generator.use_feature(Feature::Pipefail) {
+        prelude.push(Command::simple(vec![
+            Word::bare("set"),
+            Word::bare("-o"),
+            Word::bare("pipefail"),
+        ]));
+    }
+    if !prelude.is_empty() {
+        prelude.push(Command::Blank);
+    }
+    let reporting = if options.line_markers {
+        generator.failure_reporting()
+    } else {
+        Vec::new()
+    };
+    generator.collect_functions(&program.0);
+    let body = generator.lower_block(&program.0)?;
+
+    let helpers = generator.used_helpers();
+    let mut commands = prelude;
+    commands.extend(generator.runtime(&helpers));
+    commands.extend(saved_stderr(&helpers));
+    commands.extend(reporting);
+    commands.extend(generator.exit_handling(&printer, false));
+    commands.extend(generator.yes_flag());
+    commands.append(&mut generator.lambdas);
+    commands.extend(body);
+
     let mut output = String::new();
+    output.push_str(options.target.shebang());
+    output.push('\n');
+    if !options.minify {
+        output.push_str("# Generated by Shard\n\n");
+    }
+    output.push_str(&printer.print(&commands));
 
-    output.push_str("#!/bin/sh\n");
-    output.push_str("# Generated by Shard\n\n");
+    let (text, mappings) = resolve_locations(&output);
+    let source_map = options.source_map.then(|| SourceMap {
+        source: options.source_name.clone(),
+        mappings,
+        ..SourceMap::default()
+    });
 
-    for statement in &program.0 {
-        generate_statement(&mut output, statement)?;
-        output.push('\n');
+    Ok(Script {
+        text,
+        features: generator.features,
+        warnings: generator.warnings,
+        helpers: helpers.iter().map(|helper| helper.name).collect(),
+        source_map,
+    })
+}
+
+/// The shared file `Runtime::External` scripts source: every runtime
+/// helper, named as `options` names them.
+pub fn generate_runtime(options: &CodegenOptions) -> String {
+    let generator = Generator::new(options);
+    let mut commands = Vec::new();
+    for helper in runtime::resolve(HELPERS.iter().map(|helper| helper.name), HELPERS) {
+        commands.push(generator.helper_definition(helper.source));
+        commands.push(Command::Blank);
     }
+    let mut output = String::new();
+    if !options.minify {
+        output.push_str("# Shard runtime, sourced by scripts built with --runtime external\n\n");
+    }
+    output.push_str(&Printer::for_options(options).print(&commands));
+    output
+}
 
-    Ok(output)
+/// Save stderr as fd 9 when one of `helpers` writes there. Commands run
+/// with their output captured, and traps may fire while that redirection
+/// is still in effect, so logs and reports go to the saved stderr.
+fn saved_stderr(helpers: &[Helper]) -> Option<Command> {
+    helpers
+        .iter()
+        .any(Helper::uses_saved_stderr)
+        .then(|| Command::Simple {
+            words: vec![Word::bare("exec")],
+            redirects: vec![Redirect::Duplicate(9, 2)],
+        })
 }
 
-fn generate_statement(output: &mut String, statement: &Statement) -> CodegenResult<()> {
-    match statement {
-        Statement::Assignment { name, value } => {
-            let var_name = format!("__shard_{}", name);
-            let var_value = generate_expression(value)?;
-            output.push_str(&format!("{}={}\n", var_name, var_value));
-        }
-        Statement::Command { name, args } => {
-            generate_command(output, name, args)?;
+/// Strip location markers from printed text, mapping every non-empty line
+/// that follows one to its Shard location.
+fn resolve_locations(output: &str) -> (String, Vec<Mapping>) {
+    let mut text = String::with_capacity(output.len());
+    let mut mappings = Vec::new();
+    let mut location = None;
+    let mut generated_line = 0;
+
+    for line in output.split_inclusive('\n') {
+        if let Some(marker) = line.strip_prefix(LOCATION_MARKER) {
+            location = marker
+                .trim_end()
+                .split_once(':')
+                .and_then(|(l, c)| Some((l.parse().ok()?, c.parse().ok()?)));
+            continue;
         }
-        Statement::If {
-            condition,
-            then_branch,
-            else_branch,
-        } => {
-            let cond_str = generate_expression(condition)?;
-            // If condition is already a test expression [ ... ], use it directly
-            // Otherwise wrap it in [ ]
-            if cond_str.starts_with('[') && cond_str.ends_with(']') {
-                output.push_str(&format!("if {}; then\n", cond_str));
-            } else {
-                output.push_str(&format!("if [ {} ]; then\n", cond_str));
+        text.push_str(line);
+        generated_line += 1;
+        if let Some((source_line, source_column)) = location {
+            if !line.trim().is_empty() {
+                mappings.push(Mapping {
+                    generated_line,
+                    source_line,
+                    source_column,
+                });
             }
-            for stmt in then_branch {
-                generate_statement(output, stmt)?;
-                output.push('\n');
-            }
-            if let Some(else_body) = else_branch {
-                output.push_str("else\n");
-                for stmt in else_body {
-                    generate_statement(output, stmt)?;
-                    output.push('\n');
-                }
-            }
-            output.push_str("fi\n");
         }
-        Statement::While { condition, body } => {
-            let cond_str = generate_expression(condition)?;
-            // If condition is already a test expression [ ... ], use it directly
-            if cond_str.starts_with('[') && cond_str.ends_with(']') {
-                output.push_str(&format!("while {}; do\n", cond_str));
-            } else {
-                output.push_str(&format!("while [ {} ]; do\n", cond_str));
-            }
-            for stmt in body {
-                generate_statement(output, stmt)?;
-                output.push('\n');
-            }
-            output.push_str("done\n");
+    }
+
+    (text, mappings)
+}
+
+/// Generate shell for a sequence of statements without the script header.
+///
+/// Used where the output is fed to an already running shell, such as the REPL.
+/// `declarations` are the functions, structs, enums and constants the shell
+/// already has; `statements` may use them, but they are not generated again.
+pub fn generate_statements(
+    declarations: &[Statement],
+    statements: &[Statement],
+) -> CodegenResult<String> {
+    let options = CodegenOptions::default();
+    let mut generator = Generator::new(&options);
+    let printer = Printer::new();
+    generator.collect_functions(declarations);
+    generator.collect_functions(statements);
+    // Lowered for the types and names they give, not for their code.
+    generator.lower_block(declarations)?;
+    generator.helpers.clear();
+    generator.lambdas.clear();
+    let body = generator.lower_block(statements)?;
+    let helpers = generator.used_helpers();
+    let mut commands = generator.runtime(&helpers);
+    commands.extend(saved_stderr(&helpers));
+    commands.extend(generator.exit_handling(&printer, true));
+    commands.append(&mut generator.lambdas);
+    commands.extend(body);
+    Ok(printer.print(&commands))
+}
+
+/// Lowers statements and expressions to shell IR for one target.
+///
+/// Arrays and maps use native (associative) arrays where the target has
+/// them. On POSIX they are newline-separated strings, one element or
+/// `key=value` pair per line.
+///
+/// Every non-POSIX construct goes through `use_feature` (when a portable
+/// fallback exists) or `require` (when it does not), so the set of
+/// features a script needs is known once generation finishes.
+struct Generator<'a> {
+    options: &'a CodegenOptions,
+    features: BTreeSet<Feature>,
+    warnings: Vec<ComplianceWarning>,
+    /// Runtime helpers called so far.
+    helpers: BTreeSet<&'static str>,
+    /// Whether `tempfile` or `tempdir` is called, so the script needs a
+    /// directory to create them in and remove on exit.
+    temporaries: bool,
+    /// Whether the script asks for input, so it accepts `--yes`.
+    interactive: bool,
+    /// Commands the EXIT trap runs, in order.
+    exit_actions: Vec<Command>,
+    /// Inferred types of the variables assigned so far, by shell name,
+    /// which decide whether arithmetic needs `awk`.
+    types: HashMap<String, Type>,
+    /// Shell names of the variables renamed in the code being lowered,
+    /// innermost scope last, such as the parameters of a lambda or the
+    /// variables a block declares with `let`.
+    scopes: Vec<HashMap<String, String>>,
+    /// Variables declared with `let` or `const` in the function being
+    /// lowered, and those its loops keep temporaries in, by shell name.
+    declared: Vec<String>,
+    declared_count: usize,
+    /// Functions defined for lambdas, emitted before the script body.
+    lambdas: Vec<Command>,
+    lambda_count: usize,
+    /// Checks of the enum conversions in the statement being lowered,
+    /// each into a temporary it reads, run before the statement so an
+    /// invalid value exits the script.
+    conversions: Vec<Command>,
+    conversion_count: usize,
+    /// Parameters of the functions the program defines, which decide
+    /// where named arguments are passed.
+    functions: HashMap<String, Vec<Param>>,
+    /// Fields of the structs the program declares.
+    structs: HashMap<String, Vec<Field>>,
+    /// The struct each variable holding one holds, by shell name.
+    records: HashMap<String, String>,
+    /// Variants of the enums the program declares.
+    enums: HashMap<String, Vec<String>>,
+    /// Functions that can call themselves, whose variables are saved and
+    /// restored around each call on targets without `local`.
+    recursive: BTreeSet<String>,
+    /// The prefix of the shell names of the function being lowered.
+    function: Option<String>,
+    /// Whether the function being lowered captures the output of a
+    /// command.
+    captures: bool,
+}
+
+impl<'a> Generator<'a> {
+    fn new(options: &'a CodegenOptions) -> Self {
+        Generator {
+            options,
+            features: BTreeSet::new(),
+            warnings: Vec::new(),
+            helpers: BTreeSet::new(),
+            temporaries: false,
+            interactive: false,
+            exit_actions: Vec::new(),
+            types: HashMap::new(),
+            scopes: Vec::new(),
+            declared: Vec::new(),
+            declared_count: 0,
+            lambdas: Vec::new(),
+            lambda_count: 0,
+            conversions: Vec::new(),
+            conversion_count: 0,
+            functions: HashMap::new(),
+            recursive: BTreeSet::new(),
+            structs: HashMap::new(),
+            records: HashMap::new(),
+            enums: HashMap::new(),
+            function: None,
+            captures: false,
         }
-        Statement::For {
-            variable,
-            iterable,
-            body,
-        } => {
-            // Check if iterable is an array literal
-            let iter_str = match iterable {
-                Expression::Literal(Literal::Array(elements)) => {
-                    let elems: Result<Vec<String>, _> =
-                        elements.iter().map(|e| generate_expression(e)).collect();
-                    elems?.join(" ")
-                }
-                _ => {
-                    let iter = generate_expression(iterable)?;
-                    // Remove parentheses if present
-                    iter.trim_start_matches('(')
-                        .trim_end_matches(')')
-                        .to_string()
-                }
+    }
+
+    /// How a map is declared: global unless it is a variable of the
+    /// function being lowered.
+    fn assoc_declaration(&mut self, name: &str) -> &'static str {
+        let local =
+            self.function.is_some() && self.scopes.iter().any(|scope| scope.contains_key(name));
+        if local && self.use_feature(Feature::Local) {
+            "local -A"
+        } else if self.options.target == Target::Zsh {
+            "typeset -gA"
+        } else {
+            "declare -gA"
+        }
+    }
+
+    /// The keys of the associative array `var_name`, one word each.
+    fn map_keys(&self, var_name: &str) -> Word {
+        if self.options.target == Target::Zsh {
+            Word::bare(format!("\"${{(@k){}}}\"", var_name))
+        } else {
+            Word::new(vec![Segment::Keys(var_name.to_string())])
+        }
+    }
+
+    /// The shell name of a Shard variable, or of a field of one such as
+    /// `s.host`.
+    fn variable(&self, name: &str) -> String {
+        if let Some((name, field)) = name.split_once('.') {
+            return format!("{}__{}", self.variable(name), field.replace('.', "__"));
+        }
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).cloned())
+            .unwrap_or_else(|| self.var(name))
+    }
+
+    /// Declare the variable `name` in the block being lowered and return
+    /// its shell name. At the top of the script that is the name of the
+    /// variable; anywhere else it is a new one, so the variable cannot
+    /// change another of the same name outside the block.
+    fn declare(&mut self, name: &str) -> String {
+        let shell_name = if self.function.is_none() && self.scopes.len() == 1 {
+            self.var(name)
+        } else {
+            self.declared_count += 1;
+            let base = match &self.function {
+                Some(prefix) => format!("{}_{}", prefix, name),
+                None => self.var(name),
             };
-            output.push_str(&format!("for __shard_{} in {}; do\n", variable, iter_str));
-            for stmt in body {
-                generate_statement(output, stmt)?;
-                output.push('\n');
+            let shell_name = format!("{}__{}", base, self.declared_count);
+            self.declared.push(shell_name.clone());
+            shell_name
+        };
+        self.scopes
+            .last_mut()
+            .expect("a block is being lowered")
+            .insert(name.to_string(), shell_name.clone());
+        shell_name
+    }
+
+    /// The shell name of a generator helper or variable, or of a Shard
+    /// variable that is not renamed.
+    fn var(&self, name: &str) -> String {
+        let prefix = if self.options.minify {
+            "_s_"
+        } else {
+            "__shard_"
+        };
+        format!("{}{}", prefix, name)
+    }
+
+    /// The inferred type of `expr`, given the variables assigned so far.
+    fn type_of(&self, expr: &Expression) -> Type {
+        sema::infer_type_with(expr, &|name| self.types.get(&self.variable(name)).copied())
+    }
+
+    /// Record a call to the runtime helper `name` and return the word that
+    /// calls it.
+    fn helper(&mut self, name: &'static str) -> Word {
+        debug_assert!(runtime::helper(name).is_some(), "unknown helper {}", name);
+        self.helpers.insert(name);
+        Word::bare(self.var(name))
+    }
+
+    /// The helpers called so far with their dependencies, in definition
+    /// order.
+    fn used_helpers(&self) -> Vec<Helper> {
+        runtime::resolve(self.helpers.iter().copied(), HELPERS)
+    }
+
+    /// Definitions of `helpers`, or the command that sources them.
+    fn runtime(&self, helpers: &[Helper]) -> Vec<Command> {
+        let mut commands = Vec::new();
+        if helpers.is_empty() {
+            return commands;
+        }
+        match self.options.runtime {
+            Runtime::Inline => {
+                for helper in helpers {
+                    commands.push(self.helper_definition(helper.source));
+                    commands.push(Command::Blank);
+                }
+            }
+            Runtime::External => {
+                commands.push(Command::Raw(format!(
+                    ". \"${{SHARD_RUNTIME:-$(dirname \"$0\")/{}}}\"",
+                    RUNTIME_FILE
+                )));
+                commands.push(Command::Blank);
             }
-            output.push_str("done\n");
         }
-        Statement::FunctionDef {
-            name,
-            params,
-            body,
-            return_value,
-        } => {
-            output.push_str(&format!("{}() {{\n", name));
-            // Declare parameters as local variables
-            for (i, param) in params.iter().enumerate() {
-                output.push_str(&format!("  local __shard_{}=${}\n", param, i + 1));
-            }
-            for stmt in body {
-                generate_statement(output, stmt)?;
-                output.push('\n');
-            }
-            if let Some(expr) = return_value {
-                let val_str = generate_expression(expr)?;
-                output.push_str(&format!("  echo {}\n", val_str));
-            }
-            output.push_str("}\n");
+        commands
+    }
+
+    /// The definition of a runtime helper under this script's prefix.
+    fn helper_definition(&self, source: &str) -> Command {
+        Command::Raw(source.replace("__shard_", &self.var("")))
+    }
+
+    /// Record `feature` if the target has it. Callers emit a portable
+    /// fallback when this returns `false`.
+    fn use_feature(&mut self, feature: Feature) -> bool {
+        if self.options.target.supports(feature) {
+            self.features.insert(feature);
+            true
+        } else {
+            false
         }
-        Statement::Return { value } => {
-            if let Some(expr) = value {
-                let val_str = generate_expression(expr)?;
-                output.push_str(&format!("return {}\n", val_str));
-            } else {
-                output.push_str("return\n");
+    }
+
+    /// Record `feature` for a construct that has no portable fallback.
+    fn require(&mut self, feature: Feature, construct: String) -> CodegenResult<()> {
+        self.features.insert(feature);
+        let target = self.options.target;
+        if target.supports(feature) {
+            return Ok(());
+        }
+        match self.options.compliance {
+            Compliance::Strict => Err(CodegenError::UnsupportedFeature {
+                feature: feature.to_string(),
+                construct,
+                target: target.to_string(),
+            }),
+            Compliance::Warn => {
+                self.warnings.push(ComplianceWarning {
+                    feature,
+                    construct,
+                    target,
+                });
+                Ok(())
             }
         }
-        Statement::Try {
-            body,
-            catch_var,
-            catch_body,
-        } => {
-            let mut try_block = String::new();
-            for stmt in body {
-                generate_statement(&mut try_block, stmt)?;
-                try_block.push('\n');
-            }
-            output.push_str(&format!(
-                "if __shard_error=$({{ {} }} 2>&1); then\n",
-                try_block.replace('\n', "; ").trim_end_matches("; ")
-            ));
-            output.push_str(&format!("  __shard_{}=''\n", catch_var));
-            output.push_str("else\n");
-            output.push_str(&format!("  __shard_{}=$?\n", catch_var));
-            for stmt in catch_body {
-                generate_statement(output, stmt)?;
-                output.push('\n');
-            }
-            output.push_str("fi\n");
+    }
+
+    /// Shell that reports the Shard line of failing commands and of a
+    /// non-zero exit. `__shard_line` is kept current by the assignments
+    /// emitted for `Statement::Location`.
+    fn failure_reporting(&mut self) -> Vec<Command> {
+        let source = self.options.source_name.as_deref().unwrap_or("<stdin>");
+        let report = self.helper("report");
+        self.exit_actions
+            .push(Command::assign(self.var("exit"), Word::param("?")));
+        self.exit_actions.push(Command::Or(
+            Box::new(Command::Test {
+                test: Test::Binary(Word::param(self.var("exit")), "-eq", Word::bare("0")),
+                double: false,
+            }),
+            Box::new(Command::simple(vec![
+                report,
+                Word::bare("exited"),
+                Word::param(self.var("exit")),
+            ])),
+        ));
+
+        let mut commands = vec![
+            Command::assign(self.var("source"), Word::literal(source)),
+            Command::assign(self.var("line"), Word::bare("0")),
+        ];
+        if self.use_feature(Feature::ErrTrap) {
+            if self.options.target == Target::Bash {
+                commands.push(Command::simple(vec![
+                    Word::bare("set"),
+                    Word::bare("-o"),
+                    Word::bare("errtrace"),
+                ]));
+            }
+            commands.push(Command::simple(vec![
+                Word::bare("trap"),
+                Word::literal(format!("{} \"command failed\" $?", self.var("report"))),
+                Word::bare("ERR"),
+            ]));
         }
-        Statement::Break => {
-            output.push_str("break\n");
+        commands
+    }
+
+    /// The directory `tempfile` and `tempdir` create paths in, and the
+    /// traps that run `exit_actions` and remove that directory.
+    ///
+    /// A script always creates a new directory, never trusting one named
+    /// in its environment, since the directory is removed on exit. In a
+    /// `session`, code run repeatedly in one shell as by the REPL, it is
+    /// only created when unset, so later inputs keep using the first one.
+    fn exit_handling(&mut self, printer: &Printer, session: bool) -> Vec<Command> {
+        let mut commands = Vec::new();
+        if self.temporaries {
+            let tmp = self.var("tmp");
+            let create = Command::assign(
+                tmp.clone(),
+                Word::substitution(vec![Command::simple(vec![
+                    Word::bare("mktemp"),
+                    Word::bare("-d"),
+                ])]),
+            );
+            if session {
+                commands.push(Command::Or(
+                    Box::new(Command::Test {
+                        test: Test::Unary("-n", Word::param(tmp.clone())),
+                        double: false,
+                    }),
+                    Box::new(create),
+                ));
+            } else {
+                commands.push(create);
+            }
+            self.exit_actions.push(Command::simple(vec![
+                Word::bare("rm"),
+                Word::bare("-rf"),
+                Word::param(tmp),
+            ]));
+            // Not every shell runs the EXIT trap when killed by a signal.
+            for (signal, status) in [("INT", "130"), ("TERM", "143")] {
+                commands.push(Command::simple(vec![
+                    Word::bare("trap"),
+                    Word::literal(format!("exit {}", status)),
+                    Word::bare(signal),
+                ]));
+            }
         }
-        Statement::Continue => {
-            output.push_str("continue\n");
+        if !self.exit_actions.is_empty() {
+            commands.push(Command::simple(vec![
+                Word::bare("trap"),
+                Word::literal(printer.print_inline(&self.exit_actions)),
+                Word::bare("EXIT"),
+            ]));
+            commands.push(Command::Blank);
         }
-        Statement::ExpressionStatement(expr) => {
-            let expr_str = generate_expression(expr)?;
-            output.push_str(&format!("{}\n", expr_str));
+        commands
+    }
+
+    /// Set `SHARD_YES` when the script is passed `--yes`, so questions take
+    /// their defaults.
+    fn yes_flag(&mut self) -> Vec<Command> {
+        if !self.interactive {
+            return Vec::new();
         }
+        let arg = self.var("arg");
+        let is_yes = self.test(Test::Binary(
+            Word::param(arg.clone()),
+            "=",
+            Word::literal("--yes"),
+        ));
+        vec![
+            Command::For {
+                variable: arg,
+                words: vec![Word::param("@")],
+                body: vec![Command::If {
+                    condition: Box::new(is_yes),
+                    then_branch: vec![Command::assign("SHARD_YES", Word::bare("1"))],
+                    else_branch: None,
+                }],
+            },
+            Command::Blank,
+        ]
     }
-    Ok(())
-}
 
-fn generate_expression(expr: &Expression) -> CodegenResult<String> {
-    match expr {
-        Expression::Literal(literal) => match literal {
-            Literal::Integer(n) => Ok(n.to_string()),
-            Literal::Float(f) => Ok(f.to_string()),
-            Literal::Boolean(true) => Ok("true".to_string()),
-            Literal::Boolean(false) => Ok("false".to_string()),
-            Literal::Null => Ok("null".to_string()),
-            Literal::String(s) => Ok(format!("'{}'", s)),
-            Literal::Array(elements) => {
-                let elements_str: Vec<String> = elements
-                    .iter()
-                    .map(|e| generate_expression(e))
-                    .collect::<Result<Vec<_>, _>>()?;
-                Ok(format!("({})", elements_str.join(" ")))
-            }
-            Literal::Map(pairs) => {
-                let pairs_str: Vec<String> = pairs
-                    .iter()
-                    .map(|(k, v)| {
-                        let k_str = generate_expression(k)?;
-                        let v_str = generate_expression(v)?;
-                        Ok(format!("{}={}", k_str, v_str))
-                    })
-                    .collect::<Result<Vec<_>, _>>()?;
-                Ok(format!("({})", pairs_str.join(" ")))
-            }
-        },
-        Expression::Identifier(name) => Ok(format!("\"$__shard_{}\"", name)),
-        Expression::BinaryOp { op, left, right } => {
-            let left_str = generate_expression(left)?;
-            let right_str = generate_expression(right)?;
-
-            // For comparison operators used in conditions, use test syntax
-            let is_comparison = matches!(
-                op,
-                crate::ast::BinaryOperator::Equals
-                    | crate::ast::BinaryOperator::NotEquals
-                    | crate::ast::BinaryOperator::Less
-                    | crate::ast::BinaryOperator::Greater
-                    | crate::ast::BinaryOperator::LessEquals
-                    | crate::ast::BinaryOperator::GreaterEquals
-            );
+    /// Whether tests use `[[ ]]` rather than `[ ]`.
+    fn double_brackets(&mut self) -> bool {
+        self.use_feature(Feature::DoubleBrackets)
+    }
+
+    fn test(&mut self, test: Test) -> Command {
+        let double = self.double_brackets();
+        Command::Test { test, double }
+    }
+
+    /// Lower a block, in a scope of its own for the variables it declares.
+    fn lower_block(&mut self, statements: &[Statement]) -> CodegenResult<Vec<Command>> {
+        self.scopes.push(HashMap::new());
+        let commands = self.lower_statements(statements);
+        self.scopes.pop();
+        commands
+    }
+
+    /// Lower statements. At the top of the script, a blank line sets off
+    /// each block and function from the code around it.
+    fn lower_statements(&mut self, statements: &[Statement]) -> CodegenResult<Vec<Command>> {
+        let top = self.function.is_none() && self.scopes.len() == 1;
+        let outer = std::mem::take(&mut self.conversions);
+        let mut commands = Vec::new();
+        for (i, statement) in statements.iter().enumerate() {
+            let mut lowered = Vec::new();
+            let result = self.lower_statement(&mut lowered, statement);
+            commands.append(&mut self.conversions);
+            commands.append(&mut lowered);
+            if let Err(error) = result {
+                self.conversions = outer;
+                return Err(error);
+            }
+            if !top || !emits_code(statement) {
+                continue;
+            }
+            let next = statements[i + 1..].iter().find(|next| emits_code(next));
+            if next.is_some_and(|next| is_block(statement) || is_block(next)) {
+                commands.push(Command::Blank);
+            }
+        }
+        self.conversions = outer;
+        Ok(commands)
+    }
 
-            if is_comparison {
-                let shell_op = match op {
-                    crate::ast::BinaryOperator::Equals => "-eq",
-                    crate::ast::BinaryOperator::NotEquals => "-ne",
-                    crate::ast::BinaryOperator::Less => "-lt",
-                    crate::ast::BinaryOperator::Greater => "-gt",
-                    crate::ast::BinaryOperator::LessEquals => "-le",
-                    crate::ast::BinaryOperator::GreaterEquals => "-ge",
-                    _ => unreachable!(),
+    fn lower_statement(
+        &mut self,
+        commands: &mut Vec<Command>,
+        statement: &Statement,
+    ) -> CodegenResult<()> {
+        match statement {
+            Statement::Assignment { name, value } => {
+                if let Some(structure) = self.struct_of(value) {
+                    return self.lower_struct_assignment(commands, name, &structure, value);
+                }
+                let var_name = self.variable(name);
+                self.records.remove(&var_name);
+                let value_type = match (self.type_of(value), value) {
+                    // Only `json_parse` keeps the JSON a value came from.
+                    (Type::Json, Expression::FunctionCall { .. }) => Type::Json,
+                    (Type::Json, _) => Type::Unknown,
+                    (value_type, _) => value_type,
                 };
-                // Remove quotes from identifiers for test command
-                let left_clean = left_str.trim_matches('"').to_string();
-                let right_clean = right_str.trim_matches('"').to_string();
-                Ok(format!("[ {} {} {} ]", left_clean, shell_op, right_clean))
-            } else {
-                let shell_op = match op {
-                    crate::ast::BinaryOperator::Add => "+",
-                    crate::ast::BinaryOperator::Subtract => "-",
-                    crate::ast::BinaryOperator::Multiply => "*",
-                    crate::ast::BinaryOperator::Divide => "/",
-                    crate::ast::BinaryOperator::Modulo => "%",
-                    crate::ast::BinaryOperator::And => "&&",
-                    crate::ast::BinaryOperator::Or => "||",
-                    _ => unreachable!(),
+                match value {
+                    Expression::Literal(Literal::Array(elements))
+                        if self.use_feature(Feature::IndexedArrays) =>
+                    {
+                        let words = self.lower_words(elements)?;
+                        commands.push(Command::Assign {
+                            name: var_name,
+                            value: Value::Array(words),
+                        });
+                    }
+                    Expression::Literal(Literal::Map(pairs))
+                        if self.use_feature(Feature::AssocArrays) =>
+                    {
+                        let mut entries = Vec::new();
+                        for (key, value) in pairs {
+                            entries.push((self.lower_key(key)?, self.lower_word(value)?));
+                        }
+                        let builtin = self.assoc_declaration(name);
+                        if self.options.target == Target::Zsh {
+                            commands.push(Command::Declare {
+                                builtin,
+                                name: var_name.clone(),
+                                value: None,
+                            });
+                            let flat = entries.into_iter().flat_map(|(k, v)| [k, v]).collect();
+                            commands.push(Command::Assign {
+                                name: var_name,
+                                value: Value::Array(flat),
+                            });
+                        } else {
+                            commands.push(Command::Declare {
+                                builtin,
+                                name: var_name,
+                                value: Some(Value::Assoc(entries)),
+                            });
+                        }
+                    }
+                    Expression::FunctionCall { name: called, args }
+                        if called == "json_parse" && args.len() == 1 =>
+                    {
+                        self.lower_json_assignment(commands, name, &args[0])?;
+                    }
+                    Expression::FunctionCall { name: called, args }
+                        if self.enums.contains_key(called) && args.len() == 1 =>
+                    {
+                        let text = self.lower_word(&args[0])?;
+                        commands.push(self.validated_assignment(var_name, called, text));
+                    }
+                    // Builtins return arrays as lines. Newlines are IFS
+                    // whitespace, which would merge empty elements, so
+                    // they are split on a unit separator instead.
+                    Expression::FunctionCall { name, args }
+                        if sema::builtins::lookup(name).is_some_and(|builtin| {
+                            builtin.returns == Type::Array && builtin.accepts(args.len())
+                        }) && self.use_feature(Feature::IndexedArrays) =>
+                    {
+                        let lines = self.lower_word(value)?;
+                        let word = Word::substitution(vec![Command::Pipeline(vec![
+                            Command::simple(vec![Word::bare("printf"), Word::literal("%s"), lines]),
+                            Command::simple(vec![
+                                Word::bare("tr"),
+                                Word::literal("\\n"),
+                                Word::literal("\\037"),
+                            ]),
+                        ])])
+                        .split();
+                        let separator = Word::substitution(vec![Command::simple(vec![
+                            Word::bare("printf"),
+                            Word::literal("\\037"),
+                        ])]);
+                        let restore_ifs = Command::assign("IFS", Word::param(self.var("ifs")));
+                        commands.push(Command::assign(self.var("ifs"), Word::param("IFS")));
+                        commands.push(Command::assign("IFS", separator));
+                        commands.push(Command::Assign {
+                            name: var_name,
+                            value: Value::Array(vec![word]),
+                        });
+                        commands.push(restore_ifs);
+                    }
+                    _ => {
+                        let word = self.lower_word(value)?;
+                        commands.push(Command::assign(var_name, word));
+                    }
+                }
+                self.types.insert(self.variable(name), value_type);
+            }
+            Statement::Let { name, value } | Statement::Const { name, value } => {
+                let shell_name = self.declare(name);
+                let mut assignment = Vec::new();
+                self.lower_statement(
+                    &mut assignment,
+                    &Statement::Assignment {
+                        name: name.clone(),
+                        value: value.clone(),
+                    },
+                )?;
+                // A constant at the top of the script is assigned once,
+                // so the shell can enforce it.
+                let top = self.function.is_none() && self.scopes.len() == 1;
+                if matches!(statement, Statement::Const { .. }) && top {
+                    let words = assignment.iter().all(|command| {
+                        matches!(
+                            command,
+                            Command::Assign {
+                                value: Value::Word(_),
+                                ..
+                            }
+                        )
+                    });
+                    if words {
+                        // One assignment, or one per field of a struct.
+                        for command in &mut assignment {
+                            if let Command::Assign { name, value } = command {
+                                *command = Command::Declare {
+                                    builtin: "readonly",
+                                    name: std::mem::take(name),
+                                    value: Some(value.clone()),
+                                };
+                            }
+                        }
+                    } else {
+                        assignment.push(Command::Declare {
+                            builtin: "readonly",
+                            name: shell_name,
+                            value: None,
+                        });
+                    }
+                }
+                commands.extend(assignment);
+            }
+            Statement::Command { name, args } => match (sema::builtins::lookup(name), &args[..]) {
+                // A builtin called as a statement, such as `mkdir_p(dir)`.
+                (Some(builtin), [Expression::FunctionCall { name: called, args }])
+                    if called == name && builtin.accepts(args.len()) =>
+                {
+                    let command = match self.lower_builtin(builtin, args)? {
+                        Call::Status(command) => command,
+                        // Run a helper directly so its status, such as a
+                        // failed `parse_int`, is kept.
+                        Call::Value(mut word) => match word.segments.as_mut_slice() {
+                            [Segment::Substitution(body)] if body.len() == 1 => body.remove(0),
+                            _ => self.print_lines("%s\\n", vec![word]),
+                        },
+                    };
+                    // Logging is meant for the terminal, not the captured
+                    // output.
+                    if builtin.returns == Type::Void {
+                        commands.push(command);
+                    } else {
+                        self.capture(commands, command);
+                    }
+                }
+                // Any other call as a statement, such as `deploy(env)`, runs
+                // the command rather than passing it its own output.
+                (None, [Expression::FunctionCall { name: called, args }]) if called == name => {
+                    self.lower_command(commands, name, args)?
+                }
+                _ => self.lower_command(commands, name, args)?,
+            },
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition = self.lower_condition(condition)?;
+                let then_branch = self.lower_block(then_branch)?;
+                let else_branch = match else_branch {
+                    Some(body) => Some(self.lower_block(body)?),
+                    None => None,
+                };
+                commands.push(Command::If {
+                    condition: Box::new(condition),
+                    then_branch,
+                    else_branch,
+                });
+            }
+            Statement::While { condition, body } => {
+                let mut condition = self.lower_condition(condition)?;
+                // The conversions in the condition are checked each time.
+                if !self.conversions.is_empty() {
+                    let mut checked = std::mem::take(&mut self.conversions);
+                    checked.push(condition);
+                    condition = Command::Group {
+                        body: checked,
+                        redirects: Vec::new(),
+                    };
+                }
+                let body = self.lower_block(body)?;
+                commands.push(Command::While {
+                    condition: Box::new(condition),
+                    body,
+                    redirects: Vec::new(),
+                });
+            }
+            Statement::For {
+                index,
+                variable,
+                iterable,
+                body,
+            } => self.lower_for(commands, index.as_deref(), variable, iterable, body)?,
+            Statement::FunctionDef {
+                name,
+                params,
+                body,
+                return_value,
+            } => {
+                let function = self.lower_function(name, params, body, return_value.as_ref())?;
+                commands.extend(function);
+            }
+            Statement::Return { value } => {
+                let mut words = vec![Word::bare("return")];
+                if let Some(expr) = value {
+                    words.push(self.lower_word(expr)?);
+                }
+                commands.push(Command::simple(words));
+            }
+            Statement::Try {
+                body,
+                catch_var,
+                catch_body,
+            } => {
+                let catch_var = self.variable(catch_var);
+                let attempt = Command::Group {
+                    body: self.lower_block(body)?,
+                    redirects: vec![Redirect::Duplicate(2, 1)],
                 };
-                // For arithmetic, strip quotes from variable references
-                let left_clean = left_str.trim_matches('"').to_string();
-                let right_clean = right_str.trim_matches('"').to_string();
-                Ok(format!("$(({} {} {}))", left_clean, shell_op, right_clean))
+                let mut handler = vec![Command::assign(catch_var.clone(), Word::param("?"))];
+                handler.extend(self.lower_block(catch_body)?);
+                commands.push(Command::If {
+                    condition: Box::new(Command::assign(
+                        self.var("error"),
+                        Word::substitution(vec![attempt]),
+                    )),
+                    then_branch: vec![Command::assign(catch_var, Word::literal(""))],
+                    else_branch: Some(handler),
+                });
+            }
+            Statement::Match { value, arms } => {
+                let word = self.lower_word(value)?;
+                let mut cases = Vec::new();
+                for arm in arms {
+                    let patterns = arm
+                        .patterns
+                        .iter()
+                        .map(|pattern| match pattern {
+                            Pattern::Glob(glob) => glob_word(glob),
+                            Pattern::Wildcard => Word::bare("*"),
+                            Pattern::Variant { variant, .. } => glob_word(variant),
+                        })
+                        .collect();
+                    cases.push((patterns, self.lower_block(&arm.body)?));
+                }
+                commands.push(Command::Case { word, arms: cases });
+            }
+            // Only changes how the function's variables are named.
+            Statement::Global { .. } | Statement::Struct { .. } | Statement::Enum { .. } => {}
+            Statement::Break => commands.push(Command::simple(vec![Word::bare("break")])),
+            Statement::Continue => commands.push(Command::simple(vec![Word::bare("continue")])),
+            Statement::ExpressionStatement(expr) => {
+                let word = self.lower_word(expr)?;
+                commands.push(Command::simple(vec![word]));
+            }
+            Statement::Location { line, column } => {
+                if self.options.needs_locations() {
+                    commands.push(Command::Location {
+                        line: *line,
+                        column: *column,
+                    });
+                }
+                if self.options.line_markers {
+                    commands.push(Command::assign(
+                        self.var("line"),
+                        Word::bare(line.to_string()),
+                    ));
+                }
             }
         }
-        Expression::UnaryOp { op, expr } => {
-            let expr_str = generate_expression(expr)?;
-            match op {
-                crate::ast::UnaryOperator::Negate => Ok(format!("$((-{}))", expr_str)),
-                crate::ast::UnaryOperator::Not => Ok(format!("!{}", expr_str)),
+        Ok(())
+    }
+
+    /// Lower a condition for `if`/`while`. Comparisons become tests, `and`,
+    /// `or` and `not` become command lists, and any other value is tested
+    /// for being true.
+    fn lower_condition(&mut self, condition: &Expression) -> CodegenResult<Command> {
+        match condition {
+            Expression::BinaryOp { op, left, right } => {
+                if test_operator(op).is_some()
+                    && (self.type_of(left) == Type::Float || self.type_of(right) == Type::Float)
+                {
+                    return self.float_test(left, op, right);
+                }
+                if let Some(test_op) = test_operator(op) {
+                    let ordering =
+                        !matches!(op, BinaryOperator::Equals | BinaryOperator::NotEquals);
+                    if ordering
+                        && (self.type_of(left) == Type::String
+                            || self.type_of(right) == Type::String)
+                    {
+                        return self.text_order(left, op, right);
+                    }
+                    let test_op = match op {
+                        BinaryOperator::Equals if !self.numeric(left, right) => "=",
+                        BinaryOperator::NotEquals if !self.numeric(left, right) => "!=",
+                        _ => test_op,
+                    };
+                    let left = self.lower_word(left)?;
+                    let right = self.lower_word(right)?;
+                    return Ok(self.test(Test::Binary(left, test_op, right)));
+                }
+                match op {
+                    BinaryOperator::And => {
+                        return Ok(Command::And(
+                            Box::new(self.lower_condition(left)?),
+                            Box::new(self.lower_condition(right)?),
+                        ))
+                    }
+                    BinaryOperator::Or => {
+                        return Ok(Command::Or(
+                            Box::new(self.lower_condition(left)?),
+                            Box::new(self.lower_condition(right)?),
+                        ))
+                    }
+                    _ => {}
+                }
             }
+            Expression::UnaryOp {
+                op: UnaryOperator::Not,
+                expr,
+            } => return Ok(Command::Not(Box::new(self.lower_condition(expr)?))),
+            Expression::Command { name, args } => {
+                let mut words = vec![Word::bare(name)];
+                words.extend(self.lower_words(args)?);
+                return Ok(Command::simple(words));
+            }
+            Expression::FunctionCall { name, args } => {
+                if let Some(builtin) = sema::builtins::lookup(name) {
+                    if builtin.accepts(args.len()) {
+                        let kind = self.type_of(condition);
+                        return Ok(match self.lower_builtin(builtin, args)? {
+                            Call::Status(command) => command,
+                            Call::Value(word) => self.truth(word, kind),
+                        });
+                    }
+                }
+            }
+            Expression::Literal(Literal::Boolean(value)) => {
+                return Ok(Command::simple(vec![Word::bare(value.to_string())]))
+            }
+            _ => {}
         }
-        Expression::ArrayIndex { array, index } => {
-            let arr_str = generate_expression(array)?;
-            let idx_str = generate_expression(index)?;
-            Ok(format!("${{{}{}}}", arr_str, idx_str))
+        let kind = self.type_of(condition);
+        match (kind, condition) {
+            (Type::Float, _) => {
+                let zero = Expression::Literal(Literal::Float(0.0));
+                self.float_test(condition, &BinaryOperator::NotEquals, &zero)
+            }
+            (Type::Array, Expression::Identifier(name))
+                if self.use_feature(Feature::IndexedArrays) =>
+            {
+                let count = Word::new(vec![Segment::Count(self.variable(name))]);
+                Ok(self.test(Test::Binary(count, "-gt", Word::bare("0"))))
+            }
+            (Type::Map, Expression::Identifier(name)) if self.use_feature(Feature::AssocArrays) => {
+                let count = Word::new(vec![Segment::Count(self.variable(name))]);
+                Ok(self.test(Test::Binary(count, "-gt", Word::bare("0"))))
+            }
+            _ => {
+                let word = self.lower_word(condition)?;
+                Ok(self.truth(word, kind))
+            }
         }
-        Expression::MapIndex { map, key } => {
-            let map_str = generate_expression(map)?;
-            let key_str = generate_expression(key)?;
-            Ok(format!("${{{}{}}}", map_str, key_str))
+    }
+
+    /// Whether a value of type `kind` is true. Booleans are `true` or
+    /// `false`, numbers are true unless zero, and strings, arrays and maps
+    /// unless empty; `null` is the empty string, so it is false. A value of
+    /// unknown type, such as the output of a function, is false when it is
+    /// empty, `false` or `0`.
+    fn truth(&mut self, word: Word, kind: Type) -> Command {
+        match kind {
+            Type::Bool => self.test(Test::Binary(word, "=", Word::bare("true"))),
+            Type::Int => self.test(Test::Binary(word, "-ne", Word::bare("0"))),
+            Type::String | Type::Null | Type::Array | Type::Map => self.test(Test::Word(word)),
+            _ => Command::simple(vec![self.helper("truthy"), word]),
         }
-        Expression::FunctionCall { name, args } => {
-            let args_str: Vec<String> = args
-                .iter()
-                .map(|a| generate_expression(a))
-                .collect::<Result<Vec<_>, _>>()?;
-            match name.as_str() {
-                "len" if args.len() == 1 => Ok(format!("${{#{}}}", args_str[0])),
-                _ => Ok(format!("$({} {})", name, args_str.join(" "))),
+    }
+
+    /// `left op right` for an ordering of text, which `test` cannot do
+    /// portably. The `x` before each side keeps `expr` from comparing
+    /// numbers as numbers.
+    fn text_order(
+        &mut self,
+        left: &Expression,
+        op: &BinaryOperator,
+        right: &Expression,
+    ) -> CodegenResult<Command> {
+        let operator = match op {
+            BinaryOperator::Less => "<",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::LessEquals => "<=",
+            _ => ">=",
+        };
+        let text = |word: Word| {
+            let mut segments = vec![Segment::Bare("x".to_string())];
+            segments.extend(word.segments);
+            Word::new(segments)
+        };
+        let left = text(self.lower_word(left)?);
+        let right = text(self.lower_word(right)?);
+        Ok(Command::Simple {
+            words: vec![Word::bare("expr"), left, Word::literal(operator), right],
+            redirects: vec![Redirect::Output(1, Word::bare("/dev/null"))],
+        })
+    }
+
+    /// Whether `==` and `!=` compare as numbers: when one side is a number
+    /// and the other is not known to be something else. Anything else is
+    /// compared as text.
+    fn numeric(&self, left: &Expression, right: &Expression) -> bool {
+        match (self.type_of(left), self.type_of(right)) {
+            (left, right) if left.is_number() => right.is_number() || right == Type::Unknown,
+            (left, right) => right.is_number() && left == Type::Unknown,
+        }
+    }
+
+    fn lower_words(&mut self, elements: &[Expression]) -> CodegenResult<Vec<Word>> {
+        elements.iter().map(|e| self.lower_word(e)).collect()
+    }
+
+    /// Map keys written as bare identifiers (`{host: ...}`) are names, not variables.
+    fn lower_key(&mut self, key: &Expression) -> CodegenResult<Word> {
+        match key {
+            Expression::Identifier(name) => Ok(Word::literal(name.clone())),
+            _ => self.lower_word(key),
+        }
+    }
+
+    fn lower_arith(&mut self, expr: &Expression) -> CodegenResult<Arith> {
+        match expr {
+            Expression::Literal(Literal::Integer(n)) => Ok(Arith::Number(*n)),
+            Expression::Identifier(name) => Ok(Arith::Param(self.variable(name))),
+            Expression::Field { .. } => Ok(Arith::Param(self.value_variable(expr)?)),
+            Expression::BinaryOp { op, left, right } if arith_operator(op).is_some() => {
+                Ok(Arith::Binary(
+                    Box::new(self.lower_arith(left)?),
+                    arith_operator(op).unwrap_or(ArithOp::Add),
+                    Box::new(self.lower_arith(right)?),
+                ))
             }
+            Expression::UnaryOp {
+                op: UnaryOperator::Negate,
+                expr,
+            } => Ok(Arith::Negate(Box::new(self.lower_arith(expr)?))),
+            _ => Ok(Arith::Word(self.lower_word(expr)?)),
         }
-        Expression::InterpolatedString { parts } => {
-            let parts_str: Vec<String> = parts
-                .iter()
-                .map(|p| generate_expression(p))
-                .collect::<Result<Vec<_>, _>>()?;
-            Ok(format!("\"{}\"", parts_str.join("")))
+    }
+
+    /// `true` or `false` according to a condition, for conditions used as
+    /// values.
+    fn boolean_word(&mut self, condition: &Expression) -> CodegenResult<Word> {
+        let condition = self.lower_condition(condition)?;
+        Ok(self.boolean(condition))
+    }
+
+    /// `true` or `false` according to the status of a command.
+    fn boolean(&self, condition: Command) -> Word {
+        let echo = |value: &str| Command::simple(vec![Word::bare("echo"), Word::bare(value)]);
+        Word::substitution(vec![Command::If {
+            condition: Box::new(condition),
+            then_branch: vec![echo("true")],
+            else_branch: Some(vec![echo("false")]),
+        }])
+    }
+
+    /// `printf` with one line per word, the POSIX array representation.
+    fn print_lines(&mut self, format: &str, words: Vec<Word>) -> Command {
+        let mut printf = vec![Word::bare("printf"), Word::literal(format)];
+        printf.extend(words);
+        Command::simple(printf)
+    }
+
+    fn lower_word(&mut self, expr: &Expression) -> CodegenResult<Word> {
+        match expr {
+            Expression::Literal(literal) => match literal {
+                Literal::Integer(n) => Ok(Word::bare(n.to_string())),
+                Literal::Float(f) => Ok(Word::bare(format!("{:?}", f))),
+                Literal::Boolean(true) => Ok(Word::bare("true")),
+                Literal::Boolean(false) => Ok(Word::bare("false")),
+                Literal::Null => Ok(Word::literal("")),
+                Literal::String(s) => Ok(Word::literal(s.clone())),
+                Literal::Array(elements) => {
+                    if elements.is_empty() {
+                        return Ok(Word::literal(""));
+                    }
+                    let words = self.lower_words(elements)?;
+                    Ok(Word::substitution(vec![self.print_lines("%s\\n", words)]))
+                }
+                Literal::Map(pairs) => {
+                    if pairs.is_empty() {
+                        return Ok(Word::literal(""));
+                    }
+                    let mut words = Vec::new();
+                    for (key, value) in pairs {
+                        words.push(self.lower_key(key)?);
+                        words.push(self.lower_word(value)?);
+                    }
+                    Ok(Word::substitution(
+                        vec![self.print_lines("%s=%s\\n", words)],
+                    ))
+                }
+            },
+            Expression::Identifier(_) => Ok(Word::param(self.value_variable(expr)?)),
+            Expression::BinaryOp {
+                op: BinaryOperator::Add,
+                left,
+                right,
+            } if self.type_of(expr) == Type::String => {
+                let mut segments = self.lower_word(left)?.segments;
+                segments.extend(self.lower_word(right)?.segments);
+                Ok(Word::new(segments))
+            }
+            Expression::BinaryOp { op, .. } if arith_operator(op).is_some() => {
+                if self.type_of(expr) == Type::Float {
+                    return self.lower_float(expr);
+                }
+                Ok(Word::arithmetic(self.lower_arith(expr)?))
+            }
+            Expression::BinaryOp { .. } => self.boolean_word(expr),
+            Expression::UnaryOp { op, expr: operand } => match op {
+                UnaryOperator::Negate => match operand.as_ref() {
+                    Expression::Literal(Literal::Float(f)) => Ok(Word::bare(format!("-{:?}", f))),
+                    _ if self.type_of(expr) == Type::Float => self.lower_float(expr),
+                    _ => Ok(Word::arithmetic(Arith::Negate(Box::new(
+                        self.lower_arith(operand)?,
+                    )))),
+                },
+                UnaryOperator::Not => self.boolean_word(expr),
+            },
+            Expression::ArrayIndex { array, index } => {
+                let index = self.lower_arith(index)?;
+                match array.as_ref() {
+                    Expression::Identifier(name) if self.use_feature(Feature::IndexedArrays) => {
+                        Ok(Word::new(vec![Segment::Index(
+                            self.variable(name),
+                            Subscript::Position(index),
+                        )]))
+                    }
+                    _ => {
+                        let array = self.lower_word(array)?;
+                        let index = match index {
+                            Arith::Number(n) => Word::bare(n.to_string()),
+                            index => Word::arithmetic(index),
+                        };
+                        Ok(Word::substitution(vec![Command::simple(vec![
+                            self.helper("index"),
+                            array,
+                            index,
+                        ])]))
+                    }
+                }
+            }
+            Expression::MapIndex { map, key } => {
+                let key = self.lower_key(key)?;
+                match map.as_ref() {
+                    Expression::Identifier(name) if self.use_feature(Feature::AssocArrays) => {
+                        Ok(Word::new(vec![Segment::Index(
+                            self.variable(name),
+                            Subscript::Key(key),
+                        )]))
+                    }
+                    _ => {
+                        let map = self.lower_word(map)?;
+                        Ok(Word::substitution(vec![Command::simple(vec![
+                            self.helper("lookup"),
+                            map,
+                            key,
+                        ])]))
+                    }
+                }
+            }
+            Expression::FunctionCall { name, args } => match sema::builtins::lookup(name) {
+                Some(builtin) if builtin.accepts(args.len()) => {
+                    Ok(match self.lower_builtin(builtin, args)? {
+                        Call::Value(word) => word,
+                        Call::Status(command) => self.boolean(command),
+                    })
+                }
+                _ if self.enums.contains_key(name) && args.len() == 1 => {
+                    let text = self.lower_word(&args[0])?;
+                    Ok(self.checked_conversion(name, text))
+                }
+                _ => {
+                    let mut words = vec![self.callee(name)];
+                    words.extend(self.lower_arguments(name, args)?);
+                    Ok(Word::substitution(vec![Command::simple(words)]))
+                }
+            },
+            Expression::NamedArgument { name, .. } => Err(CodegenError::UnsupportedNode {
+                node_type: format!("named argument '{}' outside a call to a function", name),
+            }),
+            Expression::StructLiteral { name, .. } => Err(CodegenError::UnsupportedNode {
+                node_type: format!("{} literal outside an assignment or argument", name),
+            }),
+            Expression::Field { .. } => match self.variant(expr) {
+                Some(variant) => Ok(Word::literal(variant?)),
+                None => Ok(Word::param(self.value_variable(expr)?)),
+            },
+            Expression::InterpolatedString { parts } => {
+                let mut segments = Vec::new();
+                for part in parts {
+                    segments.extend(self.lower_word(part)?.segments);
+                }
+                Ok(Word::new(segments))
+            }
+            Expression::Range { .. } => Err(CodegenError::UnsupportedNode {
+                node_type: "range outside a for loop".to_string(),
+            }),
+            Expression::Length { expr } => self.lower_length(expr),
+            Expression::Command { .. } => self.boolean_word(expr),
+            Expression::Lambda {
+                params,
+                body,
+                return_value,
+            } => self.lower_lambda(params, body, return_value.as_deref()),
         }
-        Expression::Range { start, end } => {
-            let start_str = generate_expression(start)?;
-            let end_str = generate_expression(end)?;
-            Ok(format!("$(({}-{}))", start_str, end_str))
+    }
+
+    /// Number of elements of an array, or characters of a string.
+    fn lower_length(&mut self, expr: &Expression) -> CodegenResult<Word> {
+        match expr {
+            Expression::Literal(Literal::String(s)) => {
+                Ok(Word::bare(s.chars().count().to_string()))
+            }
+            Expression::Literal(Literal::Array(elements)) => {
+                Ok(Word::bare(elements.len().to_string()))
+            }
+            Expression::Identifier(name) if self.type_of(expr) == Type::String => {
+                Ok(Word::new(vec![Segment::Length(self.variable(name))]))
+            }
+            Expression::Identifier(name) if self.use_feature(Feature::IndexedArrays) => {
+                Ok(Word::new(vec![Segment::Count(self.variable(name))]))
+            }
+            _ if self.type_of(expr) == Type::String => {
+                let value = self.lower_word(expr)?;
+                Ok(Word::substitution(vec![Command::simple(vec![
+                    self.helper("length"),
+                    value,
+                ])]))
+            }
+            _ => {
+                let value = self.lower_word(expr)?;
+                Ok(Word::substitution(vec![Command::simple(vec![
+                    self.helper("len"),
+                    value,
+                ])]))
+            }
         }
-        Expression::Length { expr } => {
-            let expr_str = generate_expression(expr)?;
-            Ok(format!("${{#{}}}", expr_str))
+    }
+
+    fn lower_command(
+        &mut self,
+        commands: &mut Vec<Command>,
+        name: &str,
+        args: &[Expression],
+    ) -> CodegenResult<()> {
+        let mut words = vec![self.callee(name)];
+        words.extend(self.lower_arguments(name, args)?);
+        self.capture(commands, Command::simple(words));
+        Ok(())
+    }
+
+    /// Run `command` with its output and status kept in `__shard_stdout`,
+    /// `__shard_stderr` and `__shard_status`.
+    fn capture(&mut self, commands: &mut Vec<Command>, command: Command) {
+        let (stdout_tmp, stderr_tmp) = self.capture_files();
+        let status = self.var("status");
+        let mktemp = || Word::substitution(vec![Command::simple(vec![Word::bare("mktemp")])]);
+        let read = |file: &str| {
+            Word::substitution(vec![Command::Simple {
+                words: vec![Word::bare("cat")],
+                redirects: vec![Redirect::Input(0, Word::param(file))],
+            }])
+        };
+
+        commands.push(Command::assign(stdout_tmp.clone(), mktemp()));
+        commands.push(Command::assign(stderr_tmp.clone(), mktemp()));
+        let redirects = vec![
+            Redirect::Output(1, Word::param(stdout_tmp.clone())),
+            Redirect::Output(2, Word::param(stderr_tmp.clone())),
+        ];
+        commands.push(match command {
+            Command::Simple {
+                words,
+                redirects: own,
+            } if own.is_empty() => Command::Simple { words, redirects },
+            command => Command::Group {
+                body: vec![command],
+                redirects,
+            },
+        });
+        commands.push(Command::assign(status.clone(), Word::param("?")));
+        if self.options.line_markers && !self.features.contains(&Feature::ErrTrap) {
+            commands.push(Command::Or(
+                Box::new(Command::Test {
+                    test: Test::Binary(Word::param(status.clone()), "-eq", Word::bare("0")),
+                    double: false,
+                }),
+                Box::new(Command::simple(vec![
+                    self.helper("report"),
+                    Word::literal("command failed"),
+                    Word::param(status),
+                ])),
+            ));
         }
+        commands.push(Command::assign(self.var("stdout"), read(&stdout_tmp)));
+        commands.push(Command::assign(self.var("stderr"), read(&stderr_tmp)));
+        commands.push(Command::simple(vec![
+            Word::bare("rm"),
+            Word::bare("-f"),
+            Word::param(stdout_tmp),
+            Word::param(stderr_tmp),
+        ]));
     }
 }
 
-fn generate_command(output: &mut String, name: &str, args: &[Expression]) -> CodegenResult<()> {
-    let args_str: Vec<String> = args
-        .iter()
-        .map(|arg| generate_expression(arg))
-        .collect::<Result<Vec<_>, _>>()?;
+/// Whether `statement` lowers to shell code, rather than only changing
+/// how other code is generated.
+fn emits_code(statement: &Statement) -> bool {
+    !matches!(
+        statement,
+        Statement::Location { .. }
+            | Statement::Global { .. }
+            | Statement::Struct { .. }
+            | Statement::Enum { .. }
+    )
+}
 
-    output.push_str("__shard_stdout_tmp=$(mktemp)\n");
-    output.push_str("__shard_stderr_tmp=$(mktemp)\n");
+/// Whether `statement` has a body of its own.
+fn is_block(statement: &Statement) -> bool {
+    matches!(
+        statement,
+        Statement::If { .. }
+            | Statement::While { .. }
+            | Statement::For { .. }
+            | Statement::FunctionDef { .. }
+            | Statement::Try { .. }
+            | Statement::Match { .. }
+    )
+}
 
-    let cmd_line = if args_str.is_empty() {
-        format!("{}", name)
-    } else {
-        format!("{} {}", name, args_str.join(" "))
-    };
+/// The test operator for a comparison.
+fn test_operator(op: &BinaryOperator) -> Option<&'static str> {
+    match op {
+        BinaryOperator::Equals => Some("-eq"),
+        BinaryOperator::NotEquals => Some("-ne"),
+        BinaryOperator::Less => Some("-lt"),
+        BinaryOperator::Greater => Some("-gt"),
+        BinaryOperator::LessEquals => Some("-le"),
+        BinaryOperator::GreaterEquals => Some("-ge"),
+        _ => None,
+    }
+}
 
-    output.push_str(&format!(
-        "{} > \"$__shard_stdout_tmp\" 2> \"$__shard_stderr_tmp\"\n",
-        cmd_line
-    ));
-    output.push_str("__shard_status=$?\n");
-    output.push_str("__shard_stdout=$(cat < \"$__shard_stdout_tmp\")\n");
-    output.push_str("__shard_stderr=$(cat < \"$__shard_stderr_tmp\")\n");
-    output.push_str("rm -f \"$__shard_stdout_tmp\" \"$__shard_stderr_tmp\"\n");
+fn arith_operator(op: &BinaryOperator) -> Option<ArithOp> {
+    match op {
+        BinaryOperator::Add => Some(ArithOp::Add),
+        BinaryOperator::Subtract => Some(ArithOp::Subtract),
+        BinaryOperator::Multiply => Some(ArithOp::Multiply),
+        BinaryOperator::Divide => Some(ArithOp::Divide),
+        BinaryOperator::Modulo => Some(ArithOp::Modulo),
+        _ => None,
+    }
+}
 
-    Ok(())
+/// A glob as a `case` pattern: `*`, `?` and simple bracket expressions
+/// such as `[0-9]` stay unquoted, everything else matches literally.
+fn glob_word(glob: &str) -> Word {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut rest = glob;
+    while let Some(c) = rest.chars().next() {
+        let wildcard = match c {
+            '*' | '?' => Some(c.len_utf8()),
+            '[' => rest[1..].find(']').map(|end| end + 2).filter(|&end| {
+                end > 2
+                    && rest[1..end - 1]
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '!' | '_' | '.'))
+            }),
+            _ => None,
+        };
+        match wildcard {
+            Some(len) => {
+                if !literal.is_empty() {
+                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                segments.push(Segment::Bare(rest[..len].to_string()));
+                rest = &rest[len..];
+            }
+            None => {
+                literal.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    if !literal.is_empty() || segments.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+    Word::new(segments)
 }
 
 #[cfg(test)]
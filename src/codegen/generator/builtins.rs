@@ -0,0 +1,150 @@
+//! Lowering of calls to builtin functions.
+
+use super::Generator;
+use crate::ast::Expression;
+use crate::codegen::compliance::Feature;
+use crate::codegen::error::CodegenResult;
+use crate::codegen::ir::{Command, Redirect, Segment, Test, Word};
+use crate::sema::builtins::Builtin;
+use crate::sema::Type;
+
+/// How the result of a builtin call reaches the caller.
+pub(super) enum Call {
+    /// A word that expands to the result.
+    Value(Word),
+    /// A command whose exit status is the result, for `bool` and `void`
+    /// builtins.
+    Status(Command),
+}
+
+impl Generator<'_> {
+    /// A call to a builtin. Most run the runtime helper of the same name;
+    /// the rest map onto a single command.
+    pub(super) fn lower_builtin(
+        &mut self,
+        builtin: &'static Builtin,
+        args: &[Expression],
+    ) -> CodegenResult<Call> {
+        let call = match builtin.name {
+            "len" => Call::Value(self.lower_length(&args[0])?),
+            "exists" => Call::Status(self.path_test("-e", &args[0])?),
+            "is_file" => Call::Status(self.path_test("-f", &args[0])?),
+            "is_dir" => Call::Status(self.path_test("-d", &args[0])?),
+            "read_file" => Call::Value(Word::substitution(vec![self.tool("cat", &[], args)?])),
+            "basename" => Call::Value(Word::substitution(vec![self.tool(
+                "basename",
+                &[],
+                args,
+            )?])),
+            "dirname" => Call::Value(Word::substitution(vec![self.tool("dirname", &[], args)?])),
+            "mkdir_p" => Call::Status(self.tool("mkdir", &["-p"], args)?),
+            "remove" => Call::Status(self.tool("rm", &["-rf"], args)?),
+            "copy" => Call::Status(self.tool("cp", &["-R"], args)?),
+            "move" => Call::Status(self.tool("mv", &[], args)?),
+            // Arrays are passed around as lines already.
+            "lines" => Call::Value(self.lower_word(&args[0])?),
+            "json_parse" => Call::Value(self.lower_json_parse(&args[0])?),
+            "json_stringify" => Call::Value(self.lower_json_stringify(&args[0])?),
+            "write_file" | "append_file" => {
+                let path = self.lower_word(&args[0])?;
+                let content = self.lower_word(&args[1])?;
+                let redirect = if builtin.name == "write_file" {
+                    Redirect::Output(1, path)
+                } else {
+                    Redirect::Append(1, path)
+                };
+                Call::Status(Command::Simple {
+                    words: vec![Word::bare("printf"), Word::literal("%s"), content],
+                    redirects: vec![redirect],
+                })
+            }
+            "map" | "filter" | "reduce" | "each" | "sort_by" => {
+                // `map` prints what the function prints for each element.
+                let helper = if builtin.name == "map" {
+                    "each"
+                } else {
+                    builtin.name
+                };
+                let mut words = vec![
+                    self.helper(helper),
+                    self.lower_callable(&args[1])?,
+                    self.lower_list(&args[0])?,
+                ];
+                if let Some(initial) = args.get(2) {
+                    words.push(self.lower_word(initial)?);
+                }
+                let command = Command::simple(words);
+                if builtin.returns == Type::Void {
+                    Call::Status(command)
+                } else {
+                    Call::Value(Word::substitution(vec![command]))
+                }
+            }
+            "log.debug" | "log.info" | "log.warn" | "log.error" => {
+                let message = self.lower_word(&args[0])?;
+                Call::Status(Command::simple(vec![
+                    self.helper("log"),
+                    Word::bare(&builtin.name[4..]),
+                    message,
+                ]))
+            }
+            name => {
+                if matches!(name, "tempfile" | "tempdir") {
+                    self.temporaries = true;
+                }
+                if matches!(name, "prompt" | "confirm" | "select" | "password") {
+                    self.interactive = true;
+                }
+                let mut words = vec![self.helper(builtin.name)];
+                for (i, arg) in args.iter().enumerate() {
+                    words.push(if matches!((name, i), ("join", 0) | ("select", 1)) {
+                        self.lower_list(arg)?
+                    } else {
+                        self.lower_word(arg)?
+                    });
+                }
+                let command = Command::simple(words);
+                if matches!(builtin.returns, Type::Bool | Type::Void) {
+                    Call::Status(command)
+                } else {
+                    Call::Value(Word::substitution(vec![command]))
+                }
+            }
+        };
+        Ok(call)
+    }
+
+    /// `[ -flag path ]`.
+    fn path_test(&mut self, flag: &'static str, path: &Expression) -> CodegenResult<Command> {
+        let path = self.lower_word(path)?;
+        Ok(self.test(Test::Unary(flag, path)))
+    }
+
+    /// `name options -- args...`, so arguments starting with `-` are paths.
+    fn tool(
+        &mut self,
+        name: &str,
+        options: &[&str],
+        args: &[Expression],
+    ) -> CodegenResult<Command> {
+        let mut words = vec![Word::bare(name)];
+        words.extend(options.iter().map(|option| Word::bare(*option)));
+        words.push(Word::bare("--"));
+        words.extend(self.lower_words(args)?);
+        Ok(Command::simple(words))
+    }
+
+    /// An array as newline-separated elements, the form runtime helpers
+    /// take arrays in.
+    pub(super) fn lower_list(&mut self, expr: &Expression) -> CodegenResult<Word> {
+        match expr {
+            Expression::Identifier(name) if self.use_feature(Feature::IndexedArrays) => {
+                let elements = Word::new(vec![Segment::Elements(self.variable(name))]);
+                Ok(Word::substitution(vec![
+                    self.print_lines("%s\\n", vec![elements])
+                ]))
+            }
+            _ => self.lower_word(expr),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Enums, held as the names of their variants: `Env.Prod` is the string
+//! `Prod`, and text becomes an `Env` only after it is checked.
+
+use super::Generator;
+use crate::ast::Expression;
+use crate::codegen::error::{CodegenError, CodegenResult};
+use crate::codegen::ir::{Command, Word};
+
+impl Generator<'_> {
+    /// The string a variant such as `Env.Prod` stands for, or `None` when
+    /// `expr` is not a variant.
+    pub(super) fn variant(&self, expr: &Expression) -> Option<CodegenResult<String>> {
+        let Expression::Field { object, field } = expr else {
+            return None;
+        };
+        let Expression::Identifier(name) = object.as_ref() else {
+            return None;
+        };
+        let variants = self.enums.get(name)?;
+        Some(if variants.contains(field) {
+            Ok(field.clone())
+        } else {
+            Err(CodegenError::UnknownVariant {
+                enumeration: name.clone(),
+                variant: field.clone(),
+            })
+        })
+    }
+
+    /// `Name(text)`: `text` when it is a variant of `enumeration`, and an
+    /// error and status 1 otherwise.
+    fn lower_enum_conversion(&mut self, enumeration: &str, text: Word) -> Word {
+        let mut words = vec![
+            self.helper("enum"),
+            Word::literal(enumeration.to_string()),
+            text,
+        ];
+        words.extend(self.enums[enumeration].iter().cloned().map(Word::literal));
+        Word::substitution(vec![Command::simple(words)])
+    }
+
+    /// The conversion of `text` within an expression, checked into a
+    /// temporary before the statement it is part of runs.
+    pub(super) fn checked_conversion(&mut self, enumeration: &str, text: Word) -> Word {
+        self.conversion_count += 1;
+        let name = self.var(&format!("conversion_{}", self.conversion_count));
+        let check = self.validated_assignment(name.clone(), enumeration, text);
+        self.conversions.push(check);
+        Word::param(name)
+    }
+
+    /// Assign `name` the conversion of `text`, exiting when it is not a
+    /// variant, so the script never runs with an invalid value.
+    pub(super) fn validated_assignment(
+        &mut self,
+        name: String,
+        enumeration: &str,
+        text: Word,
+    ) -> Command {
+        let value = self.lower_enum_conversion(enumeration, text);
+        Command::Or(
+            Box::new(Command::assign(name, value)),
+            Box::new(Command::simple(vec![Word::bare("exit"), Word::bare("1")])),
+        )
+    }
+}
@@ -0,0 +1,308 @@
+//! Functions defined with `fn`: binding their parameters and passing the
+//! arguments of calls to them.
+
+use super::Generator;
+use crate::ast::{Expression, Param, Statement};
+use crate::codegen::compliance::{is_posix_name, Feature};
+use crate::codegen::error::CodegenResult;
+use crate::codegen::ir::{Command, Segment, Test, Value, Word};
+use crate::sema::{self, Type};
+use std::collections::HashMap;
+
+impl Generator<'_> {
+    /// Remember the parameters of the functions `statements` define, so
+    /// calls can pass named arguments in order, the fields of the structs
+    /// they pass, and the variants of the enums they take.
+    pub(super) fn collect_functions(&mut self, statements: &[Statement]) {
+        for (name, params) in sema::functions(statements) {
+            self.functions.insert(name.to_string(), params.to_vec());
+        }
+        for (name, fields) in sema::structs(statements) {
+            self.structs.insert(name.to_string(), fields.to_vec());
+        }
+        for (name, variants) in sema::enums(statements) {
+            self.enums.insert(name.to_string(), variants.to_vec());
+        }
+        let recursive = sema::scope::recursive(statements);
+        self.recursive
+            .extend(recursive.into_iter().map(str::to_string));
+    }
+
+    /// A function defined with `fn`. Its variables get names of their own,
+    /// declared `local` where the target has it. Elsewhere a function that
+    /// can call itself saves them on entry and restores them on exit, so
+    /// each call has its own.
+    pub(super) fn lower_function(
+        &mut self,
+        name: &str,
+        params: &[Param],
+        body: &[Statement],
+        return_value: Option<&Expression>,
+    ) -> CodegenResult<Vec<Command>> {
+        if !is_posix_name(name) {
+            self.require(
+                Feature::ExtendedFunctionNames,
+                format!("function name '{}'", name),
+            )?;
+        }
+        let local = self.use_feature(Feature::Local);
+        let prefix: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let prefix = self.var(&prefix);
+        let locals = sema::scope::locals(params, body);
+        let scope: HashMap<String, String> = locals
+            .iter()
+            .map(|local| (local.clone(), format!("{}_{}", prefix, local)))
+            .collect();
+
+        // The body sees the variables of the script, not those of the
+        // blocks around the definition.
+        let outer = (
+            self.function.replace(prefix.clone()),
+            self.captures,
+            std::mem::replace(&mut self.scopes, vec![scope]),
+            std::mem::take(&mut self.declared),
+        );
+        self.captures = false;
+        let bindings = self.lower_params(params, local);
+        let lowered = self.lower_block(body);
+        let value = return_value.map(|value| self.lower_word(value)).transpose();
+        let conversions = std::mem::take(&mut self.conversions);
+
+        // A struct is its fields.
+        let mut names = Vec::new();
+        for local in &locals {
+            names.extend(self.flattened(&self.variable(local)));
+        }
+        for declared in std::mem::take(&mut self.declared) {
+            names.extend(self.flattened(&declared));
+        }
+        let bound = params
+            .iter()
+            .flat_map(|param| self.flattened(&self.variable(&param.name)))
+            .count();
+        if self.captures {
+            let (stdout_tmp, stderr_tmp) = self.capture_files();
+            names.extend([stdout_tmp, stderr_tmp]);
+        }
+        (self.function, self.captures, self.scopes, self.declared) = outer;
+
+        let mut function_body = bindings?;
+        if local {
+            function_body.extend(names[bound..].iter().map(|name| Command::Declare {
+                builtin: "local",
+                name: name.clone(),
+                value: None,
+            }));
+        }
+        function_body.extend(lowered?);
+        function_body.extend(conversions);
+        if let Some(value) = value? {
+            function_body.push(Command::simple(vec![Word::bare("echo"), value]));
+        }
+
+        if !local && self.recursive.contains(name) && !names.is_empty() {
+            let run = format!("{}_run", prefix);
+            let saved: Vec<Word> = names.into_iter().map(Word::bare).collect();
+            let mut enter = vec![self.helper("enter")];
+            enter.extend(saved.clone());
+            let mut leave = vec![self.helper("leave")];
+            leave.extend(saved);
+            let mut call = vec![Word::bare(run.clone())];
+            call.push(Word::new(vec![Segment::Param("@".to_string())]));
+            return Ok(vec![
+                Command::Function {
+                    name: run,
+                    body: function_body,
+                },
+                Command::Blank,
+                Command::Function {
+                    name: name.to_string(),
+                    body: vec![
+                        Command::simple(enter),
+                        Command::simple(call),
+                        // Keep the status while the variables are restored.
+                        Command::simple(vec![
+                            Word::bare("set"),
+                            Word::bare("--"),
+                            Word::param("?"),
+                        ]),
+                        Command::simple(leave),
+                        Command::simple(vec![Word::bare("return"), Word::param("1")]),
+                    ],
+                },
+            ]);
+        }
+        Ok(vec![Command::Function {
+            name: name.to_string(),
+            body: function_body,
+        }])
+    }
+
+    /// Bind the arguments of a call to the parameters.
+    fn lower_params(&mut self, params: &[Param], local: bool) -> CodegenResult<Vec<Command>> {
+        let bind = |name: String, value: Value| {
+            if local {
+                Command::Declare {
+                    builtin: "local",
+                    name,
+                    value: Some(value),
+                }
+            } else {
+                Command::Assign { name, value }
+            }
+        };
+
+        // Bind positional arguments to parameter names. An argument that
+        // is missing or empty takes the default. A struct takes one
+        // argument per field, and an enum is checked to be a variant.
+        let mut function_body = Vec::new();
+        let mut count = 0;
+        for param in params.iter().filter(|param| !param.rest) {
+            if let Some(structure) = param
+                .ty
+                .as_ref()
+                .filter(|ty| self.structs.contains_key(*ty))
+            {
+                let structure = structure.clone();
+                for leaf in self.leaves(&structure) {
+                    count += 1;
+                    let name = self.variable(&format!("{}.{}", param.name, leaf));
+                    function_body.push(bind(name, Value::Word(Word::param(count.to_string()))));
+                }
+                self.register(&param.name, &structure);
+                continue;
+            }
+            count += 1;
+            let position = count.to_string();
+            let value = match &param.default {
+                Some(default) => {
+                    let default = self.lower_word(default)?;
+                    function_body.append(&mut self.conversions);
+                    Word::new(vec![Segment::Default(position, default)])
+                }
+                None => Word::param(position),
+            };
+            let name = self.variable(&param.name);
+            function_body.push(bind(name.clone(), Value::Word(value)));
+            let ty = param.ty.as_deref().unwrap_or_default();
+            if self.enums.contains_key(ty) {
+                let text = Word::param(name.clone());
+                function_body.push(self.validated_assignment(name, ty, text));
+            } else if let Some(ty) = Type::value(ty) {
+                self.types.insert(name, ty);
+            }
+        }
+
+        if let Some(rest) = params.iter().find(|param| param.rest) {
+            if count > 0 {
+                // `shift` fails when there are fewer arguments.
+                let more = self.test(Test::Binary(
+                    Word::param("#"),
+                    "-gt",
+                    Word::bare(count.to_string()),
+                ));
+                function_body.push(Command::If {
+                    condition: Box::new(more),
+                    then_branch: vec![Command::simple(vec![
+                        Word::bare("shift"),
+                        Word::bare(count.to_string()),
+                    ])],
+                    else_branch: Some(vec![Command::simple(vec![
+                        Word::bare("set"),
+                        Word::bare("--"),
+                    ])]),
+                });
+            }
+            let arguments = Word::param("@");
+            let value = if self.use_feature(Feature::IndexedArrays) {
+                Value::Array(vec![arguments])
+            } else {
+                Value::Word(Word::substitution(vec![
+                    self.print_lines("%s\\n", vec![arguments])
+                ]))
+            };
+            function_body.push(bind(self.variable(&rest.name), value));
+            self.types.insert(self.variable(&rest.name), Type::Array);
+        }
+
+        Ok(function_body)
+    }
+
+    /// The words passed to a function: positional arguments as written,
+    /// then named ones in the place of their parameter. A parameter
+    /// skipped before a named argument is passed empty, so it takes its
+    /// default.
+    pub(super) fn lower_arguments(
+        &mut self,
+        name: &str,
+        args: &[Expression],
+    ) -> CodegenResult<Vec<Word>> {
+        let Some(params) = self.functions.get(name).cloned() else {
+            return self.lower_words(args);
+        };
+        let (named, positional): (Vec<_>, Vec<_>) = args
+            .iter()
+            .partition(|arg| matches!(arg, Expression::NamedArgument { .. }));
+        let fixed: Vec<&Param> = params.iter().filter(|param| !param.rest).collect();
+        let mut words = Vec::new();
+        for (i, arg) in positional.iter().enumerate() {
+            match fixed.get(i) {
+                Some(param) => words.extend(self.lower_argument(name, i, param, arg)?),
+                None => words.push(self.lower_word(arg)?),
+            }
+        }
+
+        let mut later = Vec::new();
+        for (i, param) in fixed.iter().enumerate().skip(positional.len()) {
+            let value = named.iter().find_map(|arg| match arg {
+                Expression::NamedArgument { name, value } if *name == param.name => Some(value),
+                _ => None,
+            });
+            later.push(match value {
+                Some(value) => Some(self.lower_argument(name, i, param, value)?),
+                None => None,
+            });
+        }
+        while matches!(later.last(), Some(None)) {
+            later.pop();
+        }
+        words.extend(
+            later
+                .into_iter()
+                .flat_map(|word| word.unwrap_or_else(|| vec![Word::literal("")])),
+        );
+        Ok(words)
+    }
+
+    /// The words passing `arg` for the parameter at `index`.
+    fn lower_argument(
+        &mut self,
+        function: &str,
+        index: usize,
+        param: &Param,
+        arg: &Expression,
+    ) -> CodegenResult<Vec<Word>> {
+        match &param.ty {
+            Some(structure) if self.structs.contains_key(structure) => {
+                self.lower_struct_argument(function, index + 1, structure, arg)
+            }
+            _ => Ok(vec![self.lower_word(arg)?]),
+        }
+    }
+
+    /// The temporary files a captured command writes to. Each function has
+    /// its own, so a command it runs cannot remove those of its caller.
+    pub(super) fn capture_files(&mut self) -> (String, String) {
+        self.captures = true;
+        match &self.function {
+            Some(prefix) => (
+                format!("{}_stdout_tmp", prefix),
+                format!("{}_stderr_tmp", prefix),
+            ),
+            None => (self.var("stdout_tmp"), self.var("stderr_tmp")),
+        }
+    }
+}
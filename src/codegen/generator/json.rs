@@ -0,0 +1,138 @@
+//! Lowering of `json_parse` and `json_stringify` onto the `json` runtime
+//! helper.
+//!
+//! A parsed object or array is stored like a map or array literal. The
+//! compact JSON it came from is kept next to it, since a variable holding
+//! lines cannot tell a map from an array when it is stringified again.
+
+use super::Generator;
+use crate::ast::Expression;
+use crate::codegen::compliance::Feature;
+use crate::codegen::error::CodegenResult;
+use crate::codegen::ir::{Command, Segment, Subscript, Word};
+use crate::sema::Type;
+
+impl Generator<'_> {
+    /// The variable holding the compact JSON that `name` was parsed from.
+    /// Struct fields are flattened to `__` and a name, which cannot start
+    /// with a digit, so no field has the same shell name.
+    fn json_source(&self, name: &str) -> String {
+        format!("{}__0json", self.variable(name))
+    }
+
+    /// `__shard_json mode args...`.
+    fn json(&mut self, mode: &str, args: Vec<Word>) -> Word {
+        let mut words = vec![self.helper("json"), Word::bare(mode)];
+        words.extend(args);
+        Word::substitution(vec![Command::simple(words)])
+    }
+
+    /// The entries of a parsed object or array, one per line, with the
+    /// variable holding the JSON it came from. `json_parse(text)` used
+    /// directly keeps its JSON in `source`.
+    pub(super) fn json_entries(
+        &mut self,
+        commands: &mut Vec<Command>,
+        value: &Expression,
+        source: String,
+    ) -> CodegenResult<(Word, String)> {
+        let source = match value {
+            Expression::Identifier(name) => self.json_source(name),
+            Expression::FunctionCall { args, .. } => {
+                let text = self.lower_word(&args[0])?;
+                let compact = self.json("compact", vec![text]);
+                commands.push(Command::assign(source.clone(), compact));
+                source
+            }
+            _ => unreachable!("only variables and json_parse calls hold JSON"),
+        };
+        let entries = self.json("parse", vec![Word::param(source.clone())]);
+        Ok((entries, source))
+    }
+
+    /// The entries of the JSON text `text`, one per line.
+    pub(super) fn lower_json_parse(&mut self, text: &Expression) -> CodegenResult<Word> {
+        let text = self.lower_word(text)?;
+        Ok(self.json("parse", vec![text]))
+    }
+
+    /// `name = json_parse(text)`. Native targets declare an associative
+    /// array for objects and an indexed one for arrays, which is only known
+    /// once the text is read.
+    pub(super) fn lower_json_assignment(
+        &mut self,
+        commands: &mut Vec<Command>,
+        name: &str,
+        text: &Expression,
+    ) -> CodegenResult<()> {
+        let source = self.json_source(name);
+        let text = self.lower_word(text)?;
+        let compact = self.json("compact", vec![text]);
+        commands.push(Command::assign(source.clone(), compact));
+
+        let var_name = self.variable(name);
+        if self.use_feature(Feature::AssocArrays) {
+            let declare = self.assoc_declaration(name);
+            let code = self.json(
+                "declare",
+                vec![
+                    Word::bare(var_name.clone()),
+                    Word::literal(declare),
+                    Word::param(source),
+                ],
+            );
+            commands.push(Command::simple(vec![
+                Word::bare("unset"),
+                Word::bare(var_name),
+            ]));
+            commands.push(Command::simple(vec![Word::bare("eval"), code]));
+        } else {
+            let entries = self.json("parse", vec![Word::param(source)]);
+            commands.push(Command::assign(var_name, entries));
+        }
+        Ok(())
+    }
+
+    /// `value` as JSON text, shaped by its inferred type.
+    pub(super) fn lower_json_stringify(&mut self, value: &Expression) -> CodegenResult<Word> {
+        let (kind, lines) = match (self.type_of(value), value) {
+            (Type::Json, Expression::Identifier(name)) => {
+                return Ok(Word::param(self.json_source(name)))
+            }
+            (Type::Json, Expression::FunctionCall { args, .. }) => {
+                let text = self.lower_word(&args[0])?;
+                return Ok(self.json("compact", vec![text]));
+            }
+            (Type::Map, _) => ("object", self.lower_map(value)?),
+            (Type::Array, _) => ("array", self.lower_list(value)?),
+            (Type::Int | Type::Float, _) => ("number", self.lower_word(value)?),
+            (kind @ (Type::Bool | Type::Null | Type::String), _) => {
+                (kind.name(), self.lower_word(value)?)
+            }
+            _ => ("auto", self.lower_word(value)?),
+        };
+        Ok(self.json("stringify", vec![Word::bare(kind), lines]))
+    }
+
+    /// A map as `key=value` lines, the form runtime helpers take maps in.
+    fn lower_map(&mut self, expr: &Expression) -> CodegenResult<Word> {
+        match expr {
+            Expression::Identifier(name) if self.use_feature(Feature::AssocArrays) => {
+                let var_name = self.variable(name);
+                let key = self.var("key");
+                let keys = self.map_keys(&var_name);
+                let value = Word::new(vec![Segment::Index(
+                    var_name,
+                    Subscript::Key(Word::param(key.clone())),
+                )]);
+                let print = self.print_lines("%s=%s\\n", vec![Word::param(key.clone()), value]);
+                Ok(Word::substitution(vec![Command::For {
+                    variable: key,
+                    words: vec![keys],
+                    body: vec![print],
+                }]))
+            }
+            _ => self.lower_word(expr),
+        }
+    }
+}
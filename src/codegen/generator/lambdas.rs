@@ -0,0 +1,73 @@
+//! Lambdas, hoisted to shell functions, and the builtins that call them.
+
+use super::Generator;
+use crate::ast::{Expression, Statement};
+use crate::codegen::error::CodegenResult;
+use crate::codegen::ir::{Command, Word};
+use crate::sema::Type;
+use std::collections::HashMap;
+
+impl Generator<'_> {
+    /// Define a lambda as a uniquely named function and return its name.
+    ///
+    /// Parameters get names of their own so they cannot overwrite the
+    /// variables of the caller. Other variables the body uses are the
+    /// caller's, read when the lambda runs.
+    pub(super) fn lower_lambda(
+        &mut self,
+        params: &[String],
+        body: &[Statement],
+        return_value: Option<&Expression>,
+    ) -> CodegenResult<Word> {
+        self.lambda_count += 1;
+        let name = self.var(&format!("lambda_{}", self.lambda_count));
+
+        let mut scope = HashMap::new();
+        let mut function_body = Vec::new();
+        for (i, param) in params.iter().enumerate() {
+            let shell_name = format!("{}_{}", name, param);
+            function_body.push(Command::assign(
+                shell_name.clone(),
+                Word::param((i + 1).to_string()),
+            ));
+            scope.insert(param.clone(), shell_name);
+        }
+        self.scopes.push(scope);
+        let lowered = self.lower_block(body);
+        // Those of the statement around the lambda stay with it.
+        let outer = std::mem::take(&mut self.conversions);
+        let value = return_value.map(|value| self.lower_word(value)).transpose();
+        let conversions = std::mem::replace(&mut self.conversions, outer);
+        self.scopes.pop();
+
+        function_body.extend(lowered?);
+        function_body.extend(conversions);
+        if let Some(value) = value? {
+            function_body.push(self.print_lines("%s\\n", vec![value]));
+        }
+        self.lambdas.push(Command::Function {
+            name: name.clone(),
+            body: function_body,
+        });
+        self.lambdas.push(Command::Blank);
+        Ok(Word::bare(name))
+    }
+
+    /// The function a higher-order builtin calls: a lambda, or a function
+    /// defined with `fn` and passed by name.
+    pub(super) fn lower_callable(&mut self, expr: &Expression) -> CodegenResult<Word> {
+        match expr {
+            Expression::Identifier(name) => Ok(self.callee(name)),
+            _ => self.lower_word(expr),
+        }
+    }
+
+    /// The word that calls `name`: the function a variable holding a
+    /// lambda names, or else the function `name` itself.
+    pub(super) fn callee(&self, name: &str) -> Word {
+        match self.type_of(&Expression::Identifier(name.to_string())) {
+            Type::Fn => Word::param(self.variable(name)),
+            _ => Word::bare(name),
+        }
+    }
+}
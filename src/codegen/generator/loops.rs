@@ -0,0 +1,268 @@
+//! Lowering of `for` loops: over arrays, over ranges of numbers and over
+//! the lines of a string.
+
+use super::Generator;
+use crate::ast::{Expression, Literal, Statement};
+use crate::codegen::compliance::Feature;
+use crate::codegen::error::CodegenResult;
+use crate::codegen::ir::{Arith, ArithOp, Command, Redirect, Segment, Test, Word};
+use crate::sema::Type;
+
+impl Generator<'_> {
+    /// The shell name of a variable the loop over `variable` keeps `what`
+    /// in. Inside a function it is saved with the function's variables, so
+    /// the same loop in a call made from the body cannot change it.
+    fn loop_temporary(&mut self, variable: &str, what: &str) -> String {
+        let name = format!("{}_{}", variable, what);
+        if self.function.is_some() && !self.declared.contains(&name) {
+            self.declared.push(name.clone());
+        }
+        name
+    }
+
+    pub(super) fn lower_for(
+        &mut self,
+        commands: &mut Vec<Command>,
+        index: Option<&str>,
+        variable: &str,
+        iterable: &Expression,
+        body: &[Statement],
+    ) -> CodegenResult<()> {
+        // The index is incremented first thing in the body so `continue`
+        // cannot skip it.
+        let mut loop_body = Vec::new();
+        if let Some(index) = index {
+            let counter = self.variable(index);
+            commands.push(Command::assign(counter.clone(), Word::bare("-1")));
+            loop_body.push(increment(&counter, Arith::Number(1)));
+            self.types.insert(counter, Type::Int);
+        }
+        let name = variable;
+        let variable = self.variable(name);
+        match iterable {
+            Expression::Range {
+                start,
+                end,
+                inclusive,
+                step,
+            } => {
+                self.types.insert(variable.clone(), Type::Int);
+                let step = match step {
+                    Some(step) => self.lower_arith(step)?,
+                    None => Arith::Number(1),
+                };
+                let start = self.lower_arith(start)?;
+                let end = match end.as_ref() {
+                    Expression::Literal(_) | Expression::Identifier(_) => self.lower_word(end)?,
+                    // Anything else is only evaluated once.
+                    _ => {
+                        let last = self.loop_temporary(&variable, "end");
+                        let word = self.lower_word(end)?;
+                        commands.push(Command::assign(last.clone(), word));
+                        Word::param(last)
+                    }
+                };
+                // Start one step early and step before each test, so
+                // `continue` moves on to the next number.
+                let (step, test) = match constant(&step) {
+                    Some(by) => {
+                        let op = match (by < 0, inclusive) {
+                            (false, false) => "-lt",
+                            (false, true) => "-le",
+                            (true, false) => "-gt",
+                            (true, true) => "-ge",
+                        };
+                        let first = match constant(&start) {
+                            Some(start) => Word::bare((start - by).to_string()),
+                            None => Word::arithmetic(subtract(start, step.clone())),
+                        };
+                        commands.push(Command::assign(variable.clone(), first));
+                        let test = Test::Binary(Word::param(variable.clone()), op, end);
+                        (step, test)
+                    }
+                    // A step known only at run time is evaluated once, and
+                    // counts down when negative: the number has not passed
+                    // the end while the distance left has the sign of the
+                    // step.
+                    None => {
+                        let by = self.loop_temporary(&variable, "step");
+                        commands.push(Command::assign(by.clone(), Word::arithmetic(step)));
+                        let nonzero = self.test(Test::Binary(
+                            Word::param(by.clone()),
+                            "-ne",
+                            Word::bare("0"),
+                        ));
+                        commands.push(Command::Or(
+                            Box::new(nonzero),
+                            Box::new(Command::simple(vec![
+                                self.helper("die"),
+                                Word::literal("for: the step of a range is 0"),
+                            ])),
+                        ));
+                        let step = Arith::Param(by);
+                        let first = subtract(start, step.clone());
+                        commands.push(Command::assign(variable.clone(), Word::arithmetic(first)));
+                        let left = Word::arithmetic(Arith::Binary(
+                            Box::new(subtract(Arith::Word(end), Arith::Param(variable.clone()))),
+                            ArithOp::Multiply,
+                            Box::new(step.clone()),
+                        ));
+                        let op = if *inclusive { "-ge" } else { "-gt" };
+                        (step, Test::Binary(left, op, Word::bare("0")))
+                    }
+                };
+                let test = self.test(test);
+                loop_body.extend(self.lower_block(body)?);
+                commands.push(Command::While {
+                    condition: Box::new(Command::And(
+                        Box::new(increment(&variable, step)),
+                        Box::new(test),
+                    )),
+                    body: loop_body,
+                    redirects: Vec::new(),
+                });
+            }
+            // Lines are read from a temporary file rather than a pipe, which
+            // would run the loop in a subshell and lose its assignments. The
+            // file is read on fd 3, leaving stdin to the body.
+            Expression::FunctionCall { name: called, args }
+                if called == "lines" && args.len() == 1 =>
+            {
+                let lines = self.loop_temporary(&variable, "lines");
+                let text = self.lower_word(&args[0])?;
+                commands.push(Command::assign(
+                    lines.clone(),
+                    Word::substitution(vec![Command::simple(vec![Word::bare("mktemp")])]),
+                ));
+                commands.push(Command::Simple {
+                    words: vec![Word::bare("printf"), Word::literal("%s"), text],
+                    redirects: vec![Redirect::Output(1, Word::param(lines.clone()))],
+                });
+                // The last line may not end in a newline.
+                let read = Command::Simple {
+                    words: vec![
+                        Word::bare("IFS="),
+                        Word::bare("read"),
+                        Word::bare("-r"),
+                        Word::bare(variable.clone()),
+                    ],
+                    redirects: vec![Redirect::DuplicateInput(0, 3)],
+                };
+                let unfinished = self.test(Test::Unary("-n", Word::param(variable)));
+                loop_body.extend(self.lower_block(body)?);
+                commands.push(Command::While {
+                    condition: Box::new(Command::Or(Box::new(read), Box::new(unfinished))),
+                    body: loop_body,
+                    redirects: vec![Redirect::Input(3, Word::param(lines.clone()))],
+                });
+                commands.push(Command::simple(vec![
+                    Word::bare("rm"),
+                    Word::bare("-f"),
+                    Word::param(lines),
+                ]));
+            }
+            Expression::Literal(Literal::Array(elements)) => {
+                let words = self.lower_words(elements)?;
+                loop_body.extend(self.lower_block(body)?);
+                commands.push(Command::For {
+                    variable,
+                    words,
+                    body: loop_body,
+                });
+            }
+            // A map yields its keys, whatever the target holds it as.
+            Expression::Identifier(map)
+                if self.type_of(iterable) == Type::Map
+                    && self.use_feature(Feature::AssocArrays) =>
+            {
+                let words = vec![self.map_keys(&self.variable(map))];
+                loop_body.extend(self.lower_block(body)?);
+                commands.push(Command::For {
+                    variable,
+                    words,
+                    body: loop_body,
+                });
+            }
+            Expression::Identifier(array)
+                if self.type_of(iterable) != Type::Json
+                    && self.use_feature(Feature::IndexedArrays) =>
+            {
+                let words = vec![Word::new(vec![Segment::Elements(self.variable(array))])];
+                loop_body.extend(self.lower_block(body)?);
+                commands.push(Command::For {
+                    variable,
+                    words,
+                    body: loop_body,
+                });
+            }
+            // Anything else is split on newlines, the POSIX array
+            // representation.
+            _ => {
+                let kind = self.type_of(iterable);
+                let (entries, json) = if kind == Type::Json {
+                    let source = self.loop_temporary(&variable, "json");
+                    let (entries, source) = self.json_entries(commands, iterable, source)?;
+                    (entries, Some(source))
+                } else {
+                    (self.lower_word(iterable)?, None)
+                };
+                let words = vec![entries.split()];
+                let restore_ifs = Command::assign("IFS", Word::param(self.var("ifs")));
+                loop_body.insert(0, restore_ifs.clone());
+                // The lines of a map are `key=value`; keep the key. Parsed
+                // JSON is an object or an array, as its text shows.
+                let key = Command::assign(
+                    variable.clone(),
+                    Word::bare(format!("\"${{{}%%=*}}\"", variable)),
+                );
+                if kind == Type::Map {
+                    loop_body.insert(1, key);
+                } else if let Some(source) = json {
+                    loop_body.insert(
+                        1,
+                        Command::Case {
+                            word: Word::param(source),
+                            arms: vec![(vec![Word::bare("'{'*")], vec![key])],
+                        },
+                    );
+                }
+                loop_body.extend(self.lower_block(body)?);
+                commands.push(Command::assign(self.var("ifs"), Word::param("IFS")));
+                commands.push(Command::assign("IFS", Word::literal("\n")));
+                commands.push(Command::For {
+                    variable,
+                    words,
+                    body: loop_body,
+                });
+                commands.push(restore_ifs);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `name=$((name + by))`.
+fn increment(name: &str, by: Arith) -> Command {
+    Command::assign(
+        name,
+        Word::arithmetic(Arith::Binary(
+            Box::new(Arith::Param(name.to_string())),
+            ArithOp::Add,
+            Box::new(by),
+        )),
+    )
+}
+
+/// `left - right`.
+fn subtract(left: Arith, right: Arith) -> Arith {
+    Arith::Binary(Box::new(left), ArithOp::Subtract, Box::new(right))
+}
+
+/// The value of an arithmetic expression that is a number.
+fn constant(arith: &Arith) -> Option<i64> {
+    match arith {
+        Arith::Number(n) => Some(*n),
+        Arith::Negate(arith) => constant(arith).map(|n| -n),
+        _ => None,
+    }
+}
@@ -0,0 +1,112 @@
+//! Float arithmetic and comparisons, which `$(( ))` and `test` cannot do,
+//! lowered to `awk`.
+
+use super::Generator;
+use crate::ast::{BinaryOperator, Expression, Literal, UnaryOperator};
+use crate::codegen::error::CodegenResult;
+use crate::codegen::ir::{Command, Segment, Word};
+
+impl Generator<'_> {
+    /// The value of a float arithmetic expression.
+    pub(super) fn lower_float(&mut self, expr: &Expression) -> CodegenResult<Word> {
+        let mut operands = Vec::new();
+        let value = self.awk_expression(expr, &mut operands)?;
+        let program = format!("BEGIN {{ printf \"%.15g\\n\", {} }}", value);
+        Ok(Word::substitution(vec![self.awk(program, operands)]))
+    }
+
+    /// A command that succeeds when a comparison of numbers holds.
+    pub(super) fn float_test(
+        &mut self,
+        left: &Expression,
+        op: &BinaryOperator,
+        right: &Expression,
+    ) -> CodegenResult<Command> {
+        let mut operands = Vec::new();
+        let left = self.awk_expression(left, &mut operands)?;
+        let right = self.awk_expression(right, &mut operands)?;
+        let program = format!(
+            "BEGIN {{ exit !({} {} {}) }}",
+            left,
+            awk_operator(op),
+            right
+        );
+        Ok(self.awk(program, operands))
+    }
+
+    /// `expr` in awk syntax. Anything but literals and arithmetic is
+    /// computed by the shell and passed in as `v1`, `v2`, ...
+    fn awk_expression(
+        &mut self,
+        expr: &Expression,
+        operands: &mut Vec<Word>,
+    ) -> CodegenResult<String> {
+        let operand = |generator: &mut Self, expr: &Expression, operands: &mut Vec<Word>| {
+            let text = generator.awk_expression(expr, operands)?;
+            Ok::<_, crate::codegen::error::CodegenError>(match expr {
+                Expression::BinaryOp { .. } => format!("({})", text),
+                _ => text,
+            })
+        };
+        match expr {
+            Expression::Literal(Literal::Integer(n)) => Ok(n.to_string()),
+            Expression::Literal(Literal::Float(f)) => Ok(format!("{:?}", f)),
+            Expression::BinaryOp { op, left, right } if is_arithmetic(op) => Ok(format!(
+                "{} {} {}",
+                operand(self, left, operands)?,
+                awk_operator(op),
+                operand(self, right, operands)?
+            )),
+            Expression::UnaryOp {
+                op: UnaryOperator::Negate,
+                expr,
+            } => Ok(format!("-{}", operand(self, expr, operands)?)),
+            _ => {
+                operands.push(self.lower_word(expr)?);
+                Ok(format!("v{}", operands.len()))
+            }
+        }
+    }
+
+    /// `awk -v v1=... 'program'`.
+    fn awk(&self, program: String, operands: Vec<Word>) -> Command {
+        let mut words = vec![Word::bare("awk")];
+        for (i, operand) in operands.into_iter().enumerate() {
+            let mut assignment = vec![Segment::Bare(format!("v{}=", i + 1))];
+            assignment.extend(operand.segments);
+            words.push(Word::bare("-v"));
+            words.push(Word::new(assignment));
+        }
+        words.push(Word::literal(program));
+        Command::simple(words)
+    }
+}
+
+fn is_arithmetic(op: &BinaryOperator) -> bool {
+    matches!(
+        op,
+        BinaryOperator::Add
+            | BinaryOperator::Subtract
+            | BinaryOperator::Multiply
+            | BinaryOperator::Divide
+            | BinaryOperator::Modulo
+    )
+}
+
+fn awk_operator(op: &BinaryOperator) -> &'static str {
+    match op {
+        BinaryOperator::Add => "+",
+        BinaryOperator::Subtract => "-",
+        BinaryOperator::Multiply => "*",
+        BinaryOperator::Divide => "/",
+        BinaryOperator::Modulo => "%",
+        BinaryOperator::Equals => "==",
+        BinaryOperator::NotEquals => "!=",
+        BinaryOperator::Less => "<",
+        BinaryOperator::Greater => ">",
+        BinaryOperator::LessEquals => "<=",
+        BinaryOperator::GreaterEquals => ">=",
+        BinaryOperator::And => "&&",
+        BinaryOperator::Or => "||",
+    }
+}
@@ -0,0 +1,161 @@
+//! Structs, flattened into one variable per field: `s.host` is
+//! `__shard_s__host`.
+
+use super::Generator;
+use crate::ast::{Expression, Literal, Statement};
+use crate::codegen::error::{CodegenError, CodegenResult};
+use crate::codegen::ir::{Command, Word};
+use crate::sema::Type;
+
+impl Generator<'_> {
+    /// The struct `expr` is a value of: the struct of a literal, or of
+    /// the variable or field it reads.
+    pub(super) fn struct_of(&self, expr: &Expression) -> Option<String> {
+        match expr {
+            Expression::StructLiteral { name, .. } => Some(name.clone()),
+            _ => self.records.get(&self.variable(&expr.path()?)).cloned(),
+        }
+    }
+
+    /// The fields of `structure` that hold values, with the fields of
+    /// nested structs as paths such as `owner.name`.
+    pub(super) fn leaves(&self, structure: &str) -> Vec<String> {
+        let mut leaves = Vec::new();
+        for field in &self.structs[structure] {
+            if self.structs.contains_key(&field.ty) {
+                let nested = self.leaves(&field.ty);
+                leaves.extend(
+                    nested
+                        .into_iter()
+                        .map(|leaf| format!("{}.{}", field.name, leaf)),
+                );
+            } else {
+                leaves.push(field.name.clone());
+            }
+        }
+        leaves
+    }
+
+    /// The shell names of the variables holding `shell_name`: itself, or
+    /// the fields of the struct it holds.
+    pub(super) fn flattened(&self, shell_name: &str) -> Vec<String> {
+        match self.records.get(shell_name) {
+            Some(structure) => self
+                .leaves(structure)
+                .into_iter()
+                .map(|leaf| format!("{}__{}", shell_name, leaf.replace('.', "__")))
+                .collect(),
+            None => vec![shell_name.to_string()],
+        }
+    }
+
+    /// Remember that the variable or field `name` holds a `structure`,
+    /// with the types of its fields.
+    pub(super) fn register(&mut self, name: &str, structure: &str) {
+        self.records
+            .insert(self.variable(name), structure.to_string());
+        for field in self.structs[structure].clone() {
+            let path = format!("{}.{}", name, field.name);
+            if self.structs.contains_key(&field.ty) {
+                self.register(&path, &field.ty);
+            } else if let Some(ty) = Type::value(&field.ty) {
+                self.types.insert(self.variable(&path), ty);
+            }
+        }
+    }
+
+    /// `name = value` for a struct value: each field is assigned in turn.
+    pub(super) fn lower_struct_assignment(
+        &mut self,
+        commands: &mut Vec<Command>,
+        name: &str,
+        structure: &str,
+        value: &Expression,
+    ) -> CodegenResult<()> {
+        for field in self.structs[structure].clone() {
+            self.lower_statement(
+                commands,
+                &Statement::Assignment {
+                    name: format!("{}.{}", name, field.name),
+                    value: field_value(value, &field.name),
+                },
+            )?;
+        }
+        self.register(name, structure);
+        Ok(())
+    }
+
+    /// The shell name of the variable or field `expr` reads, after
+    /// checking a struct has the field and that it holds a value rather
+    /// than another struct.
+    pub(super) fn value_variable(&self, expr: &Expression) -> CodegenResult<String> {
+        if let Expression::Field { object, field } = expr {
+            let Some(structure) = self.struct_of(object) else {
+                return Err(CodegenError::NotAStruct {
+                    name: object.path().unwrap_or_else(|| "value".to_string()),
+                });
+            };
+            if !self.structs[&structure]
+                .iter()
+                .any(|known| &known.name == field)
+            {
+                return Err(CodegenError::UnknownField {
+                    structure,
+                    field: field.clone(),
+                });
+            }
+        }
+        let path = expr.path().unwrap_or_default();
+        let shell_name = self.variable(&path);
+        if let Some(structure) = self.records.get(&shell_name) {
+            return Err(CodegenError::StructValue {
+                name: path,
+                field: self.structs[structure][0].name.clone(),
+            });
+        }
+        Ok(shell_name)
+    }
+
+    /// The words passing `arg` as a `structure`: one per field.
+    pub(super) fn lower_struct_argument(
+        &mut self,
+        function: &str,
+        position: usize,
+        structure: &str,
+        arg: &Expression,
+    ) -> CodegenResult<Vec<Word>> {
+        if self.struct_of(arg).as_deref() != Some(structure) {
+            return Err(CodegenError::StructArgument {
+                function: function.to_string(),
+                position,
+                structure: structure.to_string(),
+            });
+        }
+        let mut words = Vec::new();
+        for field in self.structs[structure].clone() {
+            let value = field_value(arg, &field.name);
+            if self.structs.contains_key(&field.ty) {
+                words.extend(self.lower_struct_argument(function, position, &field.ty, &value)?);
+            } else {
+                words.push(self.lower_word(&value)?);
+            }
+        }
+        Ok(words)
+    }
+}
+
+/// The value of `field` in a struct value: what a literal gives it, or
+/// the field of the variable.
+fn field_value(value: &Expression, field: &str) -> Expression {
+    match value {
+        Expression::StructLiteral { fields, .. } => fields
+            .iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, value)| value.clone())
+            .unwrap_or(Expression::Literal(Literal::Null)),
+        _ => Expression::Field {
+            object: Box::new(value.clone()),
+            field: field.to_string(),
+        },
+    }
+}
@@ -0,0 +1,223 @@
+//! A typed model of the shell the generator emits.
+//!
+//! The generator lowers the AST into these types and `printer` renders
+//! them, so decisions about quoting, grouping and layout are made from
+//! structure rather than by inspecting already printed text.
+
+/// One shell word: segments printed back to back with no separator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Word {
+    pub segments: Vec<Segment>,
+    /// Leave expansions unquoted so the shell splits them into fields.
+    pub split: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    /// Text taken literally, quoted as needed when printed.
+    Literal(String),
+    /// Text the shell must see as is: names, numbers, options, globs.
+    Bare(String),
+    /// `$name`, a variable or special parameter.
+    Param(String),
+    /// `${name:-word}`, the parameter, or `word` when it is unset or empty.
+    Default(String, Word),
+    /// `${name[@]}`, every element of an indexed array.
+    Elements(String),
+    /// `${!name[@]}`, the keys of an associative array.
+    Keys(String),
+    /// `${#name[@]}`, the number of elements of an array.
+    Count(String),
+    /// `${#name}`, the number of characters of a variable.
+    Length(String),
+    /// `${name[subscript]}`.
+    Index(String, Subscript),
+    /// `$( ... )`.
+    Substitution(Vec<Command>),
+    /// `$(( ... ))`.
+    Arithmetic(Arith),
+}
+
+/// The subscript of an array element.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Subscript {
+    /// An indexed array position, evaluated arithmetically.
+    Position(Arith),
+    /// An associative array key.
+    Key(Word),
+}
+
+/// An arithmetic expression, printed inside `$(( ))` or a subscript.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arith {
+    Number(i64),
+    /// `$name`.
+    Param(String),
+    Binary(Box<Arith>, ArithOp, Box<Arith>),
+    Negate(Box<Arith>),
+    /// Any other word, such as a command substitution.
+    Word(Word),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+}
+
+/// The operands of a `[ ]` or `[[ ]]` test.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Test {
+    /// True when the word is not empty.
+    Word(Word),
+    Unary(&'static str, Word),
+    Binary(Word, &'static str, Word),
+}
+
+/// The value of an assignment.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Word(Word),
+    /// `(a b c)`.
+    Array(Vec<Word>),
+    /// `([k]=v ...)`, the bash associative array form.
+    Assoc(Vec<(Word, Word)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Redirect {
+    /// `fd> target`, or `> target` for standard output.
+    Output(u32, Word),
+    /// `fd>> target`.
+    Append(u32, Word),
+    /// `fd< target`, or `< target` for standard input.
+    Input(u32, Word),
+    /// `fd>&to`.
+    Duplicate(u32, u32),
+    /// `fd<&from`, or `<&from` for standard input.
+    DuplicateInput(u32, u32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Simple {
+        words: Vec<Word>,
+        redirects: Vec<Redirect>,
+    },
+    /// `name=value`.
+    Assign {
+        name: String,
+        value: Value,
+    },
+    /// `builtin name=value`, e.g. `local` or `declare -gA`.
+    Declare {
+        builtin: &'static str,
+        name: String,
+        value: Option<Value>,
+    },
+    /// `[ ... ]`, or `[[ ... ]]` when `double` is set.
+    Test {
+        test: Test,
+        double: bool,
+    },
+    Not(Box<Command>),
+    And(Box<Command>, Box<Command>),
+    Or(Box<Command>, Box<Command>),
+    Pipeline(Vec<Command>),
+    /// `{ ...; }` with redirections applied to the whole group.
+    Group {
+        body: Vec<Command>,
+        redirects: Vec<Redirect>,
+    },
+    If {
+        condition: Box<Command>,
+        then_branch: Vec<Command>,
+        else_branch: Option<Vec<Command>>,
+    },
+    /// `while condition; do body; done`, with redirections applied to
+    /// the whole loop.
+    While {
+        condition: Box<Command>,
+        body: Vec<Command>,
+        redirects: Vec<Redirect>,
+    },
+    For {
+        variable: String,
+        words: Vec<Word>,
+        body: Vec<Command>,
+    },
+    /// `case word in pattern | pattern) body ;; ... esac`.
+    Case {
+        word: Word,
+        arms: Vec<(Vec<Word>, Vec<Command>)>,
+    },
+    Function {
+        name: String,
+        body: Vec<Command>,
+    },
+    /// Shell source printed as is, such as a runtime helper. Each two
+    /// leading spaces of a line count as one level of indentation.
+    Raw(String),
+    /// An empty line between the code of consecutive statements.
+    Blank,
+    /// Where the following code came from in the Shard source.
+    Location {
+        line: usize,
+        column: usize,
+    },
+}
+
+impl Word {
+    pub fn new(segments: Vec<Segment>) -> Self {
+        Word {
+            segments,
+            split: false,
+        }
+    }
+
+    pub fn literal(text: impl Into<String>) -> Self {
+        Word::new(vec![Segment::Literal(text.into())])
+    }
+
+    pub fn bare(text: impl Into<String>) -> Self {
+        Word::new(vec![Segment::Bare(text.into())])
+    }
+
+    pub fn param(name: impl Into<String>) -> Self {
+        Word::new(vec![Segment::Param(name.into())])
+    }
+
+    pub fn substitution(commands: Vec<Command>) -> Self {
+        Word::new(vec![Segment::Substitution(commands)])
+    }
+
+    pub fn arithmetic(arith: Arith) -> Self {
+        Word::new(vec![Segment::Arithmetic(arith)])
+    }
+
+    /// The same word with its expansions left unquoted.
+    pub fn split(mut self) -> Self {
+        self.split = true;
+        self
+    }
+}
+
+impl Command {
+    /// A command with no redirections.
+    pub fn simple(words: Vec<Word>) -> Self {
+        Command::Simple {
+            words,
+            redirects: Vec::new(),
+        }
+    }
+
+    pub fn assign(name: impl Into<String>, value: Word) -> Self {
+        Command::Assign {
+            name: name.into(),
+            value: Value::Word(value),
+        }
+    }
+}
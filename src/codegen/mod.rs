@@ -1,5 +1,20 @@
+pub mod compliance;
 pub mod error;
 pub mod generator;
+pub mod ir;
+pub mod options;
+pub mod printer;
+pub mod runtime;
+pub mod source_map;
+pub mod target;
 
+pub use compliance::{Compliance, ComplianceWarning, Feature};
 pub use error::{CodegenError, CodegenResult};
-pub use generator::generate;
+pub use generator::{
+    generate, generate_runtime, generate_script, generate_statements, generate_with_options, Script,
+};
+pub use options::CodegenOptions;
+pub use printer::Printer;
+pub use runtime::{Helper, Runtime, RUNTIME_FILE};
+pub use source_map::{Mapping, SourceMap};
+pub use target::Target;
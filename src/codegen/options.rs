@@ -0,0 +1,60 @@
+use crate::codegen::compliance::Compliance;
+use crate::codegen::runtime::Runtime;
+use crate::codegen::target::Target;
+
+/// Settings that control how a program is lowered to shell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodegenOptions {
+    pub target: Target,
+    pub compliance: Compliance,
+    /// Name of the Shard source, used in source maps and runtime reports.
+    pub source_name: Option<String>,
+    /// Build a `SourceMap` from generated lines to Shard locations.
+    pub source_map: bool,
+    /// Set `__shard_line` before each statement and report the Shard line
+    /// of failing commands and of a non-zero exit on stderr.
+    pub line_markers: bool,
+    /// Spaces per nesting level of compound commands.
+    pub indent: usize,
+    /// Quote the Shard source line of each statement in a comment above
+    /// its code. Needs `source` and a program from `parse_with_locations`.
+    pub source_comments: bool,
+    /// Text of the Shard source, for `source_comments`.
+    pub source: Option<String>,
+    /// Drop indentation, blank lines and comments, and use a shorter
+    /// prefix than `__shard_` for generated names.
+    pub minify: bool,
+    /// Define the runtime helpers in the script or source them.
+    pub runtime: Runtime,
+}
+
+impl Default for CodegenOptions {
+    fn default() -> Self {
+        CodegenOptions {
+            target: Target::default(),
+            compliance: Compliance::default(),
+            source_name: None,
+            source_map: false,
+            line_markers: false,
+            indent: 2,
+            source_comments: false,
+            source: None,
+            minify: false,
+            runtime: Runtime::default(),
+        }
+    }
+}
+
+impl CodegenOptions {
+    pub fn for_target(target: Target) -> Self {
+        CodegenOptions {
+            target,
+            ..CodegenOptions::default()
+        }
+    }
+
+    /// Whether the program should be parsed with `parse_with_locations`.
+    pub fn needs_locations(&self) -> bool {
+        self.source_map || self.line_markers || self.source_comments
+    }
+}
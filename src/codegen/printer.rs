@@ -0,0 +1,544 @@
+//! Renders the shell IR as text.
+
+use crate::codegen::ir::{
+    Arith, ArithOp, Command, Redirect, Segment, Subscript, Test, Value, Word,
+};
+use crate::codegen::options::CodegenOptions;
+use std::cell::Cell;
+
+/// Starts a line recording the Shard location of the code that follows.
+/// NUL never occurs in generated shell, so these lines cannot be confused
+/// with real output; the generator removes them after printing.
+pub(crate) const LOCATION_MARKER: &str = "\0shard:";
+
+/// Where a word appears, which decides how its expansions are quoted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Context {
+    /// A command argument, where unquoted expansions would be split.
+    Argument,
+    /// The right-hand side of `name=value`, which is never split.
+    Assignment,
+}
+
+#[derive(Debug, Clone)]
+pub struct Printer {
+    /// Prefix added per nesting level of compound commands.
+    pub indent: String,
+    /// Keep the empty lines between the code of consecutive statements.
+    pub blank_lines: bool,
+    /// Keep the comment lines of raw source.
+    pub comments: bool,
+    /// Lines of the Shard source, quoted in a comment above the code of
+    /// each statement. Empty for no comments.
+    pub source_lines: Vec<String>,
+    /// The last source line quoted, so statements sharing a line are
+    /// commented once.
+    last_comment: Cell<usize>,
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Printer::new()
+    }
+}
+
+impl Printer {
+    pub fn new() -> Self {
+        Printer::for_options(&CodegenOptions::default())
+    }
+
+    pub fn for_options(options: &CodegenOptions) -> Self {
+        let source_lines = match &options.source {
+            Some(source) if options.source_comments && !options.minify => {
+                source.lines().map(str::to_string).collect()
+            }
+            _ => Vec::new(),
+        };
+        Printer {
+            indent: if options.minify {
+                String::new()
+            } else {
+                " ".repeat(options.indent)
+            },
+            blank_lines: !options.minify,
+            comments: !options.minify,
+            source_lines,
+            last_comment: Cell::new(0),
+        }
+    }
+
+    /// Print commands one per line.
+    pub fn print(&self, commands: &[Command]) -> String {
+        let mut output = String::new();
+        self.block(&mut output, commands, 0);
+        output
+    }
+
+    /// Print commands on a single line, separated by `;`.
+    pub fn print_inline(&self, commands: &[Command]) -> String {
+        let body = self.inline_body(commands);
+        body.trim_end_matches("; ").to_string()
+    }
+
+    /// Print commands at `depth`. Empty lines are only kept between code,
+    /// never at the start or end of a block.
+    fn block(&self, output: &mut String, commands: &[Command], depth: usize) {
+        let indent = self.indent.repeat(depth);
+        let mut started = false;
+        let mut blank = false;
+        for command in commands {
+            if matches!(command, Command::Blank) {
+                blank = started && self.blank_lines;
+                continue;
+            }
+            if blank {
+                output.push('\n');
+                blank = false;
+            }
+            match command {
+                Command::Location { line, column } => {
+                    output.push_str(&format!("{}{}:{}\n", LOCATION_MARKER, line, column));
+                    if let Some(text) = self.comment(*line) {
+                        output.push_str(&format!("{}# {}\n", indent, text));
+                        started = true;
+                    }
+                }
+                Command::Raw(text) => {
+                    output.push_str(&self.raw(text, depth));
+                    started = true;
+                }
+                _ => {
+                    output.push_str(&indent);
+                    output.push_str(&self.command(command, depth, false));
+                    output.push('\n');
+                    started = true;
+                }
+            }
+        }
+    }
+
+    /// Raw source re-indented to `depth`, one line per line of `text`.
+    fn raw(&self, text: &str, depth: usize) -> String {
+        let mut output = String::new();
+        for line in text.lines() {
+            let code = line.trim_start_matches(' ');
+            if code.starts_with('#') && !self.comments {
+                continue;
+            }
+            let level = (line.len() - code.len()) / 2;
+            output.push_str(&self.indent.repeat(depth + level));
+            output.push_str(code);
+            output.push('\n');
+        }
+        output
+    }
+
+    /// The source text to quote above the statement starting on `line`.
+    fn comment(&self, line: usize) -> Option<&str> {
+        if line == self.last_comment.get() {
+            return None;
+        }
+        let text = self.source_lines.get(line.checked_sub(1)?)?.trim();
+        if text.is_empty() {
+            return None;
+        }
+        self.last_comment.set(line);
+        Some(text)
+    }
+
+    /// Commands each followed by `; `, or `:; ` when there are none.
+    fn inline_body(&self, commands: &[Command]) -> String {
+        let body: String = commands
+            .iter()
+            .filter(|command| is_code(command))
+            .map(|command| format!("{}; ", self.command(command, 0, true)))
+            .collect();
+        if body.is_empty() {
+            ":; ".to_string()
+        } else {
+            body
+        }
+    }
+
+    /// The body of a compound command, ending where its closing word starts.
+    fn body(&self, commands: &[Command], depth: usize, inline: bool) -> String {
+        if inline {
+            format!(" {}", self.inline_body(commands))
+        } else {
+            let mut output = String::from("\n");
+            // A compound command needs at least one command in its body.
+            if !commands.iter().any(is_code) {
+                output.push_str(&self.indent.repeat(depth + 1));
+                output.push_str(":\n");
+            }
+            self.block(&mut output, commands, depth + 1);
+            output.push_str(&self.indent.repeat(depth));
+            output
+        }
+    }
+
+    fn command(&self, command: &Command, depth: usize, inline: bool) -> String {
+        match command {
+            Command::Simple { words, redirects } => {
+                let mut parts: Vec<String> = words
+                    .iter()
+                    .map(|w| self.word(w, Context::Argument))
+                    .collect();
+                parts.extend(redirects.iter().map(|r| self.redirect(r)));
+                parts.join(" ")
+            }
+            Command::Assign { name, value } => {
+                format!("{}={}", name, self.value(value, Context::Assignment))
+            }
+            Command::Declare {
+                builtin,
+                name,
+                value,
+            } => match value {
+                Some(value) => format!(
+                    "{} {}={}",
+                    builtin,
+                    name,
+                    self.value(value, Context::Argument)
+                ),
+                None => format!("{} {}", builtin, name),
+            },
+            Command::Test { test, double } => {
+                let (open, close) = if *double { ("[[", "]]") } else { ("[", "]") };
+                let operands = match test {
+                    Test::Word(word) => self.word(word, Context::Argument),
+                    Test::Unary(op, word) => {
+                        format!("{} {}", op, self.word(word, Context::Argument))
+                    }
+                    Test::Binary(left, op, right) => format!(
+                        "{} {} {}",
+                        self.word(left, Context::Argument),
+                        op,
+                        self.word(right, Context::Argument)
+                    ),
+                };
+                format!("{} {} {}", open, operands, close)
+            }
+            Command::Not(command) => format!("! {}", self.operand(command)),
+            Command::And(left, right) => {
+                format!(
+                    "{} && {}",
+                    self.command(left, 0, true),
+                    self.list_operand(right)
+                )
+            }
+            Command::Or(left, right) => {
+                format!(
+                    "{} || {}",
+                    self.command(left, 0, true),
+                    self.list_operand(right)
+                )
+            }
+            Command::Pipeline(commands) => commands
+                .iter()
+                .map(|c| self.operand(c))
+                .collect::<Vec<_>>()
+                .join(" | "),
+            Command::Group { body, redirects } => {
+                let mut text = format!("{{ {}}}", self.inline_body(body));
+                for redirect in redirects {
+                    text.push(' ');
+                    text.push_str(&self.redirect(redirect));
+                }
+                text
+            }
+            Command::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let mut text = format!(
+                    "if {}; then{}",
+                    self.command(condition, 0, true),
+                    self.body(then_branch, depth, inline)
+                );
+                if let Some(else_branch) = else_branch {
+                    text.push_str("else");
+                    text.push_str(&self.body(else_branch, depth, inline));
+                }
+                text.push_str("fi");
+                text
+            }
+            Command::While {
+                condition,
+                body,
+                redirects,
+            } => {
+                let mut text = format!(
+                    "while {}; do{}done",
+                    self.command(condition, 0, true),
+                    self.body(body, depth, inline)
+                );
+                for redirect in redirects {
+                    text.push(' ');
+                    text.push_str(&self.redirect(redirect));
+                }
+                text
+            }
+            Command::For {
+                variable,
+                words,
+                body,
+            } => {
+                let words: Vec<String> = words
+                    .iter()
+                    .map(|w| self.word(w, Context::Argument))
+                    .collect();
+                format!(
+                    "for {} in {}; do{}done",
+                    variable,
+                    words.join(" "),
+                    self.body(body, depth, inline)
+                )
+            }
+            Command::Case { word, arms } => {
+                let mut text = format!("case {} in", self.word(word, Context::Assignment));
+                for (patterns, body) in arms {
+                    let patterns: Vec<String> = patterns
+                        .iter()
+                        .map(|p| self.word(p, Context::Argument))
+                        .collect();
+                    let patterns = patterns.join(" | ");
+                    if inline {
+                        text.push_str(&format!(" {}) {};;", patterns, self.inline_body(body)));
+                    } else {
+                        let indent = self.indent.repeat(depth + 1);
+                        text.push_str(&format!("\n{}{})\n", indent, patterns));
+                        self.block(&mut text, body, depth + 2);
+                        text.push_str(&format!("{}{};;", indent, self.indent));
+                    }
+                }
+                if inline {
+                    text.push_str(" esac");
+                } else {
+                    text.push_str(&format!("\n{}esac", self.indent.repeat(depth)));
+                }
+                text
+            }
+            Command::Function { name, body } => {
+                format!("{}() {{{}}}", name, self.body(body, depth, inline))
+            }
+            Command::Raw(text) => text.clone(),
+            Command::Blank | Command::Location { .. } => String::new(),
+        }
+    }
+
+    /// The right operand of `&&` or `||`. A negated command is a pipeline,
+    /// so it needs no group there.
+    fn list_operand(&self, command: &Command) -> String {
+        match command {
+            Command::Not(_) => self.command(command, 0, true),
+            _ => self.operand(command),
+        }
+    }
+
+    /// A command used as an operand of `!`, `&&`, `||` or `|`, grouped when
+    /// it is itself a list.
+    fn operand(&self, command: &Command) -> String {
+        match command {
+            Command::And(..) | Command::Or(..) | Command::Not(_) | Command::Pipeline(_) => {
+                format!("{{ {}; }}", self.command(command, 0, true))
+            }
+            _ => self.command(command, 0, true),
+        }
+    }
+
+    fn value(&self, value: &Value, context: Context) -> String {
+        match value {
+            Value::Word(word) => self.word(word, context),
+            Value::Array(words) => {
+                let words: Vec<String> = words
+                    .iter()
+                    .map(|w| self.word(w, Context::Argument))
+                    .collect();
+                format!("({})", words.join(" "))
+            }
+            Value::Assoc(entries) => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(k, v)| {
+                        format!(
+                            "[{}]={}",
+                            self.word(k, Context::Argument),
+                            self.word(v, Context::Argument)
+                        )
+                    })
+                    .collect();
+                format!("({})", entries.join(" "))
+            }
+        }
+    }
+
+    fn word(&self, word: &Word, context: Context) -> String {
+        if word.segments.is_empty() {
+            return "''".to_string();
+        }
+        let quoted =
+            context == Context::Argument && !word.split && word.segments.iter().any(needs_quotes);
+        let text = self.segments(word, quoted);
+        if quoted {
+            format!("\"{}\"", text)
+        } else {
+            text
+        }
+    }
+
+    /// The segments of a word, escaped for double quotes when `quoted`.
+    fn segments(&self, word: &Word, quoted: bool) -> String {
+        let mut text = String::new();
+        for (i, segment) in word.segments.iter().enumerate() {
+            match segment {
+                Segment::Literal(s) if quoted => text.push_str(&escape_double(s)),
+                Segment::Literal(s) => text.push_str(&quote(s)),
+                Segment::Bare(s) if quoted => text.push_str(&escape_double(s)),
+                Segment::Bare(s) => text.push_str(s),
+                Segment::Param(name) => {
+                    let joins_next = word.segments.get(i + 1).is_some_and(|next| {
+                        matches!(next, Segment::Bare(s) | Segment::Literal(s)
+                            if s.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_'))
+                    });
+                    // `$10` would be `$1` followed by a 0.
+                    let positional = name.len() > 1 && name.bytes().all(|b| b.is_ascii_digit());
+                    if joins_next || positional {
+                        text.push_str(&format!("${{{}}}", name));
+                    } else {
+                        text.push_str(&format!("${}", name));
+                    }
+                }
+                Segment::Elements(name) => text.push_str(&format!("${{{}[@]}}", name)),
+                Segment::Count(name) => text.push_str(&format!("${{#{}[@]}}", name)),
+                Segment::Length(name) => text.push_str(&format!("${{#{}}}", name)),
+                Segment::Keys(name) => text.push_str(&format!("${{!{}[@]}}", name)),
+                Segment::Index(name, subscript) => {
+                    let subscript = match subscript {
+                        Subscript::Position(arith) => self.arith(arith, 0, false),
+                        Subscript::Key(key) => self.word(key, Context::Argument),
+                    };
+                    text.push_str(&format!("${{{}[{}]}}", name, subscript));
+                }
+                Segment::Substitution(commands) => {
+                    text.push_str(&format!("$({})", self.print_inline(commands)));
+                }
+                Segment::Arithmetic(arith) => {
+                    text.push_str(&format!("$(({}))", self.arith(arith, 0, false)));
+                }
+                // The default is double-quoted inside the braces, which
+                // keeps it whole and lets it contain `}`.
+                Segment::Default(name, default) => {
+                    let default = self.segments(default, true);
+                    text.push_str(&format!("${{{}:-\"{}\"}}", name, default));
+                }
+            }
+        }
+        text
+    }
+
+    /// An arithmetic expression, parenthesized where the operator binds
+    /// more loosely than its context.
+    fn arith(&self, arith: &Arith, parent: u8, right: bool) -> String {
+        match arith {
+            Arith::Number(n) => n.to_string(),
+            Arith::Param(name) => format!("${}", name),
+            Arith::Binary(left, op, right_operand) => {
+                let precedence = op.precedence();
+                let text = format!(
+                    "{} {} {}",
+                    self.arith(left, precedence, false),
+                    op.symbol(),
+                    self.arith(right_operand, precedence, true)
+                );
+                if precedence < parent || (precedence == parent && right) {
+                    format!("({})", text)
+                } else {
+                    text
+                }
+            }
+            Arith::Negate(operand) => match operand.as_ref() {
+                Arith::Binary(..) | Arith::Negate(_) => {
+                    format!("-({})", self.arith(operand, 0, false))
+                }
+                Arith::Number(n) if *n < 0 => format!("-({})", n),
+                _ => format!("-{}", self.arith(operand, 3, false)),
+            },
+            Arith::Word(word) => self.word(word, Context::Assignment),
+        }
+    }
+
+    fn redirect(&self, redirect: &Redirect) -> String {
+        match redirect {
+            Redirect::Output(1, target) => format!("> {}", self.word(target, Context::Argument)),
+            Redirect::Output(fd, target) => {
+                format!("{}> {}", fd, self.word(target, Context::Argument))
+            }
+            Redirect::Append(1, target) => format!(">> {}", self.word(target, Context::Argument)),
+            Redirect::Append(fd, target) => {
+                format!("{}>> {}", fd, self.word(target, Context::Argument))
+            }
+            Redirect::Input(0, source) => format!("< {}", self.word(source, Context::Argument)),
+            Redirect::Input(fd, source) => {
+                format!("{}< {}", fd, self.word(source, Context::Argument))
+            }
+            Redirect::Duplicate(1, to) => format!(">&{}", to),
+            Redirect::Duplicate(fd, to) => format!("{}>&{}", fd, to),
+            Redirect::DuplicateInput(0, from) => format!("<&{}", from),
+            Redirect::DuplicateInput(fd, from) => format!("{}<&{}", fd, from),
+        }
+    }
+}
+
+impl ArithOp {
+    fn precedence(self) -> u8 {
+        match self {
+            ArithOp::Add | ArithOp::Subtract => 1,
+            ArithOp::Multiply | ArithOp::Divide | ArithOp::Modulo => 2,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            ArithOp::Add => "+",
+            ArithOp::Subtract => "-",
+            ArithOp::Multiply => "*",
+            ArithOp::Divide => "/",
+            ArithOp::Modulo => "%",
+        }
+    }
+}
+
+fn is_code(command: &Command) -> bool {
+    !matches!(command, Command::Blank | Command::Location { .. })
+}
+
+/// Expansions that undergo field splitting unless double-quoted.
+fn needs_quotes(segment: &Segment) -> bool {
+    matches!(
+        segment,
+        Segment::Param(_)
+            | Segment::Default(..)
+            | Segment::Elements(_)
+            | Segment::Keys(_)
+            | Segment::Index(..)
+            | Segment::Substitution(_)
+    )
+}
+
+/// Quote `text` for the shell so it is taken literally.
+pub fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn escape_double(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '$' | '`' | '"' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
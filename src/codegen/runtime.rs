@@ -0,0 +1,973 @@
+//! Shell functions generated scripts call at runtime.
+//!
+//! Each helper names the helpers it calls. A script includes only the
+//! helpers it uses, with their dependencies, or sources all of them from a
+//! shared file with `Runtime::External`.
+
+use clap::ValueEnum;
+use std::collections::BTreeSet;
+
+/// Name of the shared file that `Runtime::External` scripts source.
+pub const RUNTIME_FILE: &str = "shard-runtime.sh";
+
+/// Where a script gets its helpers from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Runtime {
+    /// Define the helpers a script uses at its top.
+    #[default]
+    Inline,
+    /// Source every helper from `shard-runtime.sh`, looked up through
+    /// `$SHARD_RUNTIME` or next to the script.
+    External,
+}
+
+/// A shell function in the runtime library.
+///
+/// `source` is written with the `__shard_` prefix and two-space
+/// indentation; both are adjusted when the helper is printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Helper {
+    /// The name without its prefix.
+    pub name: &'static str,
+    /// Helpers `source` calls.
+    pub deps: &'static [&'static str],
+    pub source: &'static str,
+}
+
+impl Helper {
+    /// Whether the helper writes to the stderr a script saves as fd 9 at
+    /// its start. Commands run with their stderr captured, so messages
+    /// meant for the terminal, such as logs, go there instead.
+    pub fn uses_saved_stderr(&self) -> bool {
+        self.source.contains(">&9")
+    }
+}
+
+pub const HELPERS: &[Helper] = &[
+    Helper {
+        name: "len",
+        deps: &[],
+        source: r#"# Number of lines in $1.
+__shard_len() {
+  printf '%s' "$1" | awk 'END { print NR }'
+}"#,
+    },
+    Helper {
+        name: "length",
+        deps: &[],
+        source: r#"# Number of characters in $1.
+__shard_length() {
+  printf '%s' "${#1}"
+}"#,
+    },
+    Helper {
+        name: "index",
+        deps: &[],
+        source: r#"# Line $2 of $1, counting from zero.
+__shard_index() {
+  printf '%s\n' "$1" | sed -n "$(($2 + 1))p"
+}"#,
+    },
+    Helper {
+        name: "lookup",
+        deps: &[],
+        source: r#"# The value for key $2 in the key=value lines of $1.
+__shard_lookup() {
+  printf '%s\n' "$1" | awk -v k="$2" 'index($0, k "=") == 1 { print substr($0, length(k) + 2); exit }'
+}"#,
+    },
+    Helper {
+        name: "report",
+        deps: &[],
+        source: r#"# Report the current Shard line on the saved stderr: $1 what happened,
+# $2 the status.
+__shard_report() {
+  printf 'shard: %s:%s: %s with status %s\n' "$__shard_source" "$__shard_line" "$1" "$2" >&9
+}"#,
+    },
+    Helper {
+        name: "truthy",
+        deps: &[],
+        source: r#"# Whether $1, of a type only known at run time, is true: anything but
+# the empty string, false and 0.
+__shard_truthy() {
+  case $1 in
+    '' | false | 0) return 1 ;;
+  esac
+}"#,
+    },
+    Helper {
+        name: "enter",
+        deps: &[],
+        source: r#"# Save the variables named by the arguments on entry to a function that
+# can call itself, one copy per depth of calls.
+__shard_enter() {
+  __shard_depth=$((${__shard_depth:-0} + 1))
+  while [ "$#" -gt 0 ]; do
+    eval "${1}_$__shard_depth=\$$1"
+    shift
+  done
+}"#,
+    },
+    Helper {
+        name: "leave",
+        deps: &[],
+        source: r#"# Restore the variables saved by the matching __shard_enter.
+__shard_leave() {
+  while [ "$#" -gt 0 ]; do
+    eval "$1=\$${1}_$__shard_depth"
+    unset "${1}_$__shard_depth"
+    shift
+  done
+  __shard_depth=$((__shard_depth - 1))
+}"#,
+    },
+    // String functions. Predicates answer with their exit status. Arguments reach awk through the environment, since
+    // `awk -v` would interpret backslash escapes in them.
+    Helper {
+        name: "upper",
+        deps: &[],
+        source: r#"__shard_upper() {
+  printf '%s' "$1" | tr '[:lower:]' '[:upper:]'
+}"#,
+    },
+    Helper {
+        name: "lower",
+        deps: &[],
+        source: r#"__shard_lower() {
+  printf '%s' "$1" | tr '[:upper:]' '[:lower:]'
+}"#,
+    },
+    Helper {
+        name: "trim",
+        deps: &[],
+        source: r#"__shard_trim() {
+  set -- "${1#"${1%%[![:space:]]*}"}"
+  printf '%s' "${1%"${1##*[![:space:]]}"}"
+}"#,
+    },
+    Helper {
+        name: "split",
+        deps: &[],
+        source: r#"# The parts of $1 separated by $2, one per line. An empty separator
+# splits into characters.
+__shard_split() {
+  S="$1" D="$2" awk 'BEGIN {
+    s = ENVIRON["S"]; d = ENVIRON["D"]
+    if (d == "") {
+      for (i = 1; i <= length(s); i++) print substr(s, i, 1)
+      exit
+    }
+    while ((i = index(s, d)) > 0) {
+      print substr(s, 1, i - 1)
+      s = substr(s, i + length(d))
+    }
+    print s
+  }'
+}"#,
+    },
+    Helper {
+        name: "join",
+        deps: &[],
+        source: r#"# The lines of $1 separated by $2.
+__shard_join() {
+  L="$1" D="$2" awk 'BEGIN {
+    n = split(ENVIRON["L"], items, "\n")
+    for (i = 1; i <= n; i++) printf "%s%s", (i > 1 ? ENVIRON["D"] : ""), items[i]
+  }'
+}"#,
+    },
+    Helper {
+        name: "replace",
+        deps: &[],
+        source: r#"# $1 with every $2 replaced by $3.
+__shard_replace() {
+  S="$1" F="$2" T="$3" awk 'BEGIN {
+    s = ENVIRON["S"]; f = ENVIRON["F"]; t = ENVIRON["T"]
+    if (f != "") {
+      while ((i = index(s, f)) > 0) {
+        out = out substr(s, 1, i - 1) t
+        s = substr(s, i + length(f))
+      }
+    }
+    printf "%s", out s
+  }'
+}"#,
+    },
+    Helper {
+        name: "contains",
+        deps: &[],
+        source: r#"__shard_contains() {
+  case $1 in
+    *"$2"*) return 0 ;;
+  esac
+  return 1
+}"#,
+    },
+    Helper {
+        name: "starts_with",
+        deps: &[],
+        source: r#"__shard_starts_with() {
+  case $1 in
+    "$2"*) return 0 ;;
+  esac
+  return 1
+}"#,
+    },
+    Helper {
+        name: "ends_with",
+        deps: &[],
+        source: r#"__shard_ends_with() {
+  case $1 in
+    *"$2") return 0 ;;
+  esac
+  return 1
+}"#,
+    },
+    Helper {
+        name: "substr",
+        deps: &[],
+        source: r#"# $3 characters of $1 from position $2, or all of them when $3 is
+# not given. A negative $2 counts from the end.
+__shard_substr() {
+  S="$1" awk -v start="$2" -v n="${3--1}" 'BEGIN {
+    s = ENVIRON["S"]
+    if (start < 0) start += length(s)
+    if (start < 0) start = 0
+    if (n < 0) printf "%s", substr(s, start + 1)
+    else printf "%s", substr(s, start + 1, n)
+  }'
+}"#,
+    },
+    Helper {
+        name: "index_of",
+        deps: &[],
+        source: r#"__shard_index_of() {
+  S="$1" F="$2" awk 'BEGIN { print index(ENVIRON["S"], ENVIRON["F"]) - 1 }'
+}"#,
+    },
+    Helper {
+        name: "repeat",
+        deps: &[],
+        source: r#"__shard_repeat() {
+  set -- "$1" "$2" ""
+  while [ "$2" -gt 0 ]; do
+    set -- "$1" "$(($2 - 1))" "$3$1"
+  done
+  printf '%s' "$3"
+}"#,
+    },
+    Helper {
+        name: "pad_left",
+        deps: &["repeat"],
+        source: r#"# $1 padded on the left to $2 characters with the character $3, a
+# space by default.
+__shard_pad_left() {
+  set -- "$1" "$(($2 - ${#1}))" "${3- }"
+  if [ "$2" -gt 0 ]; then
+    printf '%s%s' "$(__shard_repeat "$3" "$2")" "$1"
+  else
+    printf '%s' "$1"
+  fi
+}"#,
+    },
+    Helper {
+        name: "format",
+        deps: &[],
+        source: r#"# $1 with each {} replaced by the next of the other arguments.
+__shard_format() {
+  __shard_format_rest=$1
+  __shard_format_out=
+  shift
+  for __shard_format_arg in "$@"; do
+    case $__shard_format_rest in
+      *"{}"*) ;;
+      *) break ;;
+    esac
+    __shard_format_out=$__shard_format_out${__shard_format_rest%%"{}"*}$__shard_format_arg
+    __shard_format_rest=${__shard_format_rest#*"{}"}
+  done
+  printf '%s' "$__shard_format_out$__shard_format_rest"
+}"#,
+    },
+    // Math functions. Numbers are printed with `%.15g`, so whole results
+    // have no decimal point.
+    Helper {
+        name: "abs",
+        deps: &[],
+        source: r#"__shard_abs() {
+  awk -v x="$1" 'BEGIN { printf "%.15g\n", x < 0 ? -x : x }'
+}"#,
+    },
+    Helper {
+        name: "min",
+        deps: &[],
+        source: r#"__shard_min() {
+  awk 'BEGIN {
+    m = ARGV[1] + 0
+    for (i = 2; i < ARGC; i++) if (ARGV[i] + 0 < m) m = ARGV[i] + 0
+    printf "%.15g\n", m
+  }' "$@"
+}"#,
+    },
+    Helper {
+        name: "max",
+        deps: &[],
+        source: r#"__shard_max() {
+  awk 'BEGIN {
+    m = ARGV[1] + 0
+    for (i = 2; i < ARGC; i++) if (ARGV[i] + 0 > m) m = ARGV[i] + 0
+    printf "%.15g\n", m
+  }' "$@"
+}"#,
+    },
+    Helper {
+        name: "round",
+        deps: &[],
+        source: r#"# $1 rounded to the nearest integer, halves away from zero.
+__shard_round() {
+  awk -v x="$1" 'BEGIN { printf "%d\n", x < 0 ? -int(-x + 0.5) : int(x + 0.5) }'
+}"#,
+    },
+    Helper {
+        name: "floor",
+        deps: &[],
+        source: r#"__shard_floor() {
+  awk -v x="$1" 'BEGIN { i = int(x); if (i > x) i--; printf "%d\n", i }'
+}"#,
+    },
+    Helper {
+        name: "ceil",
+        deps: &[],
+        source: r#"__shard_ceil() {
+  awk -v x="$1" 'BEGIN { i = int(x); if (i < x) i++; printf "%d\n", i }'
+}"#,
+    },
+    Helper {
+        name: "pow",
+        deps: &[],
+        source: r#"__shard_pow() {
+  awk -v x="$1" -v y="$2" 'BEGIN { printf "%.15g\n", x ^ y }'
+}"#,
+    },
+    Helper {
+        name: "sqrt",
+        deps: &[],
+        source: r#"__shard_sqrt() {
+  awk -v x="$1" 'BEGIN { printf "%.15g\n", sqrt(x) }'
+}"#,
+    },
+    Helper {
+        name: "to_int",
+        deps: &[],
+        source: r#"# $1 with its fractional part dropped.
+__shard_to_int() {
+  awk -v x="$1" 'BEGIN { printf "%d\n", int(x) }'
+}"#,
+    },
+    Helper {
+        name: "to_float",
+        deps: &[],
+        source: r#"__shard_to_float() {
+  awk -v x="$1" 'BEGIN { printf "%.15g\n", x + 0 }'
+}"#,
+    },
+    Helper {
+        name: "parse_int",
+        deps: &[],
+        source: r#"# The integer written in $1. When it is not one, $2 if given, otherwise
+# an error and status 1.
+__shard_parse_int() {
+  if S="$1" awk 'BEGIN { exit ENVIRON["S"] !~ /^[ \t]*[+-]?[0-9]+[ \t]*$/ }'; then
+    S="$1" awk 'BEGIN { printf "%d\n", ENVIRON["S"] }'
+  elif [ "$#" -gt 1 ]; then
+    printf '%s\n' "$2"
+  else
+    printf 'shard: parse_int: not an integer: %s\n' "$1" >&9
+    return 1
+  fi
+}"#,
+    },
+    Helper {
+        name: "enum",
+        deps: &[],
+        source: r#"# $2 when it is one of the variants $3... of the enum $1, otherwise an
+# error and status 1.
+__shard_enum() {
+  __shard_enum_name=$1
+  __shard_enum_value=$2
+  shift 2
+  for __shard_enum_variant in "$@"; do
+    if [ "$__shard_enum_variant" = "$__shard_enum_value" ]; then
+      printf '%s\n' "$__shard_enum_value"
+      return
+    fi
+  done
+  printf 'shard: %s: not one of %s: %s\n' "$__shard_enum_name" "$*" "$__shard_enum_value" >&9
+  return 1
+}"#,
+    },
+    // JSON.
+    Helper {
+        name: "json",
+        deps: &[],
+        source: r#"# JSON support, by mode:
+#   parse TEXT: the entries of the object or array TEXT, one per line,
+#     as key=value for objects, with newlines in them written as \n.
+#     Nested values stay compact JSON.
+#   compact TEXT: TEXT checked and without insignificant whitespace.
+#   declare NAME DECLARE TEXT: shell code that assigns the entries of
+#     TEXT to the array NAME, declared with DECLARE for objects.
+#   stringify KIND VALUE: VALUE as JSON. KIND is object or array for
+#     lines as printed by parse, or string, number, bool, null or auto.
+__shard_json() {
+  M="$1" A="$2" B="$3" C="$4" LC_ALL=C awk '
+    function fail(message) {
+      if (!err) err = message " at offset " (pos - 1)
+      pos = n + 1
+      return ""
+    }
+    function ws() {
+      while (pos <= n && index(" \t\n\r", substr(s, pos, 1))) pos++
+    }
+    function string_token(start, c) {
+      start = pos++
+      while (pos <= n) {
+        c = substr(s, pos, 1)
+        if (c == "\\") pos += 2
+        else if (c == "\"") return substr(s, start, ++pos - start)
+        else if (c < " ") return fail("control character in string")
+        else pos++
+      }
+      return fail("unterminated string")
+    }
+    # The value at pos, compacted. With entries set, the entries of an
+    # object or array are collected in out instead.
+    function value(entries, c, t, keyed, last, text, key) {
+      ws()
+      c = substr(s, pos, 1)
+      if (c == "\"") return string_token()
+      if (c != "{" && c != "[") {
+        if (match(substr(s, pos), /^(-?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?|true|false|null)/)) {
+          t = substr(s, pos, RLENGTH)
+          pos += RLENGTH
+          return t
+        }
+        return fail(c == "" ? "unexpected end of input" : "unexpected " c)
+      }
+      keyed = c == "{"
+      last = keyed ? "}" : "]"
+      text = c
+      pos++
+      ws()
+      if (substr(s, pos, 1) == last) {
+        pos++
+        return text last
+      }
+      while (!err) {
+        if (keyed) {
+          ws()
+          if (substr(s, pos, 1) != "\"") return fail("expected a key")
+          key = string_token()
+          ws()
+          if (substr(s, pos++, 1) != ":") return fail("expected :")
+        }
+        t = value(0)
+        if (entries) entry(keyed, plain(key), plain(t))
+        else text = text (keyed ? key ":" : "") t
+        ws()
+        c = substr(s, pos++, 1)
+        if (c == last) return text last
+        if (c != ",") return fail("expected , or " last)
+        text = text ","
+      }
+      return ""
+    }
+    function entry(keyed, key, v) {
+      if (mode == "declare") out = out " " (keyed ? "[" quote(key) "]=" : "") quote(v)
+      else out = out (keyed ? one_line(key) "=" : "") one_line(v) "\n"
+    }
+    function one_line(v) {
+      gsub(/\n/, "\\\\n", v)
+      return v
+    }
+    function quote(v) {
+      gsub(q, q "\\" q q, v)
+      return q v q
+    }
+    # A compact value with strings decoded.
+    function plain(t, text, i, c, code, low) {
+      if (t == "null") return ""
+      if (substr(t, 1, 1) != "\"") return t
+      t = substr(t, 2, length(t) - 2)
+      while ((i = index(t, "\\")) > 0) {
+        text = text substr(t, 1, i - 1)
+        c = substr(t, i + 1, 1)
+        t = substr(t, i + 2)
+        if (c == "u") {
+          code = hex(substr(t, 1, 4))
+          t = substr(t, 5)
+          if (code >= 55296 && code < 56320 && substr(t, 1, 2) == "\\u") {
+            low = hex(substr(t, 3, 4))
+            if (low >= 56320 && low < 57344) {
+              code = 65536 + (code - 55296) * 1024 + low - 56320
+              t = substr(t, 7)
+            }
+          }
+          text = text utf8(code)
+        } else {
+          text = text (c == "n" ? "\n" : c == "t" ? "\t" : c == "r" ? "\r" : c == "b" ? "\b" : c == "f" ? "\f" : c)
+        }
+      }
+      return text t
+    }
+    function hex(h, i, v) {
+      h = tolower(h)
+      for (i = 1; i <= length(h); i++) v = v * 16 + index("0123456789abcdef", substr(h, i, 1)) - 1
+      return v
+    }
+    function utf8(c) {
+      if (c < 128) return sprintf("%c", c)
+      if (c < 2048) return sprintf("%c%c", 192 + int(c / 64), 128 + c % 64)
+      if (c < 65536) return sprintf("%c%c%c", 224 + int(c / 4096), 128 + int(c / 64) % 64, 128 + c % 64)
+      return sprintf("%c%c%c%c", 240 + int(c / 262144), 128 + int(c / 4096) % 64, 128 + int(c / 64) % 64, 128 + c % 64)
+    }
+    function encode(v, kind, t, i, c, text) {
+      if (kind == "number" || kind == "bool") return v
+      if (kind == "null") return "null"
+      if (kind == "auto") {
+        if (v ~ /^(-?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?|true|false|null)$/) return v
+        if (v ~ /^[[{]/) {
+          s = v
+          n = length(s)
+          pos = 1
+          err = ""
+          t = value(0)
+          ws()
+          if (!err && pos > n) return t
+        }
+      }
+      for (i = 1; i <= length(v); i++) {
+        c = substr(v, i, 1)
+        text = text (c == "\"" || c == "\\" ? "\\" c : c in control ? control[c] : c)
+      }
+      return "\"" text "\""
+    }
+    function stringify(kind, v, lines, count, i, key, text) {
+      if (kind != "object" && kind != "array") return encode(v, kind)
+      count = v == "" ? 0 : split(v, lines, "\n")
+      for (i = 1; i <= count; i++) {
+        text = text (i > 1 ? "," : "")
+        if (kind == "array") {
+          text = text encode(lines[i], "auto")
+        } else {
+          key = substr(lines[i], 1, index(lines[i], "=") - 1)
+          text = text encode(key, "string") ":" encode(substr(lines[i], length(key) + 2), "auto")
+        }
+      }
+      return kind == "object" ? "{" text "}" : "[" text "]"
+    }
+    BEGIN {
+      mode = ENVIRON["M"]
+      q = sprintf("%c", 39)
+      for (i = 1; i < 32; i++) control[sprintf("%c", i)] = sprintf("\\u%04x", i)
+      control["\n"] = "\\n"
+      control["\t"] = "\\t"
+      control["\r"] = "\\r"
+      if (mode == "stringify") {
+        print stringify(ENVIRON["A"], ENVIRON["B"])
+        exit
+      }
+      s = mode == "declare" ? ENVIRON["C"] : ENVIRON["A"]
+      n = length(s)
+      pos = 1
+      ws()
+      c = substr(s, pos, 1)
+      t = value(mode != "compact")
+      ws()
+      if (pos <= n) fail("unexpected " substr(s, pos, 1))
+      if (err) {
+        printf "shard: json: %s\n", err > "/dev/stderr"
+        exit 1
+      }
+      if (c != "{" && c != "[") entry(0, "", plain(t))
+      if (mode == "compact") print t
+      else if (mode == "parse") printf "%s", out
+      else printf "%s%s=(%s )\n", (c == "{" ? ENVIRON["B"] " " : ""), ENVIRON["A"], out
+    }'
+}"#,
+    },
+    // Higher-order functions. $1 names the function to call and $2 holds
+    // the array as lines, which are taken off its front one at a time so
+    // the function can run in the current shell.
+    Helper {
+        name: "each",
+        deps: &[],
+        source: r#"# Call $1 with each line of $2.
+__shard_each() {
+  [ -n "$2" ] || return 0
+  set -- "$1" "$2
+"
+  while [ -n "$2" ]; do
+    "$1" "${2%%
+*}"
+    set -- "$1" "${2#*
+}"
+  done
+}"#,
+    },
+    Helper {
+        name: "filter",
+        deps: &["truthy"],
+        source: r#"# The lines of $2 for which $1 prints a true value.
+__shard_filter() {
+  [ -n "$2" ] || return 0
+  set -- "$1" "$2
+"
+  while [ -n "$2" ]; do
+    if __shard_truthy "$("$1" "${2%%
+*}")"; then
+      printf '%s\n' "${2%%
+*}"
+    fi
+    set -- "$1" "${2#*
+}"
+  done
+}"#,
+    },
+    Helper {
+        name: "reduce",
+        deps: &[],
+        source: r#"# $3 combined with each line of $2 in turn by $1, which is called with
+# the result so far and the line.
+__shard_reduce() {
+  if [ -n "$2" ]; then
+    set -- "$1" "$2
+" "$3"
+  fi
+  while [ -n "$2" ]; do
+    set -- "$1" "${2#*
+}" "$("$1" "$3" "${2%%
+*}")"
+  done
+  printf '%s\n' "$3"
+}"#,
+    },
+    Helper {
+        name: "sort_by",
+        deps: &[],
+        source: r#"# The lines of $2 ordered by what $1 prints for each, as numbers when
+# both keys are numbers and as text otherwise. Lines with equal keys keep
+# their order.
+__shard_sort_by() {
+  [ -n "$2" ] || return 0
+  set -- "$1" "$2
+"
+  while [ -n "$2" ]; do
+    printf '%s\t%s\n' "$("$1" "${2%%
+*}")" "${2%%
+*}"
+    set -- "$1" "${2#*
+}"
+  done | awk -F '\t' '
+    { key[NR] = $1; line[NR] = substr($0, length($1) + 2) }
+    END {
+      for (i = 2; i <= NR; i++) {
+        k = key[i]
+        l = line[i]
+        for (j = i - 1; j > 0 && key[j] > k; j--) {
+          key[j + 1] = key[j]
+          line[j + 1] = line[j]
+        }
+        key[j + 1] = k
+        line[j + 1] = l
+      }
+      for (i = 1; i <= NR; i++) print line[i]
+    }'
+}"#,
+    },
+    // Logging.
+    Helper {
+        name: "log",
+        deps: &[],
+        source: r#"# Print $2 on the saved stderr at level $1 when $SHARD_LOG_LEVEL (debug, info,
+# warn, error or off in any case; info by default) allows it.
+# SHARD_LOG_TIMESTAMPS=1 adds the time, and levels are colored on a
+# terminal unless NO_COLOR is set.
+__shard_log() {
+  case $1 in
+    debug) set -- 0 36 DEBUG "$2" ;;
+    info) set -- 1 32 INFO "$2" ;;
+    warn) set -- 2 33 WARN "$2" ;;
+    *) set -- 3 31 ERROR "$2" ;;
+  esac
+  __shard_log_level=$(printf '%s' "${SHARD_LOG_LEVEL:-info}" | tr '[:upper:]' '[:lower:]')
+  case $__shard_log_level in
+    debug) __shard_log_min=0 ;;
+    warn | warning) __shard_log_min=2 ;;
+    error) __shard_log_min=3 ;;
+    off | none) __shard_log_min=4 ;;
+    *) __shard_log_min=1 ;;
+  esac
+  [ "$1" -ge "$__shard_log_min" ] || return 0
+  __shard_log_time=
+  if [ -n "${SHARD_LOG_TIMESTAMPS:-}" ]; then
+    __shard_log_time="$(date '+%Y-%m-%dT%H:%M:%S') "
+  fi
+  if [ -t 9 ] && [ -z "${NO_COLOR:-}" ]; then
+    printf '%s\033[%sm%-5s\033[0m %s\n' "$__shard_log_time" "$2" "$3" "$4" >&9
+  else
+    printf '%s%-5s %s\n' "$__shard_log_time" "$3" "$4" >&9
+  fi
+}"#,
+    },
+    Helper {
+        name: "die",
+        deps: &["log"],
+        source: r#"# Log $1 as an error and exit with status $2, 1 by default.
+__shard_die() {
+  __shard_log error "$1"
+  exit "${2:-1}"
+}"#,
+    },
+    // Interactive input. Questions and errors go to the saved stderr, so a
+    // prompt's answer can be captured and questions asked inside functions
+    // are still shown. Without a terminal, answers are read from stdin, and
+    // --yes or SHARD_YES takes the defaults without asking.
+    Helper {
+        name: "ask",
+        deps: &[],
+        source: r#"# Read a line into __shard_answer, showing $1 first on a terminal. Fails
+# at the end of input.
+__shard_ask() {
+  if [ -t 0 ]; then
+    printf '%s' "$1" >&9
+  fi
+  IFS= read -r __shard_answer
+}"#,
+    },
+    Helper {
+        name: "prompt",
+        deps: &["ask"],
+        source: r#"# A line of input, or the default $2 when the line is empty.
+__shard_prompt() {
+  if [ "$#" -gt 1 ] && [ -n "${SHARD_YES:-}" ]; then
+    printf '%s\n' "$2"
+  elif __shard_ask "$1${2:+ [$2]}: "; then
+    printf '%s\n' "${__shard_answer:-$2}"
+  elif [ "$#" -gt 1 ]; then
+    printf '%s\n' "$2"
+  else
+    printf 'shard: prompt: no input for: %s\n' "$1" >&9
+    return 1
+  fi
+}"#,
+    },
+    Helper {
+        name: "confirm",
+        deps: &["ask"],
+        source: r#"# Whether the answer is yes. An empty answer or the end of input is no.
+__shard_confirm() {
+  [ -z "${SHARD_YES:-}" ] || return 0
+  while __shard_ask "$1 [y/N] "; do
+    case $__shard_answer in
+      [yY] | [yY][eE][sS]) return 0 ;;
+      "" | [nN] | [nN][oO]) return 1 ;;
+    esac
+    [ -t 0 ] || return 1
+  done
+  return 1
+}"#,
+    },
+    Helper {
+        name: "select",
+        deps: &["ask", "len"],
+        source: r#"# One of the lines of $2, chosen by its number or text. The default is
+# the first.
+__shard_select() {
+  if [ -n "${SHARD_YES:-}" ]; then
+    printf '%s\n' "$2" | sed -n 1p
+    return
+  fi
+  if [ -t 0 ]; then
+    printf '%s\n' "$1" >&9
+    printf '%s\n' "$2" | awk '{ printf "  %d) %s\n", NR, $0 }' >&9
+  fi
+  while __shard_ask "Choice: "; do
+    case $__shard_answer in
+      "" | *[!0-9]*) ;;
+      *)
+        if [ "$__shard_answer" -ge 1 ] && [ "$__shard_answer" -le "$(__shard_len "$2")" ]; then
+          printf '%s\n' "$2" | sed -n "${__shard_answer}p"
+          return
+        fi
+        ;;
+    esac
+    if printf '%s\n' "$2" | grep -Fqx -- "$__shard_answer"; then
+      printf '%s\n' "$__shard_answer"
+      return
+    fi
+    [ -t 0 ] || break
+  done
+  printf 'shard: select: no valid choice for: %s\n' "$1" >&9
+  return 1
+}"#,
+    },
+    Helper {
+        name: "password",
+        deps: &[],
+        source: r#"# A line of input, not echoed when typed on a terminal.
+__shard_password() {
+  if [ -t 0 ]; then
+    printf '%s: ' "$1" >&9
+    (
+      trap 'stty echo; printf "\n" >&9' EXIT
+      trap 'exit 130' INT
+      stty -echo
+      IFS= read -r __shard_answer && printf '%s\n' "$__shard_answer"
+    )
+  elif IFS= read -r __shard_answer; then
+    printf '%s\n' "$__shard_answer"
+  else
+    printf 'shard: password: no input for: %s\n' "$1" >&9
+    return 1
+  fi
+}"#,
+    },
+    // Filesystem functions.
+    Helper {
+        name: "glob",
+        deps: &[],
+        source: r#"# The paths matching the pattern $1, one per line.
+__shard_glob() {
+  IFS=
+  for __shard_glob_path in $1; do
+    if [ -e "$__shard_glob_path" ] || [ -L "$__shard_glob_path" ]; then
+      printf '%s\n' "$__shard_glob_path"
+    fi
+  done
+}"#,
+    },
+    Helper {
+        name: "abs_path",
+        deps: &[],
+        source: r#"# $1 as an absolute path from the current directory, with `.` and `..`
+# resolved by name. The path need not exist.
+__shard_abs_path() {
+  case $1 in
+    /*) set -- "$1" ;;
+    *) set -- "${PWD%/}/$1" ;;
+  esac
+  printf '%s\n' "$1" | awk -F/ '{
+    n = 0
+    for (i = 1; i <= NF; i++) {
+      if ($i == "" || $i == ".") continue
+      if ($i == "..") { if (n > 0) n--; continue }
+      part[++n] = $i
+    }
+    path = ""
+    for (i = 1; i <= n; i++) path = path "/" part[i]
+    print (path == "" ? "/" : path)
+  }'
+}"#,
+    },
+    Helper {
+        name: "tempfile",
+        deps: &[],
+        source: r#"# A new file, removed when the script exits.
+__shard_tempfile() {
+  mktemp "${__shard_tmp:?}/file.XXXXXX"
+}"#,
+    },
+    Helper {
+        name: "tempdir",
+        deps: &[],
+        source: r#"# A new directory, removed when the script exits.
+__shard_tempdir() {
+  mktemp -d "${__shard_tmp:?}/dir.XXXXXX"
+}"#,
+    },
+];
+
+pub fn helper(name: &str) -> Option<&'static Helper> {
+    HELPERS.iter().find(|h| h.name == name)
+}
+
+/// `used` and everything it depends on, dependencies first, each once.
+pub fn resolve<'a>(used: impl IntoIterator<Item = &'a str>, helpers: &[Helper]) -> Vec<Helper> {
+    fn visit(name: &str, helpers: &[Helper], seen: &mut BTreeSet<String>, order: &mut Vec<Helper>) {
+        if !seen.insert(name.to_string()) {
+            return;
+        }
+        if let Some(helper) = helpers.iter().find(|h| h.name == name) {
+            for dep in helper.deps {
+                visit(dep, helpers, seen, order);
+            }
+            order.push(*helper);
+        }
+    }
+
+    let mut seen = BTreeSet::new();
+    let mut order = Vec::new();
+    for name in used {
+        visit(name, helpers, &mut seen, &mut order);
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAKE: &[Helper] = &[
+        Helper {
+            name: "a",
+            deps: &["b", "c"],
+            source: "",
+        },
+        Helper {
+            name: "b",
+            deps: &["c"],
+            source: "",
+        },
+        Helper {
+            name: "c",
+            deps: &[],
+            source: "",
+        },
+        Helper {
+            name: "d",
+            deps: &[],
+            source: "",
+        },
+    ];
+
+    #[test]
+    fn test_resolve_orders_dependencies_first() {
+        let names: Vec<&str> = resolve(["a"], FAKE).iter().map(|h| h.name).collect();
+        assert_eq!(names, vec!["c", "b", "a"]);
+    }
+
+    #[test]
+    fn test_resolve_emits_each_helper_once() {
+        let names: Vec<&str> = resolve(["b", "a", "c"], FAKE)
+            .iter()
+            .map(|h| h.name)
+            .collect();
+        assert_eq!(names, vec!["c", "b", "a"]);
+    }
+
+    #[test]
+    fn test_every_dependency_exists() {
+        for helper in HELPERS {
+            for dep in helper.deps {
+                assert!(
+                    super::helper(dep).is_some(),
+                    "{} needs {}",
+                    helper.name,
+                    dep
+                );
+            }
+        }
+    }
+}
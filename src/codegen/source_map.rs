@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+/// Maps lines of a generated script back to the Shard source.
+///
+/// Serialized as JSON next to the script, e.g. `deploy.sh.map` for
+/// `deploy.sh`. Lines and columns are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceMap {
+    pub version: u32,
+    /// The generated script, when known.
+    pub file: Option<String>,
+    /// The Shard source the script was generated from, when known.
+    pub source: Option<String>,
+    /// One entry per generated line that came from a Shard statement, in
+    /// increasing `generated_line` order.
+    pub mappings: Vec<Mapping>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mapping {
+    pub generated_line: usize,
+    pub source_line: usize,
+    pub source_column: usize,
+}
+
+impl SourceMap {
+    pub const VERSION: u32 = 1;
+
+    /// The Shard location of `generated_line`, if it has one.
+    pub fn lookup(&self, generated_line: usize) -> Option<&Mapping> {
+        self.mappings
+            .binary_search_by_key(&generated_line, |m| m.generated_line)
+            .ok()
+            .map(|i| &self.mappings[i])
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl Default for SourceMap {
+    fn default() -> Self {
+        SourceMap {
+            version: Self::VERSION,
+            file: None,
+            source: None,
+            mappings: Vec::new(),
+        }
+    }
+}
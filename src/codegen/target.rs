@@ -0,0 +1,53 @@
+use crate::codegen::compliance::Feature;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Shell dialect the generated script is written for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// Strictly POSIX `sh`; runs on dash, bash, zsh and ash alike.
+    #[default]
+    Posix,
+    /// GNU bash 4+, with native indexed and associative arrays.
+    Bash,
+    /// zsh, with `KSH_ARRAYS` set so arrays are zero-based like bash.
+    /// Experimental: its output is not checked against a zsh install.
+    Zsh,
+    /// BusyBox `ash`, which adds `local` and `pipefail` to POSIX.
+    Busybox,
+}
+
+impl Target {
+    pub fn name(self) -> &'static str {
+        match self {
+            Target::Posix => "posix",
+            Target::Bash => "bash",
+            Target::Zsh => "zsh",
+            Target::Busybox => "busybox",
+        }
+    }
+
+    pub fn shebang(self) -> &'static str {
+        match self {
+            Target::Posix | Target::Busybox => "#!/bin/sh",
+            Target::Bash => "#!/usr/bin/env bash",
+            Target::Zsh => "#!/usr/bin/env zsh",
+        }
+    }
+
+    /// Whether output for this target is untested, and may not run.
+    pub fn is_experimental(self) -> bool {
+        self == Target::Zsh
+    }
+
+    pub fn supports(self, feature: Feature) -> bool {
+        feature.supported_by(self)
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -10,9 +10,18 @@ pub enum ShardError {
     #[error("Parser error: {0}")]
     Parser(String),
 
+    #[error("Semantic error: {0}")]
+    Sema(String),
+
     #[error("Codegen error: {0}")]
     Codegen(String),
 
+    #[error("Language server error: {0}")]
+    Lsp(String),
+
+    #[error("REPL error: {0}")]
+    Repl(String),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -38,10 +47,28 @@ impl From<crate::parser::error::ParserError> for ShardError {
     }
 }
 
+impl From<crate::sema::error::SemaError> for ShardError {
+    fn from(e: crate::sema::error::SemaError) -> Self {
+        ShardError::Sema(format!("{:?}", e))
+    }
+}
+
 impl From<crate::codegen::error::CodegenError> for ShardError {
     fn from(e: crate::codegen::error::CodegenError) -> Self {
         ShardError::Codegen(format!("{:?}", e))
     }
 }
 
+impl From<crate::lsp::error::LspError> for ShardError {
+    fn from(e: crate::lsp::error::LspError) -> Self {
+        ShardError::Lsp(format!("{:?}", e))
+    }
+}
+
+impl From<crate::repl::error::ReplError> for ShardError {
+    fn from(e: crate::repl::error::ReplError) -> Self {
+        ShardError::Repl(format!("{:?}", e))
+    }
+}
+
 pub type Result<T> = std::result::Result<T, ShardError>;
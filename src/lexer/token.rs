@@ -6,6 +6,7 @@ use std::str::Chars;
 pub enum TokenType {
     Identifier,
     Integer,
+    Float,
     Boolean,
     Null,
     String,
@@ -34,6 +35,12 @@ pub enum TokenType {
     Comma,
     Colon,
     Arrow,
+    FatArrow,
+    Pipe,
+    Dot,
+    DotDot,
+    DotDotEq,
+    Ellipsis,
     Newline,
     Whitespace,
     Comment,
@@ -45,6 +52,10 @@ pub struct Token {
     pub token_type: TokenType,
     pub span: (usize, usize),
     pub value: Option<String>,
+    /// 1-based line of the first character, or 0 when unknown.
+    pub line: usize,
+    /// 1-based column of the first character, or 0 when unknown.
+    pub column: usize,
 }
 
 impl Token {
@@ -53,8 +64,16 @@ impl Token {
             token_type,
             span: (start, end),
             value,
+            line: 0,
+            column: 0,
         }
     }
+
+    pub fn at(mut self, line: usize, column: usize) -> Self {
+        self.line = line;
+        self.column = column;
+        self
+    }
 }
 
 pub type SpannedToken = (usize, Token, usize);
@@ -64,6 +83,8 @@ pub struct Lexer<'a> {
     input: &'a str,
     chars: Chars<'a>,
     pos: usize,
+    line: usize,
+    column: usize,
     current_char: Option<char>,
 }
 
@@ -75,11 +96,19 @@ impl<'a> Lexer<'a> {
             input,
             chars,
             pos: 0,
+            line: 1,
+            column: 1,
             current_char,
         }
     }
 
     fn advance(&mut self) {
+        if self.current_char == Some('\n') {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
         self.pos += 1;
         self.current_char = self.chars.next();
     }
@@ -104,27 +133,47 @@ impl<'a> Lexer<'a> {
     }
 
     fn read_identifier(&mut self) -> String {
-        let start = self.pos;
+        // `pos` counts characters, so the name is collected rather than
+        // sliced from the input by bytes.
+        let mut name = String::new();
         while let Some(c) = self.current_char {
-            if c.is_alphanumeric() || c == '_' || c == '.' {
+            // `s.host` is a field of `s`, lexed as its own tokens.
+            if c.is_alphanumeric() || c == '_' {
+                name.push(c);
                 self.advance();
             } else {
                 break;
             }
         }
-        self.input[start..self.pos].to_string()
+        name
     }
 
-    fn read_number(&mut self) -> String {
-        let start = self.pos;
-        while let Some(c) = self.current_char {
-            if c.is_ascii_digit() {
-                self.advance();
-            } else {
-                break;
-            }
+    /// An integer, or a float when the digits are followed by `.` and more
+    /// digits. `1..2` stays an integer followed by dots.
+    fn read_number(&mut self) -> (TokenType, String) {
+        let mut value = self.read_digits();
+        let fraction_follows = self.current_char == Some('.')
+            && self
+                .chars
+                .clone()
+                .next()
+                .is_some_and(|c| c.is_ascii_digit());
+        if !fraction_follows {
+            return (TokenType::Integer, value);
+        }
+        self.advance();
+        value.push('.');
+        value.push_str(&self.read_digits());
+        (TokenType::Float, value)
+    }
+
+    fn read_digits(&mut self) -> String {
+        let mut digits = String::new();
+        while let Some(c) = self.current_char.filter(char::is_ascii_digit) {
+            digits.push(c);
+            self.advance();
         }
-        self.input[start..self.pos].to_string()
+        digits
     }
 
     fn read_string(&mut self) -> Result<String, LexerError> {
@@ -186,11 +235,10 @@ impl<'a> Iterator for Lexer<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         self.skip_whitespace();
 
-        if self.current_char.is_none() {
-            return None;
-        }
+        self.current_char?;
 
         let start = self.pos;
+        let (line, column) = (self.line, self.column);
 
         let token = match self.current_char {
             Some('#') => {
@@ -225,6 +273,12 @@ impl<'a> Iterator for Lexer<'a> {
                     "return" => Token::new(TokenType::Identifier, start, self.pos, Some(value)),
                     "try" => Token::new(TokenType::Identifier, start, self.pos, Some(value)),
                     "catch" => Token::new(TokenType::Identifier, start, self.pos, Some(value)),
+                    "match" => Token::new(TokenType::Identifier, start, self.pos, Some(value)),
+                    "global" => Token::new(TokenType::Identifier, start, self.pos, Some(value)),
+                    "let" => Token::new(TokenType::Identifier, start, self.pos, Some(value)),
+                    "const" => Token::new(TokenType::Identifier, start, self.pos, Some(value)),
+                    "struct" => Token::new(TokenType::Identifier, start, self.pos, Some(value)),
+                    "enum" => Token::new(TokenType::Identifier, start, self.pos, Some(value)),
                     "and" => Token::new(TokenType::And, start, self.pos, Some(value)),
                     "or" => Token::new(TokenType::Or, start, self.pos, Some(value)),
                     "not" => Token::new(TokenType::Not, start, self.pos, Some(value)),
@@ -232,8 +286,8 @@ impl<'a> Iterator for Lexer<'a> {
                 }
             }
             Some(c) if c.is_ascii_digit() => {
-                let value = self.read_number();
-                Token::new(TokenType::Integer, start, self.pos, Some(value))
+                let (token_type, value) = self.read_number();
+                Token::new(token_type, start, self.pos, Some(value))
             }
             Some('\'') => match self.read_string() {
                 Ok(value) => Token::new(TokenType::String, start, self.pos, Some(value)),
@@ -244,6 +298,9 @@ impl<'a> Iterator for Lexer<'a> {
                 if self.current_char == Some('=') {
                     self.advance();
                     Token::new(TokenType::EqEq, start, self.pos, None)
+                } else if self.current_char == Some('>') {
+                    self.advance();
+                    Token::new(TokenType::FatArrow, start, self.pos, None)
                 } else {
                     Token::new(TokenType::Equals, start, self.pos, None)
                 }
@@ -324,6 +381,27 @@ impl<'a> Iterator for Lexer<'a> {
                 self.advance();
                 Token::new(TokenType::RBrace, start, self.pos, None)
             }
+            Some('|') => {
+                self.advance();
+                Token::new(TokenType::Pipe, start, self.pos, None)
+            }
+            Some('.') if self.chars.clone().next() == Some('.') => {
+                self.advance();
+                self.advance();
+                if self.current_char == Some('=') {
+                    self.advance();
+                    Token::new(TokenType::DotDotEq, start, self.pos, None)
+                } else if self.current_char == Some('.') {
+                    self.advance();
+                    Token::new(TokenType::Ellipsis, start, self.pos, None)
+                } else {
+                    Token::new(TokenType::DotDot, start, self.pos, None)
+                }
+            }
+            Some('.') => {
+                self.advance();
+                Token::new(TokenType::Dot, start, self.pos, None)
+            }
             Some(',') => {
                 self.advance();
                 Token::new(TokenType::Comma, start, self.pos, None)
@@ -343,20 +421,18 @@ impl<'a> Iterator for Lexer<'a> {
             None => unreachable!(),
         };
 
-        Some(Ok((start, token, self.pos)))
+        Some(Ok((start, token.at(line, column), self.pos)))
     }
 }
 
 pub fn tokenize(input: &str) -> LexerResult<Vec<SpannedToken>> {
-    let lexer = Lexer::new(input);
+    let mut lexer = Lexer::new(input);
     let mut tokens = Vec::new();
-    for result in lexer {
+    for result in lexer.by_ref() {
         tokens.push(result?);
     }
-    tokens.push((
-        tokens.len(),
-        Token::new(TokenType::EOF, tokens.len(), tokens.len(), None),
-        tokens.len(),
-    ));
+    let end = lexer.pos;
+    let eof = Token::new(TokenType::EOF, end, end, None).at(lexer.line, lexer.column);
+    tokens.push((end, eof, end));
     Ok(tokens)
 }
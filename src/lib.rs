@@ -2,10 +2,16 @@ pub mod ast;
 pub mod codegen;
 pub mod error;
 pub mod lexer;
+pub mod lsp;
+pub mod optimizer;
 pub mod parser;
+pub mod repl;
+pub mod sema;
 
 pub use crate::ast::{Expression, Literal, Program, Statement};
-pub use crate::codegen::generate;
+pub use crate::codegen::{generate, generate_with_options, CodegenOptions, Target};
 pub use crate::error::{Result, ShardError};
 pub use crate::lexer::tokenize;
-pub use crate::parser::parse;
+pub use crate::optimizer::{optimize, OptLevel};
+pub use crate::parser::{parse, parse_with_locations};
+pub use crate::sema::check;
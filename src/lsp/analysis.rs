@@ -0,0 +1,467 @@
+use crate::ast::Statement;
+use crate::lexer::{tokenize, SpannedToken, Token, TokenType};
+use crate::parser::{parse, parse_with_locations};
+pub use crate::sema::types::infer_type;
+use crate::sema::types::Type;
+use crate::sema::{check_with_locations, BUILTINS};
+
+pub const KEYWORDS: &[&str] = &[
+    "if", "else", "while", "for", "in", "fn", "return", "try", "catch", "break", "continue", "and",
+    "or", "not", "true", "false", "null", "global", "let", "const", "struct", "enum",
+];
+
+/// Zero-based line/column pair. Columns are counted in UTF-16 code units,
+/// as LSP clients count them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+/// Maps character offsets (as used in token spans) to line/column positions.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+    /// The UTF-16 code units before each character, and after the last.
+    utf16: Vec<usize>,
+    len: usize,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let mut line_starts = vec![0];
+        let mut utf16 = vec![0];
+        for (i, c) in text.chars().enumerate() {
+            if c == '\n' {
+                line_starts.push(i + 1);
+            }
+            utf16.push(utf16[i] + c.len_utf16());
+        }
+        let len = utf16.len() - 1;
+        LineIndex {
+            line_starts,
+            utf16,
+            len,
+        }
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.len);
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        Position {
+            line: line as u32,
+            character: (self.utf16[offset] - self.utf16[self.line_starts[line]]) as u32,
+        }
+    }
+
+    /// The character at `position`, or the one after it when the column
+    /// falls inside a surrogate pair.
+    pub fn offset(&self, position: Position) -> usize {
+        let line = (position.line as usize).min(self.line_starts.len() - 1);
+        let start = self.line_starts[line];
+        let line_end = self
+            .line_starts
+            .get(line + 1)
+            .map_or(self.len, |next| next - 1);
+        let column = self.utf16[start] + position.character as usize;
+        let within = self.utf16[start..=line_end].partition_point(|&units| units < column);
+        (start + within).min(line_end)
+    }
+
+    pub fn end(&self) -> Position {
+        self.position(self.len)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Variable,
+    Parameter,
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Span of the defining name token.
+    pub span: (usize, usize),
+    /// Span of the whole definition (the full `fn` block for functions).
+    pub full_span: (usize, usize),
+    /// Region of the document in which the name is visible.
+    pub scope: (usize, usize),
+    /// Signature for functions, inferred type for variables.
+    pub detail: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub span: (usize, usize),
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Keyword,
+    Function,
+    Variable,
+}
+
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: Option<String>,
+}
+
+/// Everything the language server knows about one document.
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub tokens: Vec<SpannedToken>,
+    pub symbols: Vec<Symbol>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Analysis {
+    pub fn new(text: &str) -> Self {
+        let tokens = match tokenize(text) {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                let span = match &e {
+                    crate::lexer::LexerError::UnexpectedChar { span, .. }
+                    | crate::lexer::LexerError::UnterminatedString { span, .. } => *span,
+                };
+                return Analysis {
+                    tokens: Vec::new(),
+                    symbols: Vec::new(),
+                    diagnostics: vec![Diagnostic {
+                        span,
+                        message: e.to_string(),
+                    }],
+                };
+            }
+        };
+
+        let mut diagnostics = Vec::new();
+        match parse_with_locations(tokens.clone()) {
+            Err(e) => diagnostics.push(Diagnostic {
+                span: e.span().unwrap_or((0, 0)),
+                message: e.to_string(),
+            }),
+            Ok(program) => {
+                if let Err((e, location)) = check_with_locations(&program) {
+                    diagnostics.push(Diagnostic {
+                        span: location.map_or((0, 0), |location| line_span(&tokens, location)),
+                        message: e.to_string(),
+                    });
+                }
+            }
+        }
+
+        let symbols = collect_symbols(&tokens);
+
+        Analysis {
+            tokens,
+            symbols,
+            diagnostics,
+        }
+    }
+
+    /// The identifier token under `offset`, if any. A cursor placed right
+    /// after an identifier still counts as being on it.
+    pub fn identifier_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.iter().map(|(_, token, _)| token).find(|token| {
+            token.token_type == TokenType::Identifier
+                && token.span.0 <= offset
+                && offset <= token.span.1
+        })
+    }
+
+    pub fn functions(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols
+            .iter()
+            .filter(|symbol| symbol.kind == SymbolKind::Function)
+    }
+
+    /// Symbols visible at `offset`, innermost scope first.
+    pub fn visible_at(&self, offset: usize) -> Vec<&Symbol> {
+        let mut visible: Vec<&Symbol> = self
+            .symbols
+            .iter()
+            .filter(|symbol| symbol.scope.0 <= offset && offset <= symbol.scope.1)
+            .collect();
+        visible.sort_by_key(|symbol| (symbol.scope.1 - symbol.scope.0, symbol.span.0));
+        visible
+    }
+
+    pub fn definition(&self, offset: usize) -> Option<&Symbol> {
+        let token = self.identifier_at(offset)?;
+        let name = token.value.as_deref()?;
+        self.visible_at(offset)
+            .into_iter()
+            .find(|symbol| symbol.name == name)
+    }
+
+    pub fn hover(&self, offset: usize) -> Option<String> {
+        let symbol = self.definition(offset)?;
+        let text = match symbol.kind {
+            SymbolKind::Function => symbol.detail.clone(),
+            SymbolKind::Variable => format!("{}: {}", symbol.name, symbol.detail),
+            SymbolKind::Parameter => format!("(parameter) {}", symbol.name),
+        };
+        Some(format!("```shard\n{}\n```", text))
+    }
+
+    pub fn completions(&self, offset: usize) -> Vec<Completion> {
+        let mut items: Vec<Completion> = KEYWORDS
+            .iter()
+            .map(|keyword| Completion {
+                label: keyword.to_string(),
+                kind: CompletionKind::Keyword,
+                detail: None,
+            })
+            .collect();
+
+        for symbol in self.visible_at(offset) {
+            if items.iter().any(|item| item.label == symbol.name) {
+                continue;
+            }
+            let kind = match symbol.kind {
+                SymbolKind::Function => CompletionKind::Function,
+                SymbolKind::Variable | SymbolKind::Parameter => CompletionKind::Variable,
+            };
+            items.push(Completion {
+                label: symbol.name.clone(),
+                kind,
+                detail: Some(symbol.detail.clone()),
+            });
+        }
+
+        for builtin in BUILTINS {
+            items.push(Completion {
+                label: builtin.name.to_string(),
+                kind: CompletionKind::Function,
+                detail: Some(builtin.signature()),
+            });
+        }
+
+        items
+    }
+}
+
+fn token_type(tokens: &[SpannedToken], i: usize) -> TokenType {
+    tokens
+        .get(i)
+        .map_or(TokenType::EOF, |(_, token, _)| token.token_type.clone())
+}
+
+fn token_value(tokens: &[SpannedToken], i: usize) -> Option<&str> {
+    tokens
+        .get(i)
+        .and_then(|(_, token, _)| token.value.as_deref())
+}
+
+fn is_keyword(tokens: &[SpannedToken], i: usize, keyword: &str) -> bool {
+    token_type(tokens, i) == TokenType::Identifier && token_value(tokens, i) == Some(keyword)
+}
+
+/// Index of the `}` matching the `{` at `open`, or the last token.
+fn matching_brace(tokens: &[SpannedToken], open: usize) -> usize {
+    let mut depth = 0;
+    for (i, (_, token, _)) in tokens.iter().enumerate().skip(open) {
+        match token.token_type {
+            TokenType::LBrace => depth += 1,
+            TokenType::RBrace => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len().saturating_sub(1)
+}
+
+/// Index one past the last token of the simple statement starting at `start`.
+fn statement_end(tokens: &[SpannedToken], start: usize) -> usize {
+    let mut depth = 0i32;
+    let mut i = start;
+    while i < tokens.len() {
+        match token_type(tokens, i) {
+            TokenType::Newline | TokenType::EOF | TokenType::Comment => break,
+            TokenType::LBrace | TokenType::LBracket | TokenType::LParen => depth += 1,
+            TokenType::RBrace | TokenType::RBracket | TokenType::RParen => {
+                if depth == 0 {
+                    break;
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    i
+}
+
+/// Span of the first line of the statement starting at the 1-based
+/// `line` and `column`.
+fn line_span(tokens: &[SpannedToken], (line, column): (usize, usize)) -> (usize, usize) {
+    let Some(start) = tokens
+        .iter()
+        .position(|(_, token, _)| token.line == line && token.column == column)
+    else {
+        return (0, 0);
+    };
+    let end = statement_end(tokens, start).max(start + 1);
+    (tokens[start].1.span.0, tokens[end - 1].1.span.1)
+}
+
+fn assignment_type(tokens: &[SpannedToken], start: usize) -> Type {
+    let end = statement_end(tokens, start);
+    let mut slice = tokens[start..end].to_vec();
+    let eof_at = slice.last().map_or(0, |(_, token, _)| token.span.1);
+    slice.push((
+        eof_at,
+        Token::new(TokenType::EOF, eof_at, eof_at, None),
+        eof_at,
+    ));
+    match parse(slice) {
+        Ok(program) => match program.0.first() {
+            Some(Statement::Assignment { value, .. }) => infer_type(value),
+            _ => Type::Unknown,
+        },
+        Err(_) => Type::Unknown,
+    }
+}
+
+fn collect_symbols(tokens: &[SpannedToken]) -> Vec<Symbol> {
+    let document_end = tokens.last().map_or(0, |(_, token, _)| token.span.1);
+    let mut symbols = Vec::new();
+    // Body spans of the functions enclosing the current token.
+    let mut functions: Vec<(usize, usize)> = Vec::new();
+    let mut statement_start = true;
+
+    for i in 0..tokens.len() {
+        let (_, token, _) = &tokens[i];
+        let offset = token.span.0;
+        while functions.last().is_some_and(|body| offset > body.1) {
+            functions.pop();
+        }
+        let scope = functions.last().copied().unwrap_or((0, document_end));
+
+        let at_statement_start = statement_start;
+        statement_start = matches!(
+            token.token_type,
+            TokenType::Newline | TokenType::LBrace | TokenType::RBrace | TokenType::Comment
+        );
+
+        if token.token_type != TokenType::Identifier {
+            continue;
+        }
+        let name = token.value.clone().unwrap_or_default();
+
+        if at_statement_start && name == "fn" {
+            if token_type(tokens, i + 1) != TokenType::Identifier {
+                continue;
+            }
+            let (_, name_token, _) = &tokens[i + 1];
+            let mut params = Vec::new();
+            let mut j = i + 2;
+            if token_type(tokens, j) == TokenType::LParen {
+                // Names start a parameter; defaults may hold other names
+                // and calls.
+                let mut depth = 0;
+                j += 1;
+                while j < tokens.len() && (depth > 0 || token_type(tokens, j) != TokenType::RParen)
+                {
+                    match token_type(tokens, j) {
+                        TokenType::LParen => depth += 1,
+                        TokenType::RParen => depth -= 1,
+                        TokenType::Identifier
+                            if depth == 0
+                                && matches!(
+                                    token_type(tokens, j - 1),
+                                    TokenType::LParen | TokenType::Comma | TokenType::Ellipsis
+                                ) =>
+                        {
+                            params.push(j);
+                        }
+                        _ => {}
+                    }
+                    j += 1;
+                }
+            }
+            while j < tokens.len()
+                && !matches!(token_type(tokens, j), TokenType::LBrace | TokenType::EOF)
+            {
+                j += 1;
+            }
+            let close = matching_brace(tokens, j);
+            let body = (
+                tokens.get(j).map_or(document_end, |(_, t, _)| t.span.0),
+                tokens[close].1.span.1,
+            );
+            let param_names: Vec<String> = params
+                .iter()
+                .map(|&p| token_value(tokens, p).unwrap_or_default().to_string())
+                .collect();
+            let fn_name = name_token.value.clone().unwrap_or_default();
+
+            symbols.push(Symbol {
+                detail: format!("fn {}({})", fn_name, param_names.join(", ")),
+                name: fn_name,
+                kind: SymbolKind::Function,
+                span: name_token.span,
+                full_span: (token.span.0, body.1),
+                scope: (0, document_end),
+            });
+            for (&p, param) in params.iter().zip(param_names) {
+                symbols.push(Symbol {
+                    name: param,
+                    kind: SymbolKind::Parameter,
+                    span: tokens[p].1.span,
+                    full_span: tokens[p].1.span,
+                    scope: body,
+                    detail: "parameter".to_string(),
+                });
+            }
+            functions.push(body);
+            continue;
+        }
+
+        let declared =
+            i > 0 && (is_keyword(tokens, i - 1, "let") || is_keyword(tokens, i - 1, "const"));
+        let defines =
+            if (at_statement_start || declared) && token_type(tokens, i + 1) == TokenType::Equals {
+                Some(assignment_type(tokens, i))
+            } else if i > 0 && is_keyword(tokens, i - 1, "for") && is_keyword(tokens, i + 1, "in") {
+                Some(Type::Unknown)
+            } else if i > 0 && is_keyword(tokens, i - 1, "catch") {
+                Some(Type::String)
+            } else {
+                None
+            };
+
+        if let Some(ty) = defines {
+            let already_defined = symbols
+                .iter()
+                .any(|symbol| symbol.name == name && symbol.scope == scope);
+            if !already_defined {
+                symbols.push(Symbol {
+                    name,
+                    kind: SymbolKind::Variable,
+                    span: token.span,
+                    full_span: token.span,
+                    scope,
+                    detail: ty.to_string(),
+                });
+            }
+        }
+    }
+
+    symbols
+}
@@ -0,0 +1,18 @@
+use miette::Diagnostic;
+use thiserror::Error;
+
+#[derive(Error, Debug, Diagnostic)]
+#[diagnostic(code(shard::lsp))]
+pub enum LspError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Malformed message: {0}")]
+    #[diagnostic(help("Messages must be framed with a Content-Length header"))]
+    Protocol(String),
+
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+pub type LspResult<T> = std::result::Result<T, LspError>;
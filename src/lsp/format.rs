@@ -0,0 +1,81 @@
+use crate::lexer::{tokenize, TokenType};
+use crate::lsp::analysis::LineIndex;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct LineInfo {
+    opens: i64,
+    closes: i64,
+    leading_closes: i64,
+    /// The line starts inside a multi-line string and must be kept as is.
+    verbatim: bool,
+    has_tokens: bool,
+}
+
+/// Re-indent Shard source by block depth.
+///
+/// Formatting works on tokens rather than the AST so comments and the
+/// author's line breaks survive. Returns `None` when the source does not lex.
+pub fn format_source(text: &str, indent: &str) -> Option<String> {
+    let tokens = tokenize(text).ok()?;
+    let index = LineIndex::new(text);
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut info = vec![LineInfo::default(); lines.len()];
+
+    for (_, token, _) in &tokens {
+        if token.token_type == TokenType::EOF || token.token_type == TokenType::Newline {
+            continue;
+        }
+        let start = index.position(token.span.0).line as usize;
+        let end = index
+            .position(token.span.1.saturating_sub(1).max(token.span.0))
+            .line as usize;
+        for line in info.iter_mut().take(end + 1).skip(start + 1) {
+            line.verbatim = true;
+        }
+
+        let line = &mut info[start];
+        match token.token_type {
+            TokenType::LBrace => line.opens += 1,
+            TokenType::RBrace => {
+                line.closes += 1;
+                if line.opens == 0 && line.closes == line.leading_closes + 1 && !line.has_tokens {
+                    line.leading_closes += 1;
+                }
+            }
+            _ => {}
+        }
+        if token.token_type != TokenType::RBrace {
+            line.has_tokens = true;
+        }
+    }
+
+    let mut output: Vec<String> = Vec::with_capacity(lines.len());
+    let mut depth: i64 = 0;
+    for (line, info) in lines.iter().zip(&info) {
+        if info.verbatim {
+            output.push(line.to_string());
+        } else {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                if output.last().is_some_and(|previous| !previous.is_empty()) {
+                    output.push(String::new());
+                }
+            } else {
+                let level = (depth - info.leading_closes).max(0) as usize;
+                output.push(format!("{}{}", indent.repeat(level), trimmed));
+            }
+        }
+        depth = (depth + info.opens - info.closes).max(0);
+    }
+
+    while output.last().is_some_and(|line| line.is_empty()) {
+        output.pop();
+    }
+    if output.is_empty() {
+        return Some(String::new());
+    }
+
+    let mut formatted = output.join("\n");
+    formatted.push('\n');
+    Some(formatted)
+}
@@ -0,0 +1,9 @@
+pub mod analysis;
+pub mod error;
+pub mod format;
+pub mod server;
+
+pub use analysis::{Analysis, LineIndex, Position, Symbol, SymbolKind};
+pub use error::{LspError, LspResult};
+pub use format::format_source;
+pub use server::{run, Server};
@@ -0,0 +1,308 @@
+use crate::lsp::analysis::{Analysis, CompletionKind, LineIndex, Position, SymbolKind};
+use crate::lsp::error::{LspError, LspResult};
+use crate::lsp::format::format_source;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INVALID_REQUEST: i64 = -32600;
+
+/// Run the language server on stdin/stdout until the client sends `exit`.
+pub fn run() -> LspResult<()> {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    Server::new().serve(&mut stdin.lock(), &mut stdout.lock())
+}
+
+pub fn read_message(reader: &mut impl BufRead) -> LspResult<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            if content_length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                let length = value
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| LspError::Protocol(format!("bad header: {}", header)))?;
+                content_length = Some(length);
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length.unwrap_or(0)];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> LspResult<()> {
+    let body = serde_json::to_string(message)?;
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+pub struct Server {
+    documents: HashMap<String, String>,
+    shutdown_requested: bool,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Server::default()
+    }
+
+    pub fn serve(&mut self, reader: &mut impl BufRead, writer: &mut impl Write) -> LspResult<()> {
+        loop {
+            let message = match read_message(reader) {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                // The body was read whole, so the next message can be.
+                Err(LspError::Json(error)) => {
+                    let response = json!({
+                        "jsonrpc": "2.0",
+                        "id": null,
+                        "error": { "code": PARSE_ERROR, "message": error.to_string() },
+                    });
+                    write_message(writer, &response)?;
+                    continue;
+                }
+                Err(error) => return Err(error),
+            };
+            let method = message["method"].as_str().unwrap_or_default().to_string();
+            let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+            match message.get("id").cloned() {
+                Some(id) if !method.is_empty() => {
+                    let response = match self.handle_request(&method, &params) {
+                        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                        Err((code, text)) => json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "error": { "code": code, "message": text },
+                        }),
+                    };
+                    write_message(writer, &response)?;
+                }
+                // Responses to server-initiated requests are not used.
+                Some(_) => {}
+                None => {
+                    if method == "exit" {
+                        return Ok(());
+                    }
+                    for notification in self.handle_notification(&method, &params) {
+                        write_message(writer, &notification)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        if self.shutdown_requested {
+            return Err((INVALID_REQUEST, "server is shutting down".to_string()));
+        }
+
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "documentSymbolProvider": true,
+                    "completionProvider": { "resolveProvider": false },
+                    "documentFormattingProvider": true,
+                },
+                "serverInfo": { "name": "shard", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => {
+                self.shutdown_requested = true;
+                Ok(Value::Null)
+            }
+            "textDocument/definition" => {
+                let (uri, text, offset) = self.locate(params)?;
+                let analysis = Analysis::new(text);
+                Ok(analysis.definition(offset).map_or(Value::Null, |symbol| {
+                    json!({ "uri": uri, "range": range(&LineIndex::new(text), symbol.span) })
+                }))
+            }
+            "textDocument/hover" => {
+                let (_, text, offset) = self.locate(params)?;
+                let analysis = Analysis::new(text);
+                Ok(analysis.hover(offset).map_or(
+                    Value::Null,
+                    |value| json!({ "contents": { "kind": "markdown", "value": value } }),
+                ))
+            }
+            "textDocument/documentSymbol" => {
+                let (_, text) = self.document(params)?;
+                let index = LineIndex::new(text);
+                let symbols: Vec<Value> = Analysis::new(text)
+                    .functions()
+                    .map(|symbol| {
+                        json!({
+                            "name": symbol.name,
+                            "detail": symbol.detail,
+                            "kind": symbol_kind(symbol.kind),
+                            "range": range(&index, symbol.full_span),
+                            "selectionRange": range(&index, symbol.span),
+                        })
+                    })
+                    .collect();
+                Ok(Value::Array(symbols))
+            }
+            "textDocument/completion" => {
+                let (_, text, offset) = self.locate(params)?;
+                let items: Vec<Value> = Analysis::new(text)
+                    .completions(offset)
+                    .into_iter()
+                    .map(|item| {
+                        let kind = match item.kind {
+                            CompletionKind::Keyword => 14,
+                            CompletionKind::Function => 3,
+                            CompletionKind::Variable => 6,
+                        };
+                        json!({ "label": item.label, "kind": kind, "detail": item.detail })
+                    })
+                    .collect();
+                Ok(json!({ "isIncomplete": false, "items": items }))
+            }
+            "textDocument/formatting" => {
+                let (_, text) = self.document(params)?;
+                let options = &params["options"];
+                let indent = if options["insertSpaces"].as_bool().unwrap_or(true) {
+                    " ".repeat(options["tabSize"].as_u64().unwrap_or(4) as usize)
+                } else {
+                    "\t".to_string()
+                };
+                let edits = match format_source(text, &indent) {
+                    Some(formatted) if formatted != *text => {
+                        let index = LineIndex::new(text);
+                        vec![json!({
+                            "range": {
+                                "start": position(Position { line: 0, character: 0 }),
+                                "end": position(index.end()),
+                            },
+                            "newText": formatted,
+                        })]
+                    }
+                    _ => Vec::new(),
+                };
+                Ok(Value::Array(edits))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("unhandled method: {}", method))),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string();
+                self.documents.insert(uri.clone(), text);
+                vec![self.diagnostics(&uri)]
+            }
+            "textDocument/didChange" => {
+                // Full document sync: the last change holds the whole text.
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.documents.insert(uri.clone(), text.to_string());
+                }
+                vec![self.diagnostics(&uri)]
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                vec![publish_diagnostics(&uri, Vec::new())]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn diagnostics(&self, uri: &str) -> Value {
+        let text = self.documents.get(uri).map_or("", String::as_str);
+        let index = LineIndex::new(text);
+        let diagnostics = Analysis::new(text)
+            .diagnostics
+            .into_iter()
+            .map(|diagnostic| {
+                json!({
+                    "range": range(&index, diagnostic.span),
+                    "severity": 1,
+                    "source": "shard",
+                    "message": diagnostic.message,
+                })
+            })
+            .collect();
+        publish_diagnostics(uri, diagnostics)
+    }
+
+    fn document<'a>(&'a self, params: &Value) -> Result<(&'a str, &'a String), (i64, String)> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .ok_or((INVALID_PARAMS, "missing textDocument.uri".to_string()))?;
+        self.documents
+            .get_key_value(uri)
+            .map(|(uri, text)| (uri.as_str(), text))
+            .ok_or((INVALID_PARAMS, format!("unknown document: {}", uri)))
+    }
+
+    fn locate<'a>(&'a self, params: &Value) -> Result<(&'a str, &'a String, usize), (i64, String)> {
+        let (uri, text) = self.document(params)?;
+        let position = Position {
+            line: params["position"]["line"].as_u64().unwrap_or(0) as u32,
+            character: params["position"]["character"].as_u64().unwrap_or(0) as u32,
+        };
+        Ok((uri, text, LineIndex::new(text).offset(position)))
+    }
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+fn position(position: Position) -> Value {
+    json!({ "line": position.line, "character": position.character })
+}
+
+fn range(index: &LineIndex, span: (usize, usize)) -> Value {
+    json!({
+        "start": position(index.position(span.0)),
+        "end": position(index.position(span.1)),
+    })
+}
+
+fn symbol_kind(kind: SymbolKind) -> i64 {
+    match kind {
+        SymbolKind::Function => 12,
+        SymbolKind::Variable => 13,
+        SymbolKind::Parameter => 13,
+    }
+}
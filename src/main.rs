@@ -1,6 +1,12 @@
 use clap::{Parser, Subcommand, ValueEnum};
-use shard::{generate, parse, tokenize, ShardError};
-use std::path::PathBuf;
+use shard::codegen::{
+    generate_runtime, generate_script, Compliance, Runtime, Script, RUNTIME_FILE,
+};
+use shard::{
+    check, optimize, parse, parse_with_locations, tokenize, CodegenOptions, OptLevel, Program,
+    ShardError, Target,
+};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
 enum OutputFormat {
@@ -8,6 +14,82 @@ enum OutputFormat {
     Json,
 }
 
+/// Options shared by every command that generates shell.
+#[derive(Debug, clap::Args)]
+struct CodegenArgs {
+    /// Shell dialect to generate for
+    #[arg(short, long, value_enum, default_value_t = Target::Posix)]
+    target: Target,
+
+    /// How to handle constructs the target does not support
+    #[arg(long, value_enum, default_value_t = Compliance::Strict)]
+    compliance: Compliance,
+
+    /// Track the current Shard line at runtime and report it when a command fails
+    #[arg(long)]
+    line_markers: bool,
+
+    /// Spaces per indentation level
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    indent: usize,
+
+    /// Quote each Shard statement in a comment above its shell code
+    #[arg(long)]
+    source_comments: bool,
+
+    /// Strip whitespace and comments and shorten generated names
+    #[arg(long, conflicts_with = "source_comments")]
+    minify: bool,
+
+    /// Define runtime helpers in the script, or source them from shard-runtime.sh
+    #[arg(long, value_enum, default_value_t = Runtime::Inline)]
+    runtime: Runtime,
+}
+
+impl CodegenArgs {
+    fn options(&self, path: Option<&Path>, source: &str) -> CodegenOptions {
+        CodegenOptions {
+            target: self.target,
+            compliance: self.compliance,
+            source_name: path.map(|path| path.display().to_string()),
+            source_map: false,
+            line_markers: self.line_markers,
+            indent: self.indent,
+            source_comments: self.source_comments,
+            source: self.source_comments.then(|| source.to_string()),
+            minify: self.minify,
+            runtime: self.runtime,
+        }
+    }
+
+    /// Generate shell and report any compliance warnings on stderr.
+    fn generate(&self, ast: &Program, options: &CodegenOptions) -> Result<Script, ShardError> {
+        if options.target.is_experimental() {
+            eprintln!("warning: the {} target is experimental", options.target);
+        }
+        let script = generate_script(ast, options)?;
+        for warning in &script.warnings {
+            eprintln!("warning: {}", warning);
+        }
+        Ok(script)
+    }
+}
+
+/// Parse and check `tokens`, keeping statement locations when `options`
+/// needs them.
+fn parse_for(
+    tokens: Vec<shard::lexer::SpannedToken>,
+    options: &CodegenOptions,
+) -> Result<Program, ShardError> {
+    let program = if options.needs_locations() {
+        parse_with_locations(tokens)?
+    } else {
+        parse(tokens)?
+    };
+    check(&program)?;
+    Ok(program)
+}
+
 #[derive(Debug, Subcommand)]
 enum Commands {
     /// Check (lint) a Shard file without generating output
@@ -19,6 +101,9 @@ enum Commands {
         /// Output format (shell or json)
         #[arg(short, long, value_enum)]
         format: Option<OutputFormat>,
+
+        #[command(flatten)]
+        codegen: CodegenArgs,
     },
 
     /// Build a Shard file to a shell script
@@ -34,6 +119,18 @@ enum Commands {
         /// Make the output script executable
         #[arg(long)]
         executable: bool,
+
+        /// Also write a source map to <output>.map
+        #[arg(long)]
+        source_map: bool,
+
+        /// Optimization level: 0 (none), 1 (fold constants, remove
+        /// unreachable code) or 2 (also drop unused assignments)
+        #[arg(short = 'O', long, value_enum, default_value_t = OptLevel::Basic)]
+        opt_level: OptLevel,
+
+        #[command(flatten)]
+        codegen: CodegenArgs,
     },
 
     /// Transpile and print to stdout
@@ -45,8 +142,17 @@ enum Commands {
         /// Output format
         #[arg(short, long, value_enum)]
         format: Option<OutputFormat>,
+
+        #[command(flatten)]
+        codegen: CodegenArgs,
     },
 
+    /// Start the language server over stdio
+    Lsp,
+
+    /// Start an interactive session backed by a persistent shell
+    Repl,
+
     /// Initialize a new Shard project
     Init {
         /// Project name
@@ -71,8 +177,13 @@ struct Args {
 }
 
 fn handle_check(args: &Commands, verbose: bool) -> Result<(), ShardError> {
-    if let Commands::Check { input, format } = args {
-        let input = std::fs::read_to_string(input)?;
+    if let Commands::Check {
+        input: path,
+        format,
+        codegen,
+    } = args
+    {
+        let input = std::fs::read_to_string(path)?;
 
         if verbose {
             eprintln!("Checking: {:?}", input);
@@ -84,6 +195,8 @@ fn handle_check(args: &Commands, verbose: bool) -> Result<(), ShardError> {
         }
 
         let ast = parse(tokens)?;
+        check(&ast)?;
+        let options = codegen.options(Some(path), &input);
         if verbose {
             eprintln!("Parsed {} statements", ast.0.len());
         }
@@ -92,11 +205,16 @@ fn handle_check(args: &Commands, verbose: bool) -> Result<(), ShardError> {
 
         match format {
             OutputFormat::Shell => {
-                let shell = generate(&ast)?;
+                let script = codegen.generate(&ast, &options)?;
+                if verbose && !script.features.is_empty() {
+                    let features: Vec<String> =
+                        script.features.iter().map(|f| f.to_string()).collect();
+                    eprintln!("Uses {}", features.join(", "));
+                }
                 println!(
                     "✓ Check passed - {} statements, {} chars",
                     ast.0.len(),
-                    shell.len()
+                    script.text.len()
                 );
             }
             OutputFormat::Json => {
@@ -116,6 +234,9 @@ fn handle_build(args: &Commands, verbose: bool) -> Result<(), ShardError> {
         input,
         output,
         executable,
+        source_map,
+        opt_level,
+        codegen,
     } = args
     {
         let input_str = std::fs::read_to_string(input)?;
@@ -124,13 +245,36 @@ fn handle_build(args: &Commands, verbose: bool) -> Result<(), ShardError> {
             eprintln!("Building: {:?}", input);
         }
 
+        let mut options = codegen.options(Some(input), &input_str);
+        options.source_map = *source_map;
+
         let tokens = tokenize(&input_str)?;
-        let ast = parse(tokens)?;
-        let shell = generate(&ast)?;
+        let ast = optimize(parse_for(tokens, &options)?, *opt_level);
+        let script = codegen.generate(&ast, &options)?;
 
         let output_path = output.clone().unwrap_or_else(|| input.with_extension("sh"));
 
-        std::fs::write(&output_path, &shell)?;
+        std::fs::write(&output_path, &script.text)?;
+
+        if let Some(mut map) = script.source_map {
+            map.file = output_path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned());
+            let mut map_path = output_path.clone().into_os_string();
+            map_path.push(".map");
+            std::fs::write(&map_path, map.to_json()?)?;
+            if verbose {
+                eprintln!("Wrote source map {:?}", map_path);
+            }
+        }
+
+        if options.runtime == Runtime::External && !script.helpers.is_empty() {
+            let runtime_path = output_path.with_file_name(RUNTIME_FILE);
+            std::fs::write(&runtime_path, generate_runtime(&options))?;
+            if verbose {
+                eprintln!("Wrote runtime {:?}", runtime_path);
+            }
+        }
 
         if *executable {
             #[cfg(unix)]
@@ -150,7 +294,12 @@ fn handle_build(args: &Commands, verbose: bool) -> Result<(), ShardError> {
 }
 
 fn handle_transpile(args: &Commands, verbose: bool) -> Result<(), ShardError> {
-    if let Commands::Transpile { input, format } = args {
+    if let Commands::Transpile {
+        input,
+        format,
+        codegen,
+    } = args
+    {
         let input_str = if let Some(path) = input {
             std::fs::read_to_string(path)?
         } else {
@@ -161,14 +310,26 @@ fn handle_transpile(args: &Commands, verbose: bool) -> Result<(), ShardError> {
             eprintln!("Transpiling {} bytes", input_str.len());
         }
 
-        let tokens = tokenize(&input_str)?;
-        let ast = parse(tokens)?;
+        let options = codegen.options(input.as_deref(), &input_str);
         let format = format.unwrap_or(OutputFormat::Shell);
 
+        let tokens = tokenize(&input_str)?;
+        let ast = match format {
+            OutputFormat::Shell => parse_for(tokens, &options)?,
+            OutputFormat::Json => parse(tokens)?,
+        };
+
         match format {
             OutputFormat::Shell => {
-                let shell = generate(&ast)?;
-                print!("{}", shell);
+                let script = codegen.generate(&ast, &options)?;
+                // The script would source a runtime file nobody writes.
+                if options.runtime == Runtime::External && !script.helpers.is_empty() {
+                    return Err(ShardError::Codegen(format!(
+                        "transpile does not write {}; use build --runtime external",
+                        RUNTIME_FILE
+                    )));
+                }
+                print!("{}", script.text);
             }
             OutputFormat::Json => {
                 let json = serde_json::to_string_pretty(&ast)?;
@@ -215,6 +376,16 @@ fn handle_init(args: &Commands) -> Result<(), ShardError> {
     }
 }
 
+fn handle_lsp() -> Result<(), ShardError> {
+    shard::lsp::run()?;
+    Ok(())
+}
+
+fn handle_repl() -> Result<(), ShardError> {
+    shard::repl::run()?;
+    Ok(())
+}
+
 fn main() -> Result<(), ShardError> {
     let args = Args::parse();
 
@@ -223,6 +394,8 @@ fn main() -> Result<(), ShardError> {
         Commands::Build { .. } => handle_build(&args.command, args.verbose)?,
         Commands::Transpile { .. } => handle_transpile(&args.command, args.verbose)?,
         Commands::Init { .. } => handle_init(&args.command)?,
+        Commands::Lsp => handle_lsp()?,
+        Commands::Repl => handle_repl()?,
     }
 
     Ok(())
@@ -0,0 +1,411 @@
+use crate::ast::{Expression, Literal, MatchArm, Statement};
+use crate::sema;
+use std::collections::HashSet;
+
+/// Remove code that can never run: branches of `if` on a constant, `while
+/// false` loops, loops over an empty list, and statements following
+/// `return`, `break` or `continue` in the same block.
+///
+/// The branch taken replaces the `if`, unless it declares variables with
+/// `let` or `const` that would then outlive it.
+pub fn eliminate_dead_code(statements: Vec<Statement>) -> Vec<Statement> {
+    let mut live = Vec::new();
+
+    for statement in statements {
+        let reached_end = matches!(
+            statement,
+            Statement::Return { .. } | Statement::Break | Statement::Continue
+        );
+        match statement {
+            Statement::If {
+                condition: Expression::Literal(Literal::Boolean(taken)),
+                then_branch,
+                else_branch,
+            } if !declares(if taken {
+                &then_branch
+            } else {
+                else_branch.as_deref().unwrap_or_default()
+            }) =>
+            {
+                let branch = if taken {
+                    then_branch
+                } else {
+                    else_branch.unwrap_or_default()
+                };
+                let branch = eliminate_dead_code(branch);
+                let exits = branch.iter().any(|s| {
+                    matches!(
+                        s,
+                        Statement::Return { .. } | Statement::Break | Statement::Continue
+                    )
+                });
+                live.extend(branch);
+                if exits {
+                    break;
+                }
+                continue;
+            }
+            Statement::While {
+                condition: Expression::Literal(Literal::Boolean(false)),
+                ..
+            } => continue,
+            Statement::For {
+                iterable: Expression::Literal(Literal::Array(ref elements)),
+                ..
+            } if elements.is_empty() => continue,
+            other => live.push(eliminate_nested(other)),
+        }
+        if reached_end {
+            break;
+        }
+    }
+
+    tidy_locations(live)
+}
+
+/// Whether a block declares variables of its own.
+fn declares(statements: &[Statement]) -> bool {
+    statements
+        .iter()
+        .any(|statement| matches!(statement, Statement::Let { .. } | Statement::Const { .. }))
+}
+
+fn eliminate_nested(statement: Statement) -> Statement {
+    match statement {
+        Statement::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => Statement::If {
+            condition,
+            then_branch: eliminate_dead_code(then_branch),
+            else_branch: else_branch.map(eliminate_dead_code),
+        },
+        Statement::While { condition, body } => Statement::While {
+            condition,
+            body: eliminate_dead_code(body),
+        },
+        Statement::For {
+            index,
+            variable,
+            iterable,
+            body,
+        } => Statement::For {
+            index,
+            variable,
+            iterable,
+            body: eliminate_dead_code(body),
+        },
+        Statement::FunctionDef {
+            name,
+            params,
+            body,
+            return_value,
+        } => Statement::FunctionDef {
+            name,
+            params,
+            body: eliminate_dead_code(body),
+            return_value,
+        },
+        Statement::Try {
+            body,
+            catch_var,
+            catch_body,
+        } => Statement::Try {
+            body: eliminate_dead_code(body),
+            catch_var,
+            catch_body: eliminate_dead_code(catch_body),
+        },
+        Statement::Match { value, arms } => Statement::Match {
+            value,
+            arms: map_arms(arms, eliminate_dead_code),
+        },
+        other => other,
+    }
+}
+
+/// Apply `f` to the body of each arm.
+fn map_arms(
+    arms: Vec<MatchArm>,
+    mut f: impl FnMut(Vec<Statement>) -> Vec<Statement>,
+) -> Vec<MatchArm> {
+    arms.into_iter()
+        .map(|arm| MatchArm {
+            patterns: arm.patterns,
+            body: f(arm.body),
+        })
+        .collect()
+}
+
+/// Drop location markers whose statement was removed.
+fn tidy_locations(statements: Vec<Statement>) -> Vec<Statement> {
+    let mut tidy: Vec<Statement> = Vec::with_capacity(statements.len());
+    for statement in statements {
+        if let Some(Statement::Location { .. }) = tidy.last() {
+            if matches!(statement, Statement::Location { .. }) {
+                tidy.pop();
+            }
+        }
+        tidy.push(statement);
+    }
+    if let Some(Statement::Location { .. }) = tidy.last() {
+        tidy.pop();
+    }
+    tidy
+}
+
+/// Remove assignments to variables that are never read anywhere in the
+/// program. Assignments whose value calls a function are kept for the
+/// call's side effects.
+pub fn remove_unused_assignments(mut statements: Vec<Statement>) -> Vec<Statement> {
+    // Removing one assignment can leave the variables it read unused.
+    loop {
+        let mut reads = HashSet::new();
+        collect_reads(&statements, &mut reads);
+        let before = count_statements(&statements);
+        statements = remove_assignments(statements, &reads);
+        if count_statements(&statements) == before {
+            return statements;
+        }
+    }
+}
+
+fn remove_assignments(statements: Vec<Statement>, reads: &HashSet<String>) -> Vec<Statement> {
+    let kept = statements
+        .into_iter()
+        .filter(|statement| match statement {
+            Statement::Assignment { name, value } => {
+                reads.contains(sema::scope::variable(name)) || calls(value)
+            }
+            _ => true,
+        })
+        .map(|statement| match statement {
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => Statement::If {
+                condition,
+                then_branch: remove_assignments(then_branch, reads),
+                else_branch: else_branch.map(|b| remove_assignments(b, reads)),
+            },
+            Statement::While { condition, body } => Statement::While {
+                condition,
+                body: remove_assignments(body, reads),
+            },
+            Statement::For {
+                index,
+                variable,
+                iterable,
+                body,
+            } => Statement::For {
+                index,
+                variable,
+                iterable,
+                body: remove_assignments(body, reads),
+            },
+            Statement::FunctionDef {
+                name,
+                params,
+                body,
+                return_value,
+            } => Statement::FunctionDef {
+                name,
+                params,
+                body: remove_assignments(body, reads),
+                return_value,
+            },
+            Statement::Try {
+                body,
+                catch_var,
+                catch_body,
+            } => Statement::Try {
+                body: remove_assignments(body, reads),
+                catch_var,
+                catch_body: remove_assignments(catch_body, reads),
+            },
+            Statement::Match { value, arms } => Statement::Match {
+                value,
+                arms: map_arms(arms, |body| remove_assignments(body, reads)),
+            },
+            other => other,
+        })
+        .collect();
+    tidy_locations(kept)
+}
+
+fn count_statements(statements: &[Statement]) -> usize {
+    statements
+        .iter()
+        .map(|statement| {
+            1 + match statement {
+                Statement::If {
+                    then_branch,
+                    else_branch,
+                    ..
+                } => {
+                    count_statements(then_branch)
+                        + else_branch.as_deref().map_or(0, count_statements)
+                }
+                Statement::While { body, .. }
+                | Statement::For { body, .. }
+                | Statement::FunctionDef { body, .. } => count_statements(body),
+                Statement::Try {
+                    body, catch_body, ..
+                } => count_statements(body) + count_statements(catch_body),
+                Statement::Match { arms, .. } => {
+                    arms.iter().map(|arm| count_statements(&arm.body)).sum()
+                }
+                _ => 0,
+            }
+        })
+        .sum()
+}
+
+fn collect_reads(statements: &[Statement], reads: &mut HashSet<String>) {
+    for statement in statements {
+        match statement {
+            Statement::Assignment { value, .. }
+            | Statement::Let { value, .. }
+            | Statement::Const { value, .. } => collect_expression_reads(value, reads),
+            Statement::Command { args, .. } => {
+                for arg in args {
+                    collect_expression_reads(arg, reads);
+                }
+            }
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                collect_expression_reads(condition, reads);
+                collect_reads(then_branch, reads);
+                if let Some(else_branch) = else_branch {
+                    collect_reads(else_branch, reads);
+                }
+            }
+            Statement::While { condition, body } => {
+                collect_expression_reads(condition, reads);
+                collect_reads(body, reads);
+            }
+            Statement::For { iterable, body, .. } => {
+                collect_expression_reads(iterable, reads);
+                collect_reads(body, reads);
+            }
+            Statement::FunctionDef {
+                params,
+                body,
+                return_value,
+                ..
+            } => {
+                for default in params.iter().filter_map(|param| param.default.as_ref()) {
+                    collect_expression_reads(default, reads);
+                }
+                collect_reads(body, reads);
+                if let Some(value) = return_value {
+                    collect_expression_reads(value, reads);
+                }
+            }
+            Statement::Return { value: Some(value) } | Statement::ExpressionStatement(value) => {
+                collect_expression_reads(value, reads)
+            }
+            Statement::Try {
+                body, catch_body, ..
+            } => {
+                collect_reads(body, reads);
+                collect_reads(catch_body, reads);
+            }
+            Statement::Match { value, arms } => {
+                collect_expression_reads(value, reads);
+                for arm in arms {
+                    collect_reads(&arm.body, reads);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collect_expression_reads(expr: &Expression, reads: &mut HashSet<String>) {
+    visit(expr, &mut |e| match e {
+        Expression::Identifier(name) => {
+            reads.insert(name.clone());
+        }
+        Expression::Lambda { body, .. } => collect_reads(body, reads),
+        _ => {}
+    });
+}
+
+/// Whether evaluating `expr` calls a function.
+fn calls(expr: &Expression) -> bool {
+    let mut found = false;
+    visit(expr, &mut |e| {
+        found |= matches!(
+            e,
+            Expression::FunctionCall { .. } | Expression::Command { .. }
+        );
+    });
+    found
+}
+
+fn visit(expr: &Expression, f: &mut impl FnMut(&Expression)) {
+    f(expr);
+    match expr {
+        Expression::Literal(Literal::Array(elements)) => {
+            for element in elements {
+                visit(element, f);
+            }
+        }
+        Expression::Literal(Literal::Map(pairs)) => {
+            for (key, value) in pairs {
+                visit(key, f);
+                visit(value, f);
+            }
+        }
+        Expression::BinaryOp { left, right, .. } => {
+            visit(left, f);
+            visit(right, f);
+        }
+        Expression::UnaryOp { expr, .. } | Expression::Length { expr } => visit(expr, f),
+        Expression::NamedArgument { value, .. } => visit(value, f),
+        Expression::Field { object, .. } => visit(object, f),
+        Expression::StructLiteral { fields, .. } => {
+            for (_, value) in fields {
+                visit(value, f);
+            }
+        }
+        Expression::ArrayIndex { array, index } => {
+            visit(array, f);
+            visit(index, f);
+        }
+        Expression::MapIndex { map, key } => {
+            visit(map, f);
+            visit(key, f);
+        }
+        Expression::FunctionCall { args, .. } | Expression::Command { args, .. } => {
+            for arg in args {
+                visit(arg, f);
+            }
+        }
+        Expression::InterpolatedString { parts } => {
+            for part in parts {
+                visit(part, f);
+            }
+        }
+        Expression::Range {
+            start, end, step, ..
+        } => {
+            visit(start, f);
+            visit(end, f);
+            if let Some(step) = step {
+                visit(step, f);
+            }
+        }
+        // Only the value; the statements of the body are not expressions.
+        Expression::Lambda {
+            return_value: Some(value),
+            ..
+        } => visit(value, f),
+        _ => {}
+    }
+}
@@ -0,0 +1,339 @@
+use crate::ast::{BinaryOperator, Expression, Literal, MatchArm, Param, Statement, UnaryOperator};
+use crate::sema;
+use std::collections::HashMap;
+
+/// Evaluate every subexpression whose operands are known at compile time.
+///
+/// A `const` at the top of the script whose value is a string, number or
+/// boolean is replaced by its value wherever it is read, unless the name
+/// is bound anywhere else.
+///
+/// Integer arithmetic that would overflow or divide by zero is left for the
+/// shell, so the script fails at runtime exactly as it would unoptimized.
+pub fn fold_constants(statements: Vec<Statement>) -> Vec<Statement> {
+    let bindings = sema::scope::bindings(&statements);
+    let mut constants = HashMap::new();
+    for statement in &statements {
+        if let Statement::Const { name, value } = statement {
+            let bound_once = bindings.iter().filter(|&&bound| bound == name).count() == 1;
+            match fold_with(value.clone(), &constants) {
+                Expression::Literal(
+                    literal @ (Literal::String(_)
+                    | Literal::Integer(_)
+                    | Literal::Float(_)
+                    | Literal::Boolean(_)),
+                ) if bound_once => {
+                    constants.insert(name.clone(), literal);
+                }
+                _ => {}
+            }
+        }
+    }
+    fold_block(statements, &constants)
+}
+
+type Constants = HashMap<String, Literal>;
+
+fn fold_block(statements: Vec<Statement>, constants: &Constants) -> Vec<Statement> {
+    statements
+        .into_iter()
+        .map(|statement| fold_statement(statement, constants))
+        .collect()
+}
+
+fn fold_statement(statement: Statement, constants: &Constants) -> Statement {
+    let fold_expression = |expr| fold_with(expr, constants);
+    let fold_constants = |statements| fold_block(statements, constants);
+    match statement {
+        Statement::Assignment { name, value } => Statement::Assignment {
+            name,
+            value: fold_expression(value),
+        },
+        Statement::Let { name, value } => Statement::Let {
+            name,
+            value: fold_expression(value),
+        },
+        Statement::Const { name, value } => Statement::Const {
+            name,
+            value: fold_expression(value),
+        },
+        Statement::Command { name, args } => Statement::Command {
+            name,
+            args: args.into_iter().map(fold_expression).collect(),
+        },
+        Statement::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => Statement::If {
+            condition: fold_condition(fold_expression(condition)),
+            then_branch: fold_constants(then_branch),
+            else_branch: else_branch.map(fold_constants),
+        },
+        Statement::While { condition, body } => Statement::While {
+            condition: fold_condition(fold_expression(condition)),
+            body: fold_constants(body),
+        },
+        Statement::For {
+            index,
+            variable,
+            iterable,
+            body,
+        } => Statement::For {
+            index,
+            variable,
+            iterable: fold_expression(iterable),
+            body: fold_constants(body),
+        },
+        Statement::FunctionDef {
+            name,
+            params,
+            body,
+            return_value,
+        } => Statement::FunctionDef {
+            name,
+            params: params
+                .into_iter()
+                .map(|param| Param {
+                    default: param.default.map(fold_expression),
+                    ..param
+                })
+                .collect(),
+            body: fold_constants(body),
+            return_value: return_value.map(fold_expression),
+        },
+        Statement::Return { value } => Statement::Return {
+            value: value.map(fold_expression),
+        },
+        Statement::Try {
+            body,
+            catch_var,
+            catch_body,
+        } => Statement::Try {
+            body: fold_constants(body),
+            catch_var,
+            catch_body: fold_constants(catch_body),
+        },
+        Statement::Match { value, arms } => Statement::Match {
+            value: fold_expression(value),
+            arms: arms
+                .into_iter()
+                .map(|arm| MatchArm {
+                    patterns: arm.patterns,
+                    body: fold_constants(arm.body),
+                })
+                .collect(),
+        },
+        Statement::ExpressionStatement(expr) => {
+            Statement::ExpressionStatement(fold_expression(expr))
+        }
+        other => other,
+    }
+}
+
+pub fn fold_expression(expr: Expression) -> Expression {
+    fold_with(expr, &HashMap::new())
+}
+
+fn fold_with(expr: Expression, constants: &Constants) -> Expression {
+    let fold_expression = |expr| fold_with(expr, constants);
+    let fold_constants = |statements| fold_block(statements, constants);
+    match expr {
+        Expression::Identifier(name) => match constants.get(&name) {
+            Some(literal) => Expression::Literal(literal.clone()),
+            None => Expression::Identifier(name),
+        },
+        Expression::BinaryOp { op, left, right } => {
+            let left = fold_expression(*left);
+            let right = fold_expression(*right);
+            fold_binary(&op, &left, &right).unwrap_or(Expression::BinaryOp {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            })
+        }
+        Expression::UnaryOp { op, expr } => {
+            let expr = fold_expression(*expr);
+            let folded = match (&op, &expr) {
+                (UnaryOperator::Negate, Expression::Literal(Literal::Integer(n))) => {
+                    n.checked_neg().map(Literal::Integer)
+                }
+                (UnaryOperator::Not, Expression::Literal(Literal::Boolean(b))) => {
+                    Some(Literal::Boolean(!b))
+                }
+                _ => None,
+            };
+            folded
+                .map(Expression::Literal)
+                .unwrap_or(Expression::UnaryOp {
+                    op,
+                    expr: Box::new(expr),
+                })
+        }
+        Expression::Literal(Literal::Array(elements)) => Expression::Literal(Literal::Array(
+            elements.into_iter().map(fold_expression).collect(),
+        )),
+        Expression::Literal(Literal::Map(pairs)) => Expression::Literal(Literal::Map(
+            pairs
+                .into_iter()
+                .map(|(k, v)| (fold_expression(k), fold_expression(v)))
+                .collect(),
+        )),
+        Expression::ArrayIndex { array, index } => Expression::ArrayIndex {
+            array: Box::new(fold_expression(*array)),
+            index: Box::new(fold_expression(*index)),
+        },
+        Expression::MapIndex { map, key } => Expression::MapIndex {
+            map: Box::new(fold_expression(*map)),
+            key: Box::new(fold_expression(*key)),
+        },
+        Expression::FunctionCall { name, args } => Expression::FunctionCall {
+            name,
+            args: args.into_iter().map(fold_expression).collect(),
+        },
+        Expression::Command { name, args } => Expression::Command {
+            name,
+            args: args.into_iter().map(fold_expression).collect(),
+        },
+        Expression::NamedArgument { name, value } => Expression::NamedArgument {
+            name,
+            value: Box::new(fold_expression(*value)),
+        },
+        Expression::StructLiteral { name, fields } => Expression::StructLiteral {
+            name,
+            fields: fields
+                .into_iter()
+                .map(|(field, value)| (field, fold_expression(value)))
+                .collect(),
+        },
+        Expression::InterpolatedString { parts } => {
+            let parts: Vec<Expression> = parts.into_iter().map(fold_expression).collect();
+            let text: Option<String> = parts
+                .iter()
+                .map(|part| match part {
+                    Expression::Literal(Literal::String(s)) => Some(s.as_str()),
+                    _ => None,
+                })
+                .collect();
+            match text {
+                Some(text) => Expression::Literal(Literal::String(text)),
+                None => Expression::InterpolatedString { parts },
+            }
+        }
+        Expression::Range {
+            start,
+            end,
+            inclusive,
+            step,
+        } => Expression::Range {
+            start: Box::new(fold_expression(*start)),
+            end: Box::new(fold_expression(*end)),
+            inclusive,
+            step: step.map(|step| Box::new(fold_expression(*step))),
+        },
+        Expression::Lambda {
+            params,
+            body,
+            return_value,
+        } => Expression::Lambda {
+            params,
+            body: fold_constants(body),
+            return_value: return_value.map(|value| Box::new(fold_expression(*value))),
+        },
+        Expression::Length { expr } => {
+            let expr = fold_expression(*expr);
+            let length = match &expr {
+                Expression::Literal(Literal::String(s)) => i64::try_from(s.chars().count()).ok(),
+                Expression::Literal(Literal::Array(elements)) => i64::try_from(elements.len()).ok(),
+                _ => None,
+            };
+            match length {
+                Some(n) => Expression::Literal(Literal::Integer(n)),
+                None => Expression::Length {
+                    expr: Box::new(expr),
+                },
+            }
+        }
+        other => other,
+    }
+}
+
+/// Drop the `true` of `true and x` and the `false` of `false or x` in a
+/// folded condition. As a value, `true and x` is `true` or `false` by the
+/// truth of `x`, not `x` itself.
+fn fold_condition(condition: Expression) -> Expression {
+    match condition {
+        Expression::BinaryOp {
+            op: op @ (BinaryOperator::And | BinaryOperator::Or),
+            left,
+            right,
+        } => {
+            let left = fold_condition(*left);
+            let right = fold_condition(*right);
+            match (&op, &left) {
+                (BinaryOperator::And, Expression::Literal(Literal::Boolean(true)))
+                | (BinaryOperator::Or, Expression::Literal(Literal::Boolean(false))) => right,
+                _ => Expression::BinaryOp {
+                    op,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                },
+            }
+        }
+        Expression::UnaryOp {
+            op: UnaryOperator::Not,
+            expr,
+        } => Expression::UnaryOp {
+            op: UnaryOperator::Not,
+            expr: Box::new(fold_condition(*expr)),
+        },
+        other => other,
+    }
+}
+
+fn fold_binary(op: &BinaryOperator, left: &Expression, right: &Expression) -> Option<Expression> {
+    use BinaryOperator::*;
+
+    // `false and x` is false and `true or x` is true whatever `x` is.
+    // `true and x` is only `x` as a condition; see `fold_condition`.
+    match (op, left) {
+        (And, Expression::Literal(Literal::Boolean(false)))
+        | (Or, Expression::Literal(Literal::Boolean(true))) => return Some(left.clone()),
+        _ => {}
+    }
+
+    let (Expression::Literal(left), Expression::Literal(right)) = (left, right) else {
+        return None;
+    };
+    let literal = match (left, right) {
+        (Literal::Integer(a), Literal::Integer(b)) => match op {
+            Add => Literal::Integer(a.checked_add(*b)?),
+            Subtract => Literal::Integer(a.checked_sub(*b)?),
+            Multiply => Literal::Integer(a.checked_mul(*b)?),
+            Divide => Literal::Integer(a.checked_div(*b)?),
+            Modulo => Literal::Integer(a.checked_rem(*b)?),
+            Equals => Literal::Boolean(a == b),
+            NotEquals => Literal::Boolean(a != b),
+            Less => Literal::Boolean(a < b),
+            Greater => Literal::Boolean(a > b),
+            LessEquals => Literal::Boolean(a <= b),
+            GreaterEquals => Literal::Boolean(a >= b),
+            And | Or => return None,
+        },
+        (Literal::String(a), Literal::String(b)) => match op {
+            Add => Literal::String(format!("{}{}", a, b)),
+            Equals => Literal::Boolean(a == b),
+            NotEquals => Literal::Boolean(a != b),
+            _ => return None,
+        },
+        (Literal::Boolean(a), Literal::Boolean(b)) => match op {
+            Equals => Literal::Boolean(a == b),
+            NotEquals => Literal::Boolean(a != b),
+            And => Literal::Boolean(*a && *b),
+            Or => Literal::Boolean(*a || *b),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(Expression::Literal(literal))
+}
@@ -0,0 +1,37 @@
+//! AST-to-AST passes run between `parse` and `generate`.
+
+pub mod eliminate;
+pub mod fold;
+
+use crate::ast::Program;
+use clap::ValueEnum;
+
+pub use eliminate::{eliminate_dead_code, remove_unused_assignments};
+pub use fold::fold_constants;
+
+/// How aggressively `optimize` rewrites a program.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
+pub enum OptLevel {
+    /// Leave the program as written.
+    #[value(name = "0")]
+    None,
+    /// Fold constants and remove unreachable code.
+    #[default]
+    #[value(name = "1")]
+    Basic,
+    /// Also drop assignments to variables that are never read.
+    #[value(name = "2")]
+    Full,
+}
+
+pub fn optimize(program: Program, level: OptLevel) -> Program {
+    if level == OptLevel::None {
+        return program;
+    }
+    let mut statements = fold_constants(program.0);
+    statements = eliminate_dead_code(statements);
+    if level >= OptLevel::Full {
+        statements = remove_unused_assignments(statements);
+    }
+    Program(statements)
+}
@@ -9,6 +9,23 @@ pub enum ParserError {
 
     #[error("Parser error: {0}")]
     Other(String),
+
+    #[error("{message}")]
+    Spanned {
+        message: String,
+        #[label("here")]
+        span: (usize, usize),
+    },
+}
+
+impl ParserError {
+    /// Character span of the token the parser stopped at, when known.
+    pub fn span(&self) -> Option<(usize, usize)> {
+        match self {
+            ParserError::Spanned { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
 }
 
 pub type ParserResult<T> = std::result::Result<T, ParserError>;
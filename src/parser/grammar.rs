@@ -1,23 +1,135 @@
-use crate::ast::{BinaryOperator, Expression, Literal, Program, Statement, UnaryOperator};
+use crate::ast::{
+    BinaryOperator, Expression, Field, Literal, MatchArm, Param, Pattern, Program, Statement,
+    UnaryOperator,
+};
 use crate::lexer::{SpannedToken, TokenType};
 use crate::parser::error::ParserError;
 use crate::parser::error::ParserResult;
 
 pub fn parse(tokens: Vec<SpannedToken>) -> ParserResult<Program> {
+    let Program(statements) = parse_with_locations(tokens)?;
+    Ok(Program(strip_locations(statements)))
+}
+
+/// Parse like [`parse`], but precede every statement, including nested
+/// ones, with a [`Statement::Location`] marking where it starts.
+pub fn parse_with_locations(tokens: Vec<SpannedToken>) -> ParserResult<Program> {
     let mut statements = Vec::new();
     let mut pos = 0;
 
     while pos < tokens.len() {
-        if let Some(stmt) = parse_statement(&tokens, &mut pos)? {
-            statements.push(stmt);
-        } else {
-            break;
+        skip_trivia(&tokens, &mut pos);
+        let location = location_at(&tokens, pos);
+        match parse_statement(&tokens, &mut pos) {
+            Ok(Some(stmt)) => {
+                statements.extend(location);
+                statements.push(stmt);
+            }
+            Ok(None) => break,
+            Err(e) => return Err(locate_error(&tokens, pos, e)),
         }
     }
 
     Ok(Program(statements))
 }
 
+fn skip_trivia(tokens: &[SpannedToken], pos: &mut usize) {
+    while let Some((_, token, _)) = tokens.get(*pos) {
+        match token.token_type {
+            TokenType::Newline | TokenType::Comment => *pos += 1,
+            _ => break,
+        }
+    }
+}
+
+fn location_at(tokens: &[SpannedToken], pos: usize) -> Option<Statement> {
+    let (_, token, _) = tokens.get(pos)?;
+    (token.line > 0).then_some(Statement::Location {
+        line: token.line,
+        column: token.column,
+    })
+}
+
+fn strip_locations(statements: Vec<Statement>) -> Vec<Statement> {
+    statements
+        .into_iter()
+        .filter(|stmt| !matches!(stmt, Statement::Location { .. }))
+        .map(|stmt| match stmt {
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => Statement::If {
+                condition,
+                then_branch: strip_locations(then_branch),
+                else_branch: else_branch.map(strip_locations),
+            },
+            Statement::While { condition, body } => Statement::While {
+                condition,
+                body: strip_locations(body),
+            },
+            Statement::For {
+                index,
+                variable,
+                iterable,
+                body,
+            } => Statement::For {
+                index,
+                variable,
+                iterable,
+                body: strip_locations(body),
+            },
+            Statement::FunctionDef {
+                name,
+                params,
+                body,
+                return_value,
+            } => Statement::FunctionDef {
+                name,
+                params,
+                body: strip_locations(body),
+                return_value,
+            },
+            Statement::Try {
+                body,
+                catch_var,
+                catch_body,
+            } => Statement::Try {
+                body: strip_locations(body),
+                catch_var,
+                catch_body: strip_locations(catch_body),
+            },
+            Statement::Match { value, arms } => Statement::Match {
+                value,
+                arms: arms
+                    .into_iter()
+                    .map(|arm| MatchArm {
+                        patterns: arm.patterns,
+                        body: strip_locations(arm.body),
+                    })
+                    .collect(),
+            },
+            other => other,
+        })
+        .collect()
+}
+
+/// Attach the span of the token at `pos` to an error that has none.
+fn locate_error(tokens: &[SpannedToken], pos: usize, error: ParserError) -> ParserError {
+    if error.span().is_some() {
+        return error;
+    }
+    let span = tokens
+        .get(pos)
+        .or_else(|| tokens.last())
+        .map_or((0, 0), |(_, token, _)| token.span);
+    let message = match error {
+        ParserError::ParseError(m) | ParserError::Other(m) => m,
+        ParserError::Spanned { message, .. } => message,
+    };
+    ParserError::Spanned { message, span }
+}
+
 fn parse_statement(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<Statement>> {
     if *pos >= tokens.len() {
         return Ok(None);
@@ -50,6 +162,25 @@ fn parse_statement(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Opt
                 "fn" => return parse_function_def(tokens, pos),
                 "return" => return parse_return(tokens, pos),
                 "try" => return parse_try(tokens, pos),
+                "match" => return parse_match(tokens, pos),
+                "global" if get_token_type(tokens, *pos + 1) == TokenType::Identifier => {
+                    return parse_global(tokens, pos)
+                }
+                "let" | "const" if get_token_type(tokens, *pos + 1) == TokenType::Identifier => {
+                    return parse_binding(tokens, pos)
+                }
+                "struct"
+                    if get_token_type(tokens, *pos + 1) == TokenType::Identifier
+                        && get_token_type(tokens, *pos + 2) == TokenType::LBrace =>
+                {
+                    return parse_struct(tokens, pos)
+                }
+                "enum"
+                    if get_token_type(tokens, *pos + 1) == TokenType::Identifier
+                        && get_token_type(tokens, *pos + 2) == TokenType::LBrace =>
+                {
+                    return parse_enum(tokens, pos)
+                }
                 "break" => {
                     *pos += 1;
                     consume_newline(tokens, pos);
@@ -64,6 +195,9 @@ fn parse_statement(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Opt
             }
 
             *pos += 1;
+            // `s.port = value` assigns a field; `app.info msg` runs a
+            // command with a dotted name.
+            let name = parse_dotted_name(name, tokens, pos);
 
             if *pos < tokens.len() {
                 let (_, next_token, _) = &tokens[*pos];
@@ -96,6 +230,7 @@ fn parse_statement(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Opt
                 if next_token.token_type == TokenType::Newline
                     || next_token.token_type == TokenType::EOF
                     || next_token.token_type == TokenType::RBrace
+                    || next_token.token_type == TokenType::Comma
                 {
                     break;
                 }
@@ -145,6 +280,16 @@ fn parse_command_argument(tokens: &[SpannedToken], pos: &mut usize) -> ParserRes
             *pos += 1;
             Ok(Expression::Literal(Literal::Integer(val)))
         }
+        TokenType::Float => {
+            let val = token
+                .value
+                .clone()
+                .unwrap_or_default()
+                .parse()
+                .unwrap_or(0.0);
+            *pos += 1;
+            Ok(Expression::Literal(Literal::Float(val)))
+        }
         TokenType::Boolean => {
             let val = token.value.clone().unwrap_or_default() == "true";
             *pos += 1;
@@ -168,6 +313,7 @@ fn parse_command_argument(tokens: &[SpannedToken], pos: &mut usize) -> ParserRes
             let name = token.value.clone().unwrap_or_default();
             *pos += 1;
 
+            let name = parse_dotted_name(name, tokens, pos);
             if *pos < tokens.len() {
                 let (_, next_token, _) = &tokens[*pos];
                 if next_token.token_type == TokenType::LParen {
@@ -176,35 +322,38 @@ fn parse_command_argument(tokens: &[SpannedToken], pos: &mut usize) -> ParserRes
                     return Ok(Expression::FunctionCall { name, args });
                 }
             }
+            if !name.contains('.') && is_struct_literal(tokens, *pos) {
+                return parse_struct_literal(name, tokens, pos);
+            }
 
-            Ok(Expression::Identifier(name))
+            Ok(path_expression(&name))
         }
         TokenType::Minus => {
-            // Check if this is a flag like "-la"
+            // Check if this is a flag like "-la" or "--quiet": dashes and a
+            // word written against each other
             let mut value = String::from("-");
+            let mut end = token.span.1;
             *pos += 1;
 
-            // Consume following alphanumeric characters
-            while *pos < tokens.len() {
-                let (_, next_token, _) = &tokens[*pos];
-                if let TokenType::Identifier = &next_token.token_type {
-                    if let Some(ident_value) = &next_token.value {
-                        if ident_value
-                            .chars()
-                            .next()
-                            .map_or(false, |c| c.is_alphanumeric())
-                        {
-                            value.push_str(ident_value);
-                            *pos += 1;
-                        } else {
-                            break;
-                        }
-                    } else {
+            while let Some((_, next_token, _)) = tokens.get(*pos) {
+                if next_token.span.0 != end {
+                    break;
+                }
+                match &next_token.token_type {
+                    TokenType::Minus => value.push('-'),
+                    TokenType::Identifier
+                        if next_token.value.as_ref().is_some_and(|v| {
+                            v.chars().next().is_some_and(|c| c.is_alphanumeric())
+                        }) =>
+                    {
+                        value.push_str(next_token.value.as_deref().unwrap_or_default());
+                        *pos += 1;
                         break;
                     }
-                } else {
-                    break;
+                    _ => break,
                 }
+                end = next_token.span.1;
+                *pos += 1;
             }
 
             Ok(Expression::Literal(Literal::String(value)))
@@ -252,13 +401,31 @@ fn parse_command_argument(tokens: &[SpannedToken], pos: &mut usize) -> ParserRes
 }
 
 fn parse_or_expression(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Expression> {
-    let mut left = parse_and_expression(tokens, pos)?;
+    parse_logical(tokens, pos, parse_not_expression)
+}
+
+/// The condition of an `if` or `while`, where operands of `and`, `or` and
+/// `not` may also be commands run for their status, as in
+/// `if exists(path) and grep -q pattern path`.
+fn parse_condition(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Expression> {
+    parse_logical(tokens, pos, parse_condition_operand)
+}
+
+type OperandParser = fn(&[SpannedToken], &mut usize) -> ParserResult<Expression>;
+
+/// Operands joined by `or`, which binds looser than `and`.
+fn parse_logical(
+    tokens: &[SpannedToken],
+    pos: &mut usize,
+    operand: OperandParser,
+) -> ParserResult<Expression> {
+    let mut left = parse_and_expression(tokens, pos, operand)?;
 
     while *pos < tokens.len() {
         let (_, token, _) = &tokens[*pos];
         if token.token_type == TokenType::Or {
             *pos += 1;
-            let right = parse_and_expression(tokens, pos)?;
+            let right = parse_and_expression(tokens, pos, operand)?;
             left = Expression::BinaryOp {
                 op: BinaryOperator::Or,
                 left: Box::new(left),
@@ -272,14 +439,18 @@ fn parse_or_expression(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult
     Ok(left)
 }
 
-fn parse_and_expression(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Expression> {
-    let mut left = parse_equality_expression(tokens, pos)?;
+fn parse_and_expression(
+    tokens: &[SpannedToken],
+    pos: &mut usize,
+    operand: OperandParser,
+) -> ParserResult<Expression> {
+    let mut left = operand(tokens, pos)?;
 
     while *pos < tokens.len() {
         let (_, token, _) = &tokens[*pos];
         if token.token_type == TokenType::And {
             *pos += 1;
-            let right = parse_equality_expression(tokens, pos)?;
+            let right = operand(tokens, pos)?;
             left = Expression::BinaryOp {
                 op: BinaryOperator::And,
                 left: Box::new(left),
@@ -293,9 +464,88 @@ fn parse_and_expression(tokens: &[SpannedToken], pos: &mut usize) -> ParserResul
     Ok(left)
 }
 
+/// The `not` keyword applies to a whole comparison, so `not x > 1` is
+/// `not (x > 1)`. The `!` operator binds tightly like `-`.
+fn parse_not_expression(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Expression> {
+    if is_not_keyword(tokens, *pos) {
+        *pos += 1;
+        let expr = parse_not_expression(tokens, pos)?;
+        return Ok(Expression::UnaryOp {
+            op: UnaryOperator::Not,
+            expr: Box::new(expr),
+        });
+    }
+    parse_equality_expression(tokens, pos)
+}
+
+fn parse_condition_operand(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Expression> {
+    if is_not_keyword(tokens, *pos) {
+        *pos += 1;
+        let expr = parse_condition_operand(tokens, pos)?;
+        return Ok(Expression::UnaryOp {
+            op: UnaryOperator::Not,
+            expr: Box::new(expr),
+        });
+    }
+    if !starts_command(tokens, *pos) {
+        return parse_equality_expression(tokens, pos);
+    }
+
+    let (_, token, _) = &tokens[*pos];
+    let name = token.value.clone().unwrap_or_default();
+    *pos += 1;
+    let mut args = Vec::new();
+    while *pos < tokens.len()
+        && !matches!(
+            get_token_type(tokens, *pos),
+            TokenType::And
+                | TokenType::Or
+                | TokenType::LBrace
+                | TokenType::RParen
+                | TokenType::Newline
+                | TokenType::EOF
+        )
+    {
+        args.push(parse_command_argument(tokens, pos)?);
+    }
+    Ok(Expression::Command { name, args })
+}
+
+fn is_not_keyword(tokens: &[SpannedToken], pos: usize) -> bool {
+    tokens.get(pos).is_some_and(|(_, token, _)| {
+        token.token_type == TokenType::Not && token.value.as_deref() == Some("not")
+    })
+}
+
+/// Whether a name followed by what can only be a command argument starts
+/// at `pos`: a word, a string, a number, or a flag written against its
+/// dash, as in `ls -la` rather than `x - 1`.
+fn starts_command(tokens: &[SpannedToken], pos: usize) -> bool {
+    let (Some((_, name, _)), Some((_, next, _))) = (tokens.get(pos), tokens.get(pos + 1)) else {
+        return false;
+    };
+    if name.token_type != TokenType::Identifier {
+        return false;
+    }
+    match next.token_type {
+        TokenType::Identifier
+        | TokenType::String
+        | TokenType::InterpolatedString
+        | TokenType::Integer
+        | TokenType::Float => true,
+        TokenType::Minus => {
+            next.span.0 > name.span.1
+                && tokens
+                    .get(pos + 2)
+                    .is_some_and(|(_, flag, _)| flag.span.0 == next.span.1)
+        }
+        _ => false,
+    }
+}
+
 fn parse_if(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<Statement>> {
     *pos += 1;
-    let condition = parse_expression(tokens, pos)?;
+    let condition = parse_condition(tokens, pos)?;
 
     consume_newline(tokens, pos);
 
@@ -320,14 +570,14 @@ fn parse_if(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<Sta
     let else_branch = if *pos < tokens.len() {
         let (_, token, _) = &tokens[*pos];
         if let TokenType::Identifier = token.token_type {
-            if token.value.as_ref().map_or(false, |v| v == "else") {
+            if token.value.as_ref().is_some_and(|v| v == "else") {
                 *pos += 1;
                 consume_newline(tokens, pos);
 
                 if *pos < tokens.len() {
                     let (_, next_token, _) = &tokens[*pos];
                     if let TokenType::Identifier = next_token.token_type {
-                        if next_token.value.as_ref().map_or(false, |v| v == "if") {
+                        if next_token.value.as_ref().is_some_and(|v| v == "if") {
                             return parse_if(tokens, pos);
                         }
                     }
@@ -368,7 +618,7 @@ fn parse_if(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<Sta
 
 fn parse_while(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<Statement>> {
     *pos += 1;
-    let condition = parse_expression(tokens, pos)?;
+    let condition = parse_condition(tokens, pos)?;
 
     consume_newline(tokens, pos);
 
@@ -396,22 +646,14 @@ fn parse_while(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<
 fn parse_for(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<Statement>> {
     *pos += 1;
 
-    if *pos >= tokens.len() {
-        return Err(ParserError::Other(
-            "Expected variable name after for".to_string(),
-        ));
+    let mut index = None;
+    let mut variable = parse_for_variable(tokens, pos)?;
+    if *pos < tokens.len() && get_token_type(tokens, *pos) == TokenType::Comma {
+        *pos += 1;
+        index = Some(variable);
+        variable = parse_for_variable(tokens, pos)?;
     }
 
-    let (_, token, _) = &tokens[*pos];
-    let variable = if let TokenType::Identifier = token.token_type {
-        token.value.clone().unwrap_or_default()
-    } else {
-        return Err(ParserError::Other(
-            "Expected variable name after for".to_string(),
-        ));
-    };
-    *pos += 1;
-
     if *pos >= tokens.len() {
         return Err(ParserError::Other(
             "Expected 'in' after variable".to_string(),
@@ -420,7 +662,7 @@ fn parse_for(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<St
 
     let (_, next_token, _) = &tokens[*pos];
     if let TokenType::Identifier = next_token.token_type {
-        if next_token.value.as_ref().map_or(false, |v| v == "in") {
+        if next_token.value.as_ref().is_some_and(|v| v == "in") {
             *pos += 1;
         } else {
             return Err(ParserError::Other(
@@ -434,6 +676,7 @@ fn parse_for(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<St
     }
 
     let iterable = parse_expression(tokens, pos)?;
+    let iterable = parse_range(tokens, pos, iterable)?;
 
     consume_newline(tokens, pos);
 
@@ -456,12 +699,56 @@ fn parse_for(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<St
     consume_newline(tokens, pos);
 
     Ok(Some(Statement::For {
+        index,
         variable,
         iterable,
         body,
     }))
 }
 
+fn parse_for_variable(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<String> {
+    match tokens.get(*pos) {
+        Some((_, token, _)) if token.token_type == TokenType::Identifier => {
+            *pos += 1;
+            Ok(token.value.clone().unwrap_or_default())
+        }
+        _ => Err(ParserError::Other(
+            "Expected variable name after for".to_string(),
+        )),
+    }
+}
+
+/// `start..end` or `start..=end`, optionally followed by `step n`, when
+/// the iterable `start` is followed by dots.
+fn parse_range(
+    tokens: &[SpannedToken],
+    pos: &mut usize,
+    start: Expression,
+) -> ParserResult<Expression> {
+    let inclusive = match tokens.get(*pos).map(|(_, token, _)| &token.token_type) {
+        Some(TokenType::DotDot) => false,
+        Some(TokenType::DotDotEq) => true,
+        _ => return Ok(start),
+    };
+    *pos += 1;
+    let end = parse_expression(tokens, pos)?;
+
+    let mut step = None;
+    if let Some((_, token, _)) = tokens.get(*pos) {
+        if token.token_type == TokenType::Identifier && token.value.as_deref() == Some("step") {
+            *pos += 1;
+            step = Some(Box::new(parse_expression(tokens, pos)?));
+        }
+    }
+
+    Ok(Expression::Range {
+        start: Box::new(start),
+        end: Box::new(end),
+        inclusive,
+        step,
+    })
+}
+
 fn parse_function_def(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<Statement>> {
     *pos += 1;
 
@@ -480,35 +767,9 @@ fn parse_function_def(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<
         ));
     };
     *pos += 1;
+    let name = parse_dotted_name(name, tokens, pos);
 
-    let mut params = Vec::new();
-    if *pos < tokens.len() && get_token_type(tokens, *pos) == TokenType::LParen {
-        *pos += 1;
-
-        while *pos < tokens.len() {
-            let (_, token, _) = &tokens[*pos];
-            if token.token_type == TokenType::RParen {
-                *pos += 1;
-                break;
-            }
-            if let TokenType::Identifier = &token.token_type {
-                if let Some(param) = &token.value {
-                    params.push(param.clone());
-                }
-                *pos += 1;
-
-                if *pos < tokens.len() {
-                    let (_, next_token, _) = &tokens[*pos];
-                    if next_token.token_type == TokenType::Comma {
-                        *pos += 1;
-                        continue;
-                    }
-                }
-            } else {
-                break;
-            }
-        }
-    }
+    let params = parse_params(tokens, pos)?;
 
     consume_newline(tokens, pos);
 
@@ -538,6 +799,362 @@ fn parse_function_def(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<
     }))
 }
 
+/// `(a, b = default, ...rest)` after `fn` and a function name, or after
+/// `fn` alone for a lambda.
+fn parse_params(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Vec<Param>> {
+    let mut params: Vec<Param> = Vec::new();
+    if get_token_type(tokens, *pos) != TokenType::LParen {
+        return Ok(params);
+    }
+    *pos += 1;
+
+    while get_token_type(tokens, *pos) != TokenType::RParen {
+        if params.last().is_some_and(|param| param.rest) {
+            return Err(ParserError::Other(
+                "A rest parameter must be the last parameter".to_string(),
+            ));
+        }
+        let rest = get_token_type(tokens, *pos) == TokenType::Ellipsis;
+        if rest {
+            *pos += 1;
+        }
+        let name = match tokens.get(*pos) {
+            Some((_, token, _)) if token.token_type == TokenType::Identifier => {
+                token.value.clone().unwrap_or_default()
+            }
+            _ => return Err(ParserError::Other("Expected parameter name".to_string())),
+        };
+        *pos += 1;
+        if params.iter().any(|param| param.name == name) {
+            return Err(ParserError::Other(format!(
+                "Duplicate parameter '{}'",
+                name
+            )));
+        }
+        let ty = if get_token_type(tokens, *pos) == TokenType::Colon {
+            if rest {
+                return Err(ParserError::Other(
+                    "A rest parameter cannot have a type".to_string(),
+                ));
+            }
+            *pos += 1;
+            Some(expect_identifier(tokens, pos, "Expected a type after ':'")?)
+        } else {
+            None
+        };
+
+        let default = if get_token_type(tokens, *pos) == TokenType::Equals {
+            if rest {
+                return Err(ParserError::Other(
+                    "A rest parameter cannot have a default".to_string(),
+                ));
+            }
+            *pos += 1;
+            Some(parse_expression(tokens, pos)?)
+        } else {
+            None
+        };
+        if default.is_none() && !rest && params.iter().any(|param| param.default.is_some()) {
+            return Err(ParserError::Other(format!(
+                "Parameter '{}' needs a default, as it follows one with a default",
+                name
+            )));
+        }
+        params.push(Param {
+            name,
+            default,
+            rest,
+            ty,
+        });
+
+        match get_token_type(tokens, *pos) {
+            TokenType::Comma => *pos += 1,
+            TokenType::RParen => {}
+            _ => {
+                return Err(ParserError::Other(
+                    "Expected ',' or ')' after parameter".to_string(),
+                ))
+            }
+        }
+    }
+    *pos += 1;
+    Ok(params)
+}
+
+/// `fn(x) { ... }`, with `pos` after `fn`. The body may end with an
+/// expression, which is the value of the lambda.
+fn parse_lambda(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Expression> {
+    let mut params = Vec::new();
+    for param in parse_params(tokens, pos)? {
+        if param.default.is_some() || param.rest || param.ty.is_some() {
+            return Err(ParserError::Other(format!(
+                "Lambda parameter '{}' cannot have a default or a type, or be a rest parameter",
+                param.name
+            )));
+        }
+        params.push(param.name);
+    }
+
+    if *pos >= tokens.len() || get_token_type(tokens, *pos) != TokenType::LBrace {
+        return Err(ParserError::Other(
+            "Expected '{' after lambda parameters".to_string(),
+        ));
+    }
+    *pos += 1;
+
+    let mut body = Vec::new();
+    let mut return_value = None;
+    loop {
+        while get_token_type(tokens, *pos) == TokenType::Newline {
+            *pos += 1;
+        }
+        if matches!(
+            get_token_type(tokens, *pos),
+            TokenType::RBrace | TokenType::EOF
+        ) {
+            break;
+        }
+        let start = *pos;
+        if let Ok(expr) = parse_expression(tokens, pos) {
+            consume_newline(tokens, pos);
+            if get_token_type(tokens, *pos) == TokenType::RBrace {
+                return_value = Some(Box::new(expr));
+                break;
+            }
+        }
+        *pos = start;
+        match parse_statement(tokens, pos)? {
+            Some(statement) => body.push(statement),
+            None => break,
+        }
+    }
+
+    if get_token_type(tokens, *pos) != TokenType::RBrace {
+        return Err(ParserError::Other(
+            "Expected '}' after lambda body".to_string(),
+        ));
+    }
+    *pos += 1;
+
+    Ok(Expression::Lambda {
+        params,
+        body,
+        return_value,
+    })
+}
+
+/// `global a, b`.
+fn parse_global(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<Statement>> {
+    *pos += 1;
+
+    let mut names = Vec::new();
+    loop {
+        match tokens.get(*pos) {
+            Some((_, token, _)) if token.token_type == TokenType::Identifier => {
+                names.push(token.value.clone().unwrap_or_default());
+                *pos += 1;
+            }
+            _ => {
+                return Err(ParserError::Other(
+                    "Expected variable name after ','".to_string(),
+                ))
+            }
+        }
+        if get_token_type(tokens, *pos) != TokenType::Comma {
+            break;
+        }
+        *pos += 1;
+    }
+
+    consume_newline(tokens, pos);
+    Ok(Some(Statement::Global { names }))
+}
+
+/// `let name = value` or `const NAME = value`.
+fn parse_binding(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<Statement>> {
+    let keyword = tokens[*pos].1.value.clone().unwrap_or_default();
+    let name = tokens[*pos + 1].1.value.clone().unwrap_or_default();
+    *pos += 2;
+    if get_token_type(tokens, *pos) != TokenType::Equals {
+        return Err(ParserError::Other(format!(
+            "Expected '=' after '{} {}'",
+            keyword, name
+        )));
+    }
+    *pos += 1;
+    let value = parse_expression(tokens, pos)?;
+    consume_newline(tokens, pos);
+    Ok(Some(if keyword == "const" {
+        Statement::Const { name, value }
+    } else {
+        Statement::Let { name, value }
+    }))
+}
+
+/// `struct Name { field: type, ... }`, with fields separated by commas or
+/// newlines.
+fn parse_struct(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<Statement>> {
+    let name = tokens[*pos + 1].1.value.clone().unwrap_or_default();
+    *pos += 3;
+
+    let mut fields: Vec<Field> = Vec::new();
+    loop {
+        skip_separators(tokens, pos);
+        if get_token_type(tokens, *pos) == TokenType::RBrace {
+            break;
+        }
+        let field = expect_identifier(tokens, pos, "Expected a field name")?;
+        if fields.iter().any(|existing| existing.name == field) {
+            return Err(ParserError::Other(format!(
+                "Duplicate field '{}' in struct {}",
+                field, name
+            )));
+        }
+        if get_token_type(tokens, *pos) != TokenType::Colon {
+            return Err(ParserError::Other(format!(
+                "Expected ':' and a type after field '{}'",
+                field
+            )));
+        }
+        *pos += 1;
+        let ty = expect_identifier(tokens, pos, "Expected a type after ':'")?;
+        fields.push(Field { name: field, ty });
+    }
+    *pos += 1;
+    if fields.is_empty() {
+        return Err(ParserError::Other(format!(
+            "Struct {} needs at least one field",
+            name
+        )));
+    }
+    consume_newline(tokens, pos);
+    Ok(Some(Statement::Struct { name, fields }))
+}
+
+/// `enum Name { A, B, ... }`, with variants separated by commas or
+/// newlines.
+fn parse_enum(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<Statement>> {
+    let name = tokens[*pos + 1].1.value.clone().unwrap_or_default();
+    *pos += 3;
+
+    let mut variants: Vec<String> = Vec::new();
+    loop {
+        skip_separators(tokens, pos);
+        if get_token_type(tokens, *pos) == TokenType::RBrace {
+            break;
+        }
+        let variant = expect_identifier(tokens, pos, "Expected a variant name")?;
+        if variants.contains(&variant) {
+            return Err(ParserError::Other(format!(
+                "Duplicate variant '{}' in enum {}",
+                variant, name
+            )));
+        }
+        variants.push(variant);
+    }
+    *pos += 1;
+    if variants.is_empty() {
+        return Err(ParserError::Other(format!(
+            "Enum {} needs at least one variant",
+            name
+        )));
+    }
+    consume_newline(tokens, pos);
+    Ok(Some(Statement::Enum { name, variants }))
+}
+
+/// Whether the `{` at `pos` opens a struct literal rather than a block:
+/// its first field is a name followed by `:`.
+fn is_struct_literal(tokens: &[SpannedToken], pos: usize) -> bool {
+    if get_token_type(tokens, pos) != TokenType::LBrace {
+        return false;
+    }
+    let mut next = pos + 1;
+    while get_token_type(tokens, next) == TokenType::Newline {
+        next += 1;
+    }
+    get_token_type(tokens, next) == TokenType::Identifier
+        && get_token_type(tokens, next + 1) == TokenType::Colon
+}
+
+/// `Name { field: value, ... }`, with `pos` at the `{`.
+fn parse_struct_literal(
+    name: String,
+    tokens: &[SpannedToken],
+    pos: &mut usize,
+) -> ParserResult<Expression> {
+    *pos += 1;
+    let mut fields: Vec<(String, Expression)> = Vec::new();
+    loop {
+        skip_separators(tokens, pos);
+        if get_token_type(tokens, *pos) == TokenType::RBrace {
+            break;
+        }
+        let field = expect_identifier(tokens, pos, "Expected a field name")?;
+        if get_token_type(tokens, *pos) != TokenType::Colon {
+            return Err(ParserError::Other(format!(
+                "Expected ':' and a value after field '{}'",
+                field
+            )));
+        }
+        *pos += 1;
+        fields.push((field, parse_expression(tokens, pos)?));
+    }
+    *pos += 1;
+    Ok(Expression::StructLiteral { name, fields })
+}
+
+/// `name.field...`: the name at `pos - 1` with the dotted parts that
+/// follow it, such as a field `s.host` or a function `app.info`.
+fn parse_dotted_name(mut name: String, tokens: &[SpannedToken], pos: &mut usize) -> String {
+    while get_token_type(tokens, *pos) == TokenType::Dot
+        && get_token_type(tokens, *pos + 1) == TokenType::Identifier
+    {
+        name.push('.');
+        name.push_str(tokens[*pos + 1].1.value.as_deref().unwrap_or_default());
+        *pos += 2;
+    }
+    name
+}
+
+/// The variable `name`, or the field of one it names, as in `s.owner.name`.
+fn path_expression(name: &str) -> Expression {
+    let mut parts = name.split('.');
+    let mut expr = Expression::Identifier(parts.next().unwrap_or_default().to_string());
+    for field in parts {
+        expr = Expression::Field {
+            object: Box::new(expr),
+            field: field.to_string(),
+        };
+    }
+    expr
+}
+
+/// Skip the commas and newlines between the fields of a struct.
+fn skip_separators(tokens: &[SpannedToken], pos: &mut usize) {
+    while matches!(
+        get_token_type(tokens, *pos),
+        TokenType::Comma | TokenType::Newline | TokenType::Comment
+    ) {
+        *pos += 1;
+    }
+}
+
+/// The name at `pos`, or `message` as an error.
+fn expect_identifier(
+    tokens: &[SpannedToken],
+    pos: &mut usize,
+    message: &str,
+) -> ParserResult<String> {
+    match tokens.get(*pos) {
+        Some((_, token, _)) if token.token_type == TokenType::Identifier => {
+            *pos += 1;
+            Ok(token.value.clone().unwrap_or_default())
+        }
+        _ => Err(ParserError::Other(message.to_string())),
+    }
+}
+
 fn parse_return(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<Statement>> {
     *pos += 1;
 
@@ -585,7 +1202,7 @@ fn parse_try(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<St
 
     let (_, token, _) = &tokens[*pos];
     if let TokenType::Identifier = token.token_type {
-        if token.value.as_ref().map_or(false, |v| v == "catch") {
+        if token.value.as_ref().is_some_and(|v| v == "catch") {
             *pos += 1;
         } else {
             return Err(ParserError::Other("Expected 'catch'".to_string()));
@@ -632,6 +1249,107 @@ fn parse_try(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<St
     }))
 }
 
+fn parse_match(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Option<Statement>> {
+    *pos += 1;
+    let value = parse_expression(tokens, pos)?;
+
+    consume_newline(tokens, pos);
+
+    if *pos >= tokens.len() || get_token_type(tokens, *pos) != TokenType::LBrace {
+        return Err(ParserError::Other(
+            "Expected '{' after match value".to_string(),
+        ));
+    }
+    *pos += 1;
+
+    let mut arms = Vec::new();
+    loop {
+        skip_trivia(tokens, pos);
+        match get_token_type(tokens, *pos) {
+            TokenType::RBrace => break,
+            TokenType::EOF => {
+                return Err(ParserError::Other(
+                    "Expected '}' after match arms".to_string(),
+                ))
+            }
+            _ => {}
+        }
+        arms.push(parse_match_arm(tokens, pos)?);
+        skip_trivia(tokens, pos);
+        if get_token_type(tokens, *pos) == TokenType::Comma {
+            *pos += 1;
+        }
+    }
+    *pos += 1;
+
+    consume_newline(tokens, pos);
+
+    Ok(Some(Statement::Match { value, arms }))
+}
+
+/// `pattern | pattern => { body }`, or a single statement after `=>`.
+fn parse_match_arm(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<MatchArm> {
+    let mut patterns = vec![parse_pattern(tokens, pos)?];
+    while get_token_type(tokens, *pos) == TokenType::Pipe {
+        *pos += 1;
+        patterns.push(parse_pattern(tokens, pos)?);
+    }
+
+    if get_token_type(tokens, *pos) != TokenType::FatArrow {
+        return Err(ParserError::Other(
+            "Expected '=>' after match pattern".to_string(),
+        ));
+    }
+    *pos += 1;
+
+    let body = if get_token_type(tokens, *pos) == TokenType::LBrace {
+        *pos += 1;
+        let body = parse_block(tokens, pos)?;
+        if *pos >= tokens.len() || get_token_type(tokens, *pos) != TokenType::RBrace {
+            return Err(ParserError::Other(
+                "Expected '}' after match arm".to_string(),
+            ));
+        }
+        *pos += 1;
+        body
+    } else {
+        parse_statement(tokens, pos)?.into_iter().collect()
+    };
+
+    Ok(MatchArm { patterns, body })
+}
+
+fn parse_pattern(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Pattern> {
+    let Some((_, token, _)) = tokens.get(*pos) else {
+        return Err(ParserError::Other("Expected a match pattern".to_string()));
+    };
+    let pattern = match token.token_type {
+        TokenType::String | TokenType::Integer | TokenType::Float | TokenType::Boolean => {
+            Pattern::Glob(token.value.clone().unwrap_or_default())
+        }
+        TokenType::Identifier if token.value.as_deref() == Some("_") => Pattern::Wildcard,
+        TokenType::Identifier
+            if get_token_type(tokens, *pos + 1) == TokenType::Dot
+                && get_token_type(tokens, *pos + 2) == TokenType::Identifier =>
+        {
+            let enumeration = token.value.clone().unwrap_or_default();
+            *pos += 2;
+            Pattern::Variant {
+                enumeration,
+                variant: tokens[*pos].1.value.clone().unwrap_or_default(),
+            }
+        }
+        _ => {
+            return Err(ParserError::Other(format!(
+                "Expected a match pattern, found {:?}",
+                token.token_type
+            )))
+        }
+    };
+    *pos += 1;
+    Ok(pattern)
+}
+
 fn parse_block(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Vec<Statement>> {
     let mut statements = Vec::new();
 
@@ -650,7 +1368,10 @@ fn parse_block(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult<Vec<Sta
         if token.token_type == TokenType::RBrace || token.token_type == TokenType::EOF {
             break;
         }
+        skip_trivia(tokens, pos);
+        let location = location_at(tokens, *pos);
         if let Some(stmt) = parse_statement(tokens, pos)? {
+            statements.extend(location);
             statements.push(stmt);
         }
     }
@@ -816,6 +1537,16 @@ fn parse_primary_expression(tokens: &[SpannedToken], pos: &mut usize) -> ParserR
             *pos += 1;
             Ok(Expression::Literal(Literal::Integer(val)))
         }
+        TokenType::Float => {
+            let val = token
+                .value
+                .clone()
+                .unwrap_or_default()
+                .parse()
+                .unwrap_or(0.0);
+            *pos += 1;
+            Ok(Expression::Literal(Literal::Float(val)))
+        }
         TokenType::Boolean => {
             let val = token.value.clone().unwrap_or_default() == "true";
             *pos += 1;
@@ -839,6 +1570,11 @@ fn parse_primary_expression(tokens: &[SpannedToken], pos: &mut usize) -> ParserR
             let name = token.value.clone().unwrap_or_default();
             *pos += 1;
 
+            if name == "fn" {
+                return parse_lambda(tokens, pos);
+            }
+
+            let name = parse_dotted_name(name, tokens, pos);
             if *pos < tokens.len() {
                 let (_, next_token, _) = &tokens[*pos];
                 if next_token.token_type == TokenType::LParen {
@@ -847,8 +1583,11 @@ fn parse_primary_expression(tokens: &[SpannedToken], pos: &mut usize) -> ParserR
                     return Ok(Expression::FunctionCall { name, args });
                 }
             }
+            if !name.contains('.') && is_struct_literal(tokens, *pos) {
+                return parse_struct_literal(name, tokens, pos);
+            }
 
-            Ok(Expression::Identifier(name))
+            Ok(path_expression(&name))
         }
         TokenType::LBracket => {
             *pos += 1;
@@ -954,7 +1693,23 @@ fn parse_function_args(tokens: &[SpannedToken], pos: &mut usize) -> ParserResult
             return Err(ParserError::Other("Expected ')' or argument".to_string()));
         }
 
-        args.push(parse_expression(tokens, pos)?);
+        let named = token.token_type == TokenType::Identifier
+            && get_token_type(tokens, *pos + 1) == TokenType::Colon;
+        if named {
+            let name = token.value.clone().unwrap_or_default();
+            *pos += 2;
+            let value = parse_expression(tokens, pos)?;
+            args.push(Expression::NamedArgument {
+                name,
+                value: Box::new(value),
+            });
+        } else if matches!(args.last(), Some(Expression::NamedArgument { .. })) {
+            return Err(ParserError::Other(
+                "Positional arguments must come before named arguments".to_string(),
+            ));
+        } else {
+            args.push(parse_expression(tokens, pos)?);
+        }
 
         if *pos < tokens.len() {
             let (_, token, _) = &tokens[*pos];
@@ -2,4 +2,4 @@ pub mod error;
 pub mod grammar;
 
 pub use error::{ParserError, ParserResult};
-pub use grammar::parse;
+pub use grammar::{parse, parse_with_locations};
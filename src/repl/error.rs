@@ -0,0 +1,15 @@
+use miette::Diagnostic;
+use thiserror::Error;
+
+#[derive(Error, Debug, Diagnostic)]
+#[diagnostic(code(shard::repl))]
+pub enum ReplError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Shell process exited")]
+    #[diagnostic(help("The session shell was terminated, e.g. by `exit`"))]
+    ShellExited,
+}
+
+pub type ReplResult<T> = std::result::Result<T, ReplError>;
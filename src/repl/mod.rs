@@ -0,0 +1,7 @@
+pub mod error;
+pub mod session;
+pub mod shell;
+
+pub use error::{ReplError, ReplResult};
+pub use session::{run, Session};
+pub use shell::Shell;
@@ -0,0 +1,231 @@
+use crate::ast::{Program, Statement};
+use crate::codegen::generate_statements;
+use crate::error::ShardError;
+use crate::lexer::{tokenize, LexerError, TokenType};
+use crate::parser::parse;
+use crate::repl::error::{ReplError, ReplResult};
+use crate::repl::shell::Shell;
+use crate::sema::check;
+use std::io::{BufRead, Write};
+
+const PROMPT: &str = "shard> ";
+const CONTINUATION_PROMPT: &str = "...> ";
+
+const HELP: &str = "\
+Enter Shard statements to run them. Blocks continue until braces balance;
+an empty line submits an incomplete block as is.
+
+  :sh [code]    show the generated shell for code, or for the last input
+  :ast [code]   show the AST for code, or for the last input
+  :reset        start a fresh shell session
+  :help         show this help
+  :quit         leave the REPL";
+
+/// Shows the captured output of a trailing command, which the generated
+/// code otherwise keeps in `__shard_stdout`/`__shard_stderr`.
+const SHOW_COMMAND_OUTPUT: &str = "\
+if [ -n \"$__shard_stdout\" ]; then printf '%s\\n' \"$__shard_stdout\"; fi
+if [ -n \"$__shard_stderr\" ]; then printf '%s\\n' \"$__shard_stderr\"; fi
+(exit $__shard_status)";
+
+#[derive(Debug)]
+struct Input {
+    program: Program,
+    shell: String,
+}
+
+/// Run an interactive session on stdin/stdout.
+pub fn run() -> ReplResult<()> {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    Session::new()?.run(&mut stdin.lock(), &mut stdout.lock())
+}
+
+#[derive(Debug)]
+pub struct Session {
+    shell: Shell,
+    last: Option<Input>,
+    /// The functions, structs, enums and constants earlier inputs declared,
+    /// which later ones may use.
+    declarations: Vec<Statement>,
+}
+
+impl Session {
+    pub fn new() -> ReplResult<Self> {
+        Ok(Session {
+            shell: Shell::spawn()?,
+            last: None,
+            declarations: Vec::new(),
+        })
+    }
+
+    pub fn run(&mut self, reader: &mut impl BufRead, writer: &mut impl Write) -> ReplResult<()> {
+        let mut buffer = String::new();
+
+        loop {
+            let prompt = if buffer.is_empty() {
+                PROMPT
+            } else {
+                CONTINUATION_PROMPT
+            };
+            write!(writer, "{}", prompt)?;
+            writer.flush()?;
+
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                writeln!(writer)?;
+                return Ok(());
+            }
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if buffer.is_empty() {
+                let trimmed = line.trim();
+                if trimmed.is_empty() {
+                    continue;
+                }
+                if let Some(command) = trimmed.strip_prefix(':') {
+                    if !self.meta_command(command, writer)? {
+                        return Ok(());
+                    }
+                    continue;
+                }
+            }
+
+            let submit_incomplete = line.trim().is_empty();
+            buffer.push_str(line);
+            buffer.push('\n');
+            if !submit_incomplete && !is_complete(&buffer) {
+                continue;
+            }
+
+            let source = std::mem::take(&mut buffer);
+            self.eval(&source, writer)?;
+        }
+    }
+
+    /// Compile and execute one input against the session shell.
+    pub fn eval(&mut self, source: &str, writer: &mut impl Write) -> ReplResult<()> {
+        let input = match compile(source, &self.declarations) {
+            Ok(input) => input,
+            Err(e) => {
+                writeln!(writer, "error: {}", e)?;
+                return Ok(());
+            }
+        };
+
+        let mut script = input.shell.clone();
+        if let Some(Statement::Command { .. }) = input.program.0.last() {
+            script.push_str(SHOW_COMMAND_OUTPUT);
+        }
+        self.declarations.extend(
+            input
+                .program
+                .0
+                .iter()
+                .filter(|s| is_declaration(s))
+                .cloned(),
+        );
+        self.last = Some(input);
+
+        match self.shell.run(&script) {
+            Ok((output, status)) => {
+                write!(writer, "{}", output)?;
+                if !output.is_empty() && !output.ends_with('\n') {
+                    writeln!(writer)?;
+                }
+                if status != 0 {
+                    writeln!(writer, "[exit {}]", status)?;
+                }
+            }
+            Err(ReplError::ShellExited) => {
+                writeln!(writer, "shell exited; starting a new session")?;
+                self.shell = Shell::spawn()?;
+                self.declarations.clear();
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+
+    /// Handle a `:command`. Returns `false` when the session should end.
+    fn meta_command(&mut self, command: &str, writer: &mut impl Write) -> ReplResult<bool> {
+        let (name, code) = command
+            .split_once(char::is_whitespace)
+            .map_or((command, ""), |(name, code)| (name, code.trim()));
+
+        match name {
+            "q" | "quit" | "exit" => return Ok(false),
+            "help" | "h" => writeln!(writer, "{}", HELP)?,
+            "reset" => {
+                self.shell = Shell::spawn()?;
+                self.last = None;
+                self.declarations.clear();
+                writeln!(writer, "session reset")?;
+            }
+            "sh" | "ast" => {
+                let compiled = if code.is_empty() {
+                    None
+                } else {
+                    match compile(code, &self.declarations) {
+                        Ok(input) => Some(input),
+                        Err(e) => {
+                            writeln!(writer, "error: {}", e)?;
+                            return Ok(true);
+                        }
+                    }
+                };
+                match compiled.as_ref().or(self.last.as_ref()) {
+                    Some(input) if name == "sh" => write!(writer, "{}", input.shell)?,
+                    Some(input) => match serde_json::to_string_pretty(&input.program) {
+                        Ok(json) => writeln!(writer, "{}", json)?,
+                        Err(e) => writeln!(writer, "error: {}", e)?,
+                    },
+                    None => writeln!(writer, "no previous input")?,
+                }
+            }
+            _ => writeln!(writer, "unknown command :{} (try :help)", name)?,
+        }
+        Ok(true)
+    }
+}
+
+/// Compile `source` as if it followed `declarations` in one script, so it
+/// may use them but cannot declare them again or assign a constant.
+fn compile(source: &str, declarations: &[Statement]) -> Result<Input, ShardError> {
+    let program = parse(tokenize(source)?)?;
+    check(&Program(
+        declarations.iter().chain(&program.0).cloned().collect(),
+    ))?;
+    let shell = generate_statements(declarations, &program.0)?;
+    Ok(Input { program, shell })
+}
+
+/// Whether `statement` declares something later inputs can use.
+fn is_declaration(statement: &Statement) -> bool {
+    matches!(
+        statement,
+        Statement::FunctionDef { .. }
+            | Statement::Struct { .. }
+            | Statement::Enum { .. }
+            | Statement::Const { .. }
+    )
+}
+
+/// Whether `source` can be submitted: braces balance and no string is left open.
+fn is_complete(source: &str) -> bool {
+    match tokenize(source) {
+        Ok(tokens) => {
+            let depth: i64 = tokens
+                .iter()
+                .map(|(_, token, _)| match token.token_type {
+                    TokenType::LBrace => 1,
+                    TokenType::RBrace => -1,
+                    _ => 0,
+                })
+                .sum();
+            depth <= 0
+        }
+        Err(LexerError::UnterminatedString { .. }) => false,
+        Err(_) => true,
+    }
+}
@@ -0,0 +1,70 @@
+use crate::repl::error::{ReplError, ReplResult};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+const DONE_MARKER: &str = "__shard_repl_done__";
+
+/// A long-lived `sh` coprocess. Variables and functions defined by one
+/// input stay available to the next.
+#[derive(Debug)]
+pub struct Shell {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Shell {
+    pub fn spawn() -> ReplResult<Self> {
+        // Generated code reuses a temporary directory the session names,
+        // and removes it on exit.
+        let mut child = Command::new("sh")
+            .env_remove("__shard_tmp")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().ok_or(ReplError::ShellExited)?;
+        let stdout = BufReader::new(child.stdout.take().ok_or(ReplError::ShellExited)?);
+        let mut shell = Shell {
+            child,
+            stdin,
+            stdout,
+        };
+        // Interleave stderr with stdout so output is shown in order.
+        shell.run("exec 2>&1")?;
+        Ok(shell)
+    }
+
+    /// Run a script and return its combined output and exit status.
+    pub fn run(&mut self, script: &str) -> ReplResult<(String, i32)> {
+        // The leading newline terminates output that lacks one; it is
+        // stripped again below.
+        let sentinel = format!("printf '\\n{} %s\\n' \"$?\"\n", DONE_MARKER);
+        self.stdin
+            .write_all(script.as_bytes())
+            .and_then(|_| self.stdin.write_all(b"\n"))
+            .and_then(|_| self.stdin.write_all(sentinel.as_bytes()))
+            .and_then(|_| self.stdin.flush())
+            .map_err(|_| ReplError::ShellExited)?;
+
+        let mut output = String::new();
+        loop {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line)? == 0 {
+                return Err(ReplError::ShellExited);
+            }
+            if let Some(status) = line.strip_prefix(DONE_MARKER) {
+                output.pop();
+                let status = status.trim().parse().unwrap_or(0);
+                return Ok((output, status));
+            }
+            output.push_str(&line);
+        }
+    }
+}
+
+impl Drop for Shell {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
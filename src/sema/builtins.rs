@@ -0,0 +1,170 @@
+//! Functions every Shard program can call without defining them.
+
+use crate::sema::types::Type;
+
+/// The signature of a builtin function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub params: &'static [&'static str],
+    /// How many of `params` must be passed; the rest are optional.
+    pub required: usize,
+    /// Whether any number of arguments may follow `params`.
+    pub variadic: bool,
+    /// The type of the result, as shown by the language server. `void`
+    /// builtins are called for their effect and write to the terminal.
+    pub returns: Type,
+    pub summary: &'static str,
+}
+
+const fn builtin(
+    name: &'static str,
+    params: &'static [&'static str],
+    returns: Type,
+    summary: &'static str,
+) -> Builtin {
+    Builtin {
+        name,
+        params,
+        required: params.len(),
+        variadic: false,
+        returns,
+        summary,
+    }
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    builtin("len", &["value"], Type::Int, "Number of elements of an array, or of characters of a string"),
+    builtin("upper", &["s"], Type::String, "`s` in upper case"),
+    builtin("lower", &["s"], Type::String, "`s` in lower case"),
+    builtin("trim", &["s"], Type::String, "`s` without leading and trailing whitespace"),
+    builtin("split", &["s", "separator"], Type::Array, "The parts of `s` between occurrences of `separator`"),
+    builtin("lines", &["text"], Type::Array, "The lines of `text`, such as the output of a command"),
+    builtin("join", &["items", "separator"], Type::String, "The elements of `items` with `separator` between them"),
+    builtin("replace", &["s", "from", "to"], Type::String, "`s` with every occurrence of `from` replaced by `to`"),
+    builtin("contains", &["s", "part"], Type::Bool, "Whether `part` occurs in `s`"),
+    builtin("starts_with", &["s", "prefix"], Type::Bool, "Whether `s` starts with `prefix`"),
+    builtin("ends_with", &["s", "suffix"], Type::Bool, "Whether `s` ends with `suffix`"),
+    Builtin {
+        required: 2,
+        ..builtin("substr", &["s", "start", "length"], Type::String, "`length` characters of `s` from `start`, or the rest of `s`; a negative `start` counts from the end")
+    },
+    builtin("map", &["items", "f"], Type::Array, "The result of `f` for each element of `items`"),
+    builtin("filter", &["items", "f"], Type::Array, "The elements of `items` for which `f` is true"),
+    builtin("reduce", &["items", "f", "initial"], Type::Any, "`initial` combined with each element of `items` in turn by `f(result, element)`"),
+    builtin("each", &["items", "f"], Type::Void, "Call `f` with each element of `items`"),
+    builtin("sort_by", &["items", "f"], Type::Array, "The elements of `items` ordered by the result of `f`, as numbers when both are numbers"),
+    builtin("index_of", &["s", "part"], Type::Int, "Position of the first `part` in `s`, or -1"),
+    builtin("repeat", &["s", "count"], Type::String, "`s` repeated `count` times"),
+    Builtin {
+        required: 2,
+        ..builtin("pad_left", &["s", "width", "fill"], Type::String, "`s` padded to `width` characters with `fill`, a space by default")
+    },
+    Builtin {
+        variadic: true,
+        ..builtin("format", &["template"], Type::String, "`template` with each `{}` replaced by the next argument")
+    },
+    builtin("abs", &["x"], Type::Number, "`x` without its sign"),
+    Builtin {
+        variadic: true,
+        ..builtin("min", &["a", "b"], Type::Number, "The smallest of the arguments")
+    },
+    Builtin {
+        variadic: true,
+        ..builtin("max", &["a", "b"], Type::Number, "The largest of the arguments")
+    },
+    builtin("round", &["x"], Type::Int, "`x` rounded to the nearest integer, halves away from zero"),
+    builtin("floor", &["x"], Type::Int, "The largest integer not above `x`"),
+    builtin("ceil", &["x"], Type::Int, "The smallest integer not below `x`"),
+    builtin("pow", &["x", "y"], Type::Float, "`x` raised to the power `y`"),
+    builtin("sqrt", &["x"], Type::Float, "The square root of `x`"),
+    builtin("to_int", &["x"], Type::Int, "`x` with its fractional part dropped"),
+    builtin("to_float", &["x"], Type::Float, "`x` as a float"),
+    Builtin {
+        required: 1,
+        ..builtin("parse_int", &["s", "default"], Type::Int, "The integer written in `s`, or `default`; an error when `s` is not an integer and no default is given")
+    },
+    builtin("json_parse", &["text"], Type::Json, "The object or array in the JSON `text`, as a map or array"),
+    builtin("json_stringify", &["value"], Type::String, "`value` as JSON text"),
+    builtin("log.debug", &["message"], Type::Void, "Log `message` when `SHARD_LOG_LEVEL` is `debug`"),
+    builtin("log.info", &["message"], Type::Void, "Log `message` unless `SHARD_LOG_LEVEL` is `warn` or above"),
+    builtin("log.warn", &["message"], Type::Void, "Log `message` as a warning"),
+    builtin("log.error", &["message"], Type::Void, "Log `message` as an error"),
+    Builtin {
+        required: 1,
+        ..builtin("die", &["message", "status"], Type::Void, "Log `message` as an error and exit with `status`, 1 by default")
+    },
+    Builtin {
+        required: 1,
+        ..builtin("prompt", &["message", "default"], Type::String, "A line of input, or `default` when it is empty")
+    },
+    builtin("confirm", &["message"], Type::Bool, "Whether the user answers yes; `--yes` or `SHARD_YES` answers yes"),
+    builtin("select", &["message", "options"], Type::String, "The element of `options` the user chooses"),
+    builtin("password", &["message"], Type::String, "A line of input that is not echoed"),
+    builtin("exists", &["path"], Type::Bool, "Whether `path` exists"),
+    builtin("is_file", &["path"], Type::Bool, "Whether `path` is a regular file"),
+    builtin("is_dir", &["path"], Type::Bool, "Whether `path` is a directory"),
+    builtin("read_file", &["path"], Type::String, "The contents of the file at `path`"),
+    builtin("write_file", &["path", "content"], Type::Bool, "Replace the contents of `path` with `content`"),
+    builtin("append_file", &["path", "content"], Type::Bool, "Add `content` to the end of `path`"),
+    builtin("mkdir_p", &["path"], Type::Bool, "Create the directory `path` and any missing parents"),
+    builtin("remove", &["path"], Type::Bool, "Delete `path`, recursively for directories"),
+    builtin("copy", &["from", "to"], Type::Bool, "Copy `from` to `to`, recursively for directories"),
+    builtin("move", &["from", "to"], Type::Bool, "Move or rename `from` to `to`"),
+    builtin("glob", &["pattern"], Type::Array, "The existing paths matching `pattern`"),
+    builtin("basename", &["path"], Type::String, "The last component of `path`"),
+    builtin("dirname", &["path"], Type::String, "`path` without its last component"),
+    builtin("abs_path", &["path"], Type::String, "`path` as an absolute path from the current directory, with `.` and `..` resolved; it need not exist"),
+    builtin("tempfile", &[], Type::String, "A new empty file, removed when the script exits"),
+    builtin("tempdir", &[], Type::String, "A new empty directory, removed when the script exits"),
+];
+
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+impl Builtin {
+    /// Whether a call may pass `count` arguments.
+    pub fn accepts(&self, count: usize) -> bool {
+        count >= self.required && (self.variadic || count <= self.params.len())
+    }
+
+    /// The accepted argument counts, e.g. `2`, `2 or 3` or `at least 1`.
+    pub fn arity(&self) -> String {
+        arity(self.required, self.params.len(), self.variadic)
+    }
+
+    /// `name(a, b, c?)`, with `...rest` for variadic builtins.
+    pub fn signature(&self) -> String {
+        let mut params: Vec<String> = self
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, param)| {
+                if i < self.required {
+                    param.to_string()
+                } else {
+                    format!("{}?", param)
+                }
+            })
+            .collect();
+        if self.variadic {
+            params.push("...args".to_string());
+        }
+        format!("{}({}) -> {}", self.name, params.join(", "), self.returns)
+    }
+}
+
+/// Describe the accepted argument counts of a function taking `required`
+/// to `max` arguments, or any number from `required` when `variadic`.
+pub(crate) fn arity(required: usize, max: usize, variadic: bool) -> String {
+    if variadic {
+        format!("at least {}", required)
+    } else if required == max {
+        max.to_string()
+    } else if required + 1 == max {
+        format!("{} or {}", required, max)
+    } else {
+        format!("{} to {}", required, max)
+    }
+}
@@ -0,0 +1,155 @@
+use miette::Diagnostic;
+use thiserror::Error;
+
+#[derive(Error, Debug, Diagnostic)]
+#[diagnostic(code(shard::sema))]
+pub enum SemaError {
+    #[error("{name}() takes {expected} argument(s), but {found} were given")]
+    #[diagnostic(help("The signature is {signature}"))]
+    WrongArity {
+        name: String,
+        expected: String,
+        found: usize,
+        signature: String,
+    },
+
+    #[error("{function}() has no named parameter '{name}'")]
+    #[diagnostic(help("The signature is {signature}"))]
+    UnknownArgument {
+        function: String,
+        name: String,
+        signature: String,
+    },
+
+    #[error("Parameter '{name}' of {function}() is passed more than once")]
+    DuplicateArgument { function: String, name: String },
+
+    #[error("{function}() is a builtin and takes no named arguments")]
+    #[diagnostic(help("Pass the arguments in order"))]
+    NamedBuiltinArgument { function: String },
+
+    #[error("'global {name}' is outside a function")]
+    #[diagnostic(help("Variables outside functions are global already"))]
+    GlobalOutsideFunction { name: String },
+
+    #[error("Parameter '{name}' of {function}() cannot be global")]
+    GlobalParameter { function: String, name: String },
+
+    #[error("Cannot assign to constant '{name}'")]
+    #[diagnostic(help("Declare it with `let` to change it"))]
+    AssignToConst { name: String },
+
+    #[error("'{name}' is already declared in this block")]
+    #[diagnostic(help("Assign to it instead, or declare it in a nested block"))]
+    DuplicateDeclaration { name: String },
+
+    #[error("'{name}' is used in its own declaration")]
+    #[diagnostic(help("Give the new variable another name"))]
+    SelfReferentialDeclaration { name: String },
+
+    #[error("Struct {name} is declared more than once")]
+    DuplicateStruct { name: String },
+
+    #[error("Struct {name} contains itself")]
+    #[diagnostic(help("A struct is flattened into variables, so it cannot be nested in itself"))]
+    RecursiveStruct { name: String },
+
+    #[error("Unknown type '{name}'")]
+    #[diagnostic(help("Use string, int, float, bool or the name of a struct or enum"))]
+    UnknownType { name: String },
+
+    #[error("Enum {name} is declared more than once, or as a struct too")]
+    DuplicateEnum { name: String },
+
+    #[error("Enum {enumeration} has no variant '{variant}'")]
+    #[diagnostic(help("The variants are {variants}"))]
+    UnknownVariant {
+        enumeration: String,
+        variant: String,
+        variants: String,
+    },
+
+    #[error("Match on {enumeration} has the pattern {pattern}, which is not one of its variants")]
+    EnumMatchPattern {
+        enumeration: String,
+        pattern: String,
+    },
+
+    #[error("Match on {enumeration} does not cover {missing}")]
+    #[diagnostic(help("Add an arm for each, or a `_` arm"))]
+    NonExhaustiveMatch {
+        enumeration: String,
+        missing: String,
+    },
+
+    #[error("Unknown struct '{name}'")]
+    UnknownStruct { name: String },
+
+    #[error("Struct {structure} has no field '{field}'")]
+    UnknownField { structure: String, field: String },
+
+    #[error("'{name}' is not a struct")]
+    NotAStruct { name: String },
+
+    #[error("'{name}' holds a {structure} and can only be assigned another")]
+    StructAssignment { name: String, structure: String },
+
+    #[error("Parameter '{name}' of {function}() must be given a {structure}")]
+    StructArgument {
+        function: String,
+        name: String,
+        structure: String,
+    },
+
+    #[error("Parameter '{name}' of {function}() has no type, so it cannot be given a {structure}")]
+    #[diagnostic(help("Declare the parameter as {name}: {structure}"))]
+    UntypedStructArgument {
+        function: String,
+        name: String,
+        structure: String,
+    },
+
+    #[error("Field '{field}' of {structure} is given more than once")]
+    DuplicateField { structure: String, field: String },
+
+    #[error("Field '{field}' of {structure} is missing")]
+    #[diagnostic(help("A struct literal gives a value to every field"))]
+    MissingField { structure: String, field: String },
+
+    #[error("Field '{field}' of {structure} is a {expected}, not a {found}")]
+    FieldType {
+        structure: String,
+        field: String,
+        expected: String,
+        found: String,
+    },
+
+    #[error("Parameter '{name}' of {function}() is a struct and cannot have a default")]
+    StructParameterDefault { function: String, name: String },
+
+    #[error("{function}() needs a function, but was given a {found}")]
+    #[diagnostic(help("Pass a lambda such as fn(x) {{ x }}, or the name of a function"))]
+    NotCallable { function: String, found: String },
+
+    #[error("{function}() calls {callee} with {found} argument(s), but it takes {expected}")]
+    CallableArity {
+        function: String,
+        callee: String,
+        expected: String,
+        found: usize,
+    },
+
+    #[error("Parameter '{name}' of {function}() is given a float, but its arithmetic is on whole numbers")]
+    #[diagnostic(help("Declare the parameter as {name}: float"))]
+    UntypedFloatArgument { function: String, name: String },
+
+    #[error("Function '{name}' redefines a builtin")]
+    #[diagnostic(help("Choose another name; builtins cannot be replaced"))]
+    RedefinedBuiltin { name: String },
+
+    #[error("A range cannot step by 0")]
+    #[diagnostic(help("Step by a positive number to count up, or a negative one to count down"))]
+    ZeroStep,
+}
+
+pub type SemaResult<T> = std::result::Result<T, SemaError>;
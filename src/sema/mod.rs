@@ -0,0 +1,1059 @@
+//! Checks run on a parsed program before it is optimized and generated.
+
+pub mod builtins;
+pub mod error;
+pub mod scope;
+pub mod types;
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::ast::{
+    Expression, Field, Literal, MatchArm, Param, Pattern, Program, Statement, UnaryOperator,
+};
+
+pub use builtins::{Builtin, BUILTINS};
+pub use error::{SemaError, SemaResult};
+pub use types::{infer_type, infer_type_with, Type};
+
+/// Reject programs that parse but cannot mean anything: calls with the
+/// wrong number of arguments or unknown named arguments, functions that
+/// redefine a builtin, `global` outside a function, assignments to
+/// constants, and matches that miss a variant of an enum.
+pub fn check(program: &Program) -> SemaResult<()> {
+    check_with_locations(program).map_err(|(error, _)| error)
+}
+
+/// Like [`check`], for a program parsed with locations: an error comes
+/// with the line and column of the statement it was found in, when it
+/// was found in one.
+pub fn check_with_locations(program: &Program) -> Result<(), (SemaError, Option<(usize, usize)>)> {
+    if let Some(name) = scope::globals(&program.0).first() {
+        return Err((
+            SemaError::GlobalOutsideFunction {
+                name: name.to_string(),
+            },
+            None,
+        ));
+    }
+    check_structs(&program.0).map_err(|error| (error, None))?;
+    let mut checker = Checker {
+        functions: functions(&program.0),
+        counted: counted(&program.0),
+        structs: structs(&program.0),
+        enums: enums(&program.0),
+        constants: program
+            .0
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Const { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect(),
+        scopes: Vec::new(),
+        base: 0,
+        location: None,
+    };
+    checker
+        .check_block(&program.0)
+        .map_err(|error| (error, checker.location))
+}
+
+/// The parameters of each function `statements` define, at any depth.
+pub fn functions(statements: &[Statement]) -> HashMap<&str, &[Param]> {
+    let mut functions = HashMap::new();
+    for_each_definition(statements, &mut |statement| {
+        if let Statement::FunctionDef { name, params, .. } = statement {
+            functions.insert(name.as_str(), params.as_slice());
+        }
+    });
+    functions
+}
+
+/// The parameters without a type that each function `statements` define
+/// does arithmetic on.
+fn counted(statements: &[Statement]) -> HashMap<&str, Vec<&str>> {
+    let mut counted = HashMap::new();
+    for_each_definition(statements, &mut |statement| {
+        if let Statement::FunctionDef {
+            name, params, body, ..
+        } = statement
+        {
+            let params = params
+                .iter()
+                .filter(|param| param.ty.is_none() && scope::counts(body, &param.name))
+                .map(|param| param.name.as_str())
+                .collect();
+            counted.insert(name.as_str(), params);
+        }
+    });
+    counted
+}
+
+/// The fields of each struct `statements` declare, at any depth.
+pub fn structs(statements: &[Statement]) -> HashMap<&str, &[Field]> {
+    let mut structs = HashMap::new();
+    for_each_definition(statements, &mut |statement| {
+        if let Statement::Struct { name, fields } = statement {
+            structs.insert(name.as_str(), fields.as_slice());
+        }
+    });
+    structs
+}
+
+/// The variants of each enum `statements` declare, at any depth.
+pub fn enums(statements: &[Statement]) -> HashMap<&str, &[String]> {
+    let mut enums = HashMap::new();
+    for_each_definition(statements, &mut |statement| {
+        if let Statement::Enum { name, variants } = statement {
+            enums.insert(name.as_str(), variants.as_slice());
+        }
+    });
+    enums
+}
+
+/// Call `f` with every statement in `statements` and the blocks and
+/// functions nested in them.
+fn for_each_definition<'a>(statements: &'a [Statement], f: &mut dyn FnMut(&'a Statement)) {
+    for statement in statements {
+        f(statement);
+        match statement {
+            Statement::FunctionDef { body, .. } => for_each_definition(body, f),
+            Statement::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                for_each_definition(then_branch, f);
+                if let Some(body) = else_branch {
+                    for_each_definition(body, f);
+                }
+            }
+            Statement::While { body, .. } | Statement::For { body, .. } => {
+                for_each_definition(body, f)
+            }
+            Statement::Try {
+                body, catch_body, ..
+            } => {
+                for_each_definition(body, f);
+                for_each_definition(catch_body, f);
+            }
+            Statement::Match { arms, .. } => {
+                for arm in arms {
+                    for_each_definition(&arm.body, f);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reject structs and enums declared twice, fields of unknown types, and
+/// structs that contain themselves, which could never be built.
+fn check_structs(statements: &[Statement]) -> SemaResult<()> {
+    let mut declared = Vec::new();
+    let mut declared_enums = Vec::new();
+    for_each_definition(statements, &mut |statement| match statement {
+        Statement::Struct { name, .. } => declared.push(name),
+        Statement::Enum { name, .. } => declared_enums.push(name),
+        _ => {}
+    });
+    let structs = structs(statements);
+    if declared.len() > structs.len() {
+        let twice = declared
+            .iter()
+            .enumerate()
+            .find_map(|(i, name)| declared[..i].contains(name).then_some(name))
+            .expect("a struct is declared twice");
+        return Err(SemaError::DuplicateStruct {
+            name: twice.to_string(),
+        });
+    }
+    let enums = enums(statements);
+    if let Some(twice) = declared_enums.iter().enumerate().find_map(|(i, name)| {
+        (declared_enums[..i].contains(name) || structs.contains_key(name.as_str())).then_some(name)
+    }) {
+        return Err(SemaError::DuplicateEnum {
+            name: twice.to_string(),
+        });
+    }
+
+    for (&name, fields) in &structs {
+        for field in fields.iter() {
+            if Type::value(&field.ty).is_none()
+                && !structs.contains_key(field.ty.as_str())
+                && !enums.contains_key(field.ty.as_str())
+            {
+                return Err(SemaError::UnknownType {
+                    name: field.ty.clone(),
+                });
+            }
+        }
+        // Follow the struct fields; reaching `name` again is a cycle.
+        let mut pending: Vec<&str> = vec![name];
+        let mut seen = HashSet::new();
+        while let Some(current) = pending.pop() {
+            for field in structs
+                .get(current)
+                .into_iter()
+                .flat_map(|fields| fields.iter())
+            {
+                if field.ty == name {
+                    return Err(SemaError::RecursiveStruct {
+                        name: name.to_string(),
+                    });
+                }
+                if structs.contains_key(field.ty.as_str()) && seen.insert(field.ty.as_str()) {
+                    pending.push(&field.ty);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Checker<'a> {
+    functions: HashMap<&'a str, &'a [Param]>,
+    /// The parameters without a type each function does arithmetic on.
+    counted: HashMap<&'a str, Vec<&'a str>>,
+    structs: HashMap<&'a str, &'a [Field]>,
+    enums: HashMap<&'a str, &'a [String]>,
+    /// The constants declared at the top of the script, which functions
+    /// see wherever they are defined.
+    constants: HashSet<&'a str>,
+    /// The variables of each enclosing block, innermost last.
+    scopes: Vec<Scope<'a>>,
+    /// The first scope of the function being checked.
+    base: usize,
+    /// The location of the statement an error was found in, set as the
+    /// error leaves the innermost block with a location.
+    location: Option<(usize, usize)>,
+}
+
+/// The variables of a block.
+#[derive(Default)]
+struct Scope<'a> {
+    /// The variables `let` and `const` declare here, and whether each is
+    /// a constant.
+    declared: HashMap<&'a str, bool>,
+    /// What the variables assigned here hold, as far as it is known.
+    values: HashMap<&'a str, Value<'a>>,
+    /// The variables of the script a function declares `global`.
+    globals: BTreeSet<&'a str>,
+}
+
+/// What a variable holds, as far as the checker can tell.
+#[derive(Debug, Clone, Copy)]
+enum Value<'a> {
+    /// A value of a type, `Type::Unknown` when it cannot be told.
+    Of(Type),
+    /// A lambda with these parameters.
+    Lambda(&'a [String]),
+    /// A struct of this name.
+    Struct(&'a str),
+}
+
+impl<'a> Checker<'a> {
+    /// Check a block. What it declares is gone at its end, and shadows
+    /// any variable of the same name until then.
+    fn check_block(&mut self, statements: &'a [Statement]) -> SemaResult<()> {
+        self.scopes.push(Scope::default());
+        let mut location = None;
+        let checked = statements.iter().try_for_each(|statement| {
+            if let Statement::Location { line, column } = statement {
+                location = Some((*line, *column));
+            }
+            self.check_statement(statement).inspect_err(|_| {
+                self.location = self.location.or(location);
+            })
+        });
+        self.scopes.pop();
+        checked
+    }
+
+    /// Whether assigning to `name` would change a constant: the innermost
+    /// declaration of it in the function, or else in the script.
+    fn is_constant(&self, name: &str) -> bool {
+        match self.scopes[self.base..]
+            .iter()
+            .rev()
+            .find_map(|scope| scope.declared.get(name))
+        {
+            Some(&constant) => constant,
+            None => self.base > 0 && self.constants.contains(name),
+        }
+    }
+
+    /// What the variable `name` holds: the innermost assignment or
+    /// declaration of it in the function, or else in the script.
+    fn value(&self, name: &str) -> Option<Value<'a>> {
+        if self.is_global(name) {
+            return self.scopes[0].values.get(name).copied();
+        }
+        for scope in self.scopes[self.base..].iter().rev() {
+            if let Some(value) = scope.values.get(name) {
+                return Some(*value);
+            }
+            if scope.declared.contains_key(name) {
+                return None;
+            }
+        }
+        match self.base {
+            0 => None,
+            _ => self.scopes[0].values.get(name).copied(),
+        }
+    }
+
+    /// Remember what `name` holds after assigning `value` to it. The
+    /// variable belongs to the innermost block declaring it, or else to
+    /// the function or script, or to the script if it is `global`.
+    fn assign(&mut self, name: &'a str, value: Value<'a>) {
+        let scope = if self.is_global(name) {
+            0
+        } else {
+            (self.base..self.scopes.len())
+                .rev()
+                .find(|&i| self.scopes[i].declared.contains_key(name))
+                .unwrap_or(self.base)
+        };
+        self.scopes[scope].values.insert(name, value);
+    }
+
+    /// Whether `name` is a variable of the script in the function being
+    /// checked.
+    fn is_global(&self, name: &str) -> bool {
+        self.base > 0 && self.scopes[self.base].globals.contains(name)
+    }
+
+    /// What the variable or field `path` holds. Reading a field of a
+    /// value that may be a struct is left to run time unless `strict`.
+    fn resolve(&self, path: &str, strict: bool) -> SemaResult<Option<Value<'a>>> {
+        let mut fields = path.split('.');
+        let root = fields.next().unwrap_or(path);
+        let mut value = self.value(root);
+        let mut walked = root.to_string();
+        for field in fields {
+            let structure = match value {
+                Some(Value::Struct(structure)) => structure,
+                Some(Value::Of(Type::Unknown)) if !strict => return Ok(value),
+                _ => return Err(SemaError::NotAStruct { name: walked }),
+            };
+            let Some(declared) = self.structs[structure]
+                .iter()
+                .find(|declared| declared.name == field)
+            else {
+                return Err(SemaError::UnknownField {
+                    structure: structure.to_string(),
+                    field: field.to_string(),
+                });
+            };
+            value = Some(match self.structs.get_key_value(declared.ty.as_str()) {
+                Some((&nested, _)) => Value::Struct(nested),
+                None => Value::Of(Type::value(&declared.ty).unwrap_or(Type::Unknown)),
+            });
+            walked = format!("{}.{}", walked, field);
+        }
+        Ok(value)
+    }
+
+    fn value_of(&self, expr: &'a Expression) -> Value<'a> {
+        match expr {
+            Expression::Lambda { params, .. } => Value::Lambda(params),
+            Expression::StructLiteral { name, .. } => Value::Struct(name),
+            Expression::Identifier(_) | Expression::Field { .. } => expr
+                .path()
+                .and_then(|path| self.resolve(&path, false).ok().flatten())
+                .unwrap_or(Value::Of(Type::Unknown)),
+            _ => Value::Of(self.type_of(expr)),
+        }
+    }
+
+    fn type_of(&self, expr: &Expression) -> Type {
+        infer_type_with(expr, &|path| match self.resolve(path, false).ok()?? {
+            Value::Of(ty) => Some(ty),
+            Value::Lambda(_) => Some(Type::Fn),
+            Value::Struct(_) => Some(Type::Struct),
+        })
+    }
+
+    fn check_assigned(&self, name: &str) -> SemaResult<()> {
+        if self.is_constant(name) {
+            return Err(SemaError::AssignToConst {
+                name: name.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn check_statement(&mut self, statement: &'a Statement) -> SemaResult<()> {
+        match statement {
+            Statement::Assignment { name, value } => {
+                // Assigning a field changes the struct it is part of.
+                self.check_assigned(scope::variable(name))?;
+                self.check_expression(value)?;
+                match name.rsplit_once('.') {
+                    Some((path, field)) => self.check_field_assignment(path, field, value),
+                    None => {
+                        let value = self.value_of(value);
+                        if let Some(Value::Struct(structure)) = self.value(name) {
+                            if !matches!(value, Value::Struct(assigned) if assigned == structure) {
+                                return Err(SemaError::StructAssignment {
+                                    name: name.clone(),
+                                    structure: structure.to_string(),
+                                });
+                            }
+                        }
+                        self.assign(name, value);
+                        Ok(())
+                    }
+                }
+            }
+            Statement::Let { name, value } | Statement::Const { name, value } => {
+                if scope::reads(value, name) {
+                    return Err(SemaError::SelfReferentialDeclaration { name: name.clone() });
+                }
+                self.check_expression(value)?;
+                let constant = matches!(statement, Statement::Const { .. });
+                let value = self.value_of(value);
+                let scope = self.scopes.last_mut().expect("a block is being checked");
+                if scope.declared.insert(name, constant).is_some() {
+                    return Err(SemaError::DuplicateDeclaration { name: name.clone() });
+                }
+                scope.values.insert(name, value);
+                Ok(())
+            }
+            Statement::Command { name, args } => {
+                // `deploy(x)` as a statement is checked as the call.
+                let is_call = matches!(&args[..],
+                    [Expression::FunctionCall { name: called, .. }] if called == name);
+                if !is_call {
+                    self.check_call(name, args)?;
+                }
+                args.iter().try_for_each(|arg| self.check_expression(arg))
+            }
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.check_expression(condition)?;
+                self.check_block(then_branch)?;
+                else_branch
+                    .as_deref()
+                    .map_or(Ok(()), |body| self.check_block(body))
+            }
+            Statement::While { condition, body } => {
+                self.check_expression(condition)?;
+                self.check_block(body)
+            }
+            Statement::For {
+                index,
+                variable,
+                iterable,
+                body,
+            } => {
+                index
+                    .iter()
+                    .chain([variable])
+                    .try_for_each(|name| self.check_assigned(name))?;
+                if let Expression::Range {
+                    step: Some(step), ..
+                } = iterable
+                {
+                    let zero = |expr: &Expression| {
+                        matches!(expr, Expression::Literal(Literal::Integer(0)))
+                    };
+                    if zero(step)
+                        || matches!(step.as_ref(), Expression::UnaryOp { op: UnaryOperator::Negate, expr } if zero(expr))
+                    {
+                        return Err(SemaError::ZeroStep);
+                    }
+                }
+                self.check_expression(iterable)?;
+                let element = match iterable {
+                    Expression::Range { .. } => Type::Int,
+                    _ => Type::Unknown,
+                };
+                if let Some(index) = index {
+                    self.assign(index, Value::Of(Type::Unknown));
+                }
+                self.assign(variable, Value::Of(element));
+                self.check_block(body)
+            }
+            Statement::FunctionDef {
+                name,
+                params,
+                body,
+                return_value,
+            } => {
+                if builtins::lookup(name).is_some() {
+                    return Err(SemaError::RedefinedBuiltin { name: name.clone() });
+                }
+                if let Some(param) = scope::globals(body)
+                    .into_iter()
+                    .find(|&global| params.iter().any(|param| param.name == global))
+                {
+                    return Err(SemaError::GlobalParameter {
+                        function: name.clone(),
+                        name: param.to_string(),
+                    });
+                }
+                for param in params {
+                    let Some(ty) = &param.ty else { continue };
+                    if self.structs.contains_key(ty.as_str()) {
+                        if param.default.is_some() {
+                            return Err(SemaError::StructParameterDefault {
+                                function: name.clone(),
+                                name: param.name.clone(),
+                            });
+                        }
+                    } else if Type::value(ty).is_none() && !self.enums.contains_key(ty.as_str()) {
+                        return Err(SemaError::UnknownType { name: ty.clone() });
+                    }
+                }
+                params
+                    .iter()
+                    .filter_map(|param| param.default.as_ref())
+                    .try_for_each(|default| self.check_expression(default))?;
+                // A function sees the constants of the script, and its
+                // parameters shadow them.
+                let outer = self.base;
+                self.base = self.scopes.len();
+                self.scopes.push(Scope {
+                    declared: params
+                        .iter()
+                        .map(|param| (param.name.as_str(), false))
+                        .collect(),
+                    values: params
+                        .iter()
+                        .map(|param| (param.name.as_str(), self.parameter(param)))
+                        .collect(),
+                    globals: scope::globals(body),
+                });
+                let checked = self.check_block(body).and_then(|()| {
+                    return_value
+                        .as_ref()
+                        .map_or(Ok(()), |value| self.check_expression(value))
+                });
+                self.scopes.truncate(self.base);
+                self.base = outer;
+                checked
+            }
+            Statement::Return { value } => value
+                .as_ref()
+                .map_or(Ok(()), |value| self.check_expression(value)),
+            Statement::Try {
+                body,
+                catch_var,
+                catch_body,
+            } => {
+                self.check_block(body)?;
+                self.check_assigned(catch_var)?;
+                self.assign(catch_var, Value::Of(Type::String));
+                self.check_block(catch_body)
+            }
+            Statement::Match { value, arms } => {
+                self.check_expression(value)?;
+                self.check_match(arms)?;
+                arms.iter().try_for_each(|arm| self.check_block(&arm.body))
+            }
+            Statement::ExpressionStatement(expr) => self.check_expression(expr),
+            Statement::Global { .. }
+            | Statement::Struct { .. }
+            | Statement::Enum { .. }
+            | Statement::Break
+            | Statement::Continue
+            | Statement::Location { .. } => Ok(()),
+        }
+    }
+
+    fn check_expression(&mut self, expr: &'a Expression) -> SemaResult<()> {
+        match expr {
+            Expression::Literal(Literal::Array(elements)) => elements
+                .iter()
+                .try_for_each(|element| self.check_expression(element)),
+            Expression::Literal(Literal::Map(pairs)) => {
+                pairs.iter().try_for_each(|(key, value)| {
+                    self.check_expression(key)?;
+                    self.check_expression(value)
+                })
+            }
+            Expression::Literal(_) | Expression::Identifier(_) => Ok(()),
+            Expression::BinaryOp { left, right, .. } => {
+                self.check_expression(left)?;
+                self.check_expression(right)
+            }
+            Expression::UnaryOp { expr, .. } | Expression::Length { expr } => {
+                self.check_expression(expr)
+            }
+            Expression::NamedArgument { value, .. } => self.check_expression(value),
+            Expression::ArrayIndex { array, index } => {
+                self.check_expression(array)?;
+                self.check_expression(index)
+            }
+            Expression::MapIndex { map, key } => {
+                self.check_expression(map)?;
+                self.check_expression(key)
+            }
+            Expression::FunctionCall { name, args } => {
+                self.check_call(name, args)?;
+                args.iter().try_for_each(|arg| self.check_expression(arg))
+            }
+            Expression::InterpolatedString { parts } => parts
+                .iter()
+                .try_for_each(|part| self.check_expression(part)),
+            Expression::Command { args, .. } => {
+                args.iter().try_for_each(|arg| self.check_expression(arg))
+            }
+            Expression::Lambda {
+                params,
+                body,
+                return_value,
+            } => {
+                self.scopes.push(Scope {
+                    declared: params.iter().map(|param| (param.as_str(), false)).collect(),
+                    values: params
+                        .iter()
+                        .map(|param| (param.as_str(), Value::Of(Type::Unknown)))
+                        .collect(),
+                    globals: BTreeSet::new(),
+                });
+                let checked = self.check_block(body).and_then(|()| {
+                    return_value
+                        .as_deref()
+                        .map_or(Ok(()), |value| self.check_expression(value))
+                });
+                self.scopes.pop();
+                checked
+            }
+            Expression::Range {
+                start, end, step, ..
+            } => {
+                self.check_expression(start)?;
+                self.check_expression(end)?;
+                step.as_deref()
+                    .map_or(Ok(()), |step| self.check_expression(step))
+            }
+            Expression::StructLiteral { name, fields } => {
+                self.check_struct_literal(name, fields)?;
+                fields
+                    .iter()
+                    .try_for_each(|(_, value)| self.check_expression(value))
+            }
+            Expression::Field { object, field } => match (object.as_ref(), expr.path()) {
+                (Expression::Identifier(name), _) if self.enums.contains_key(name.as_str()) => {
+                    self.check_variant(name, field)
+                }
+                (_, Some(path)) => self.resolve(&path, false).map(drop),
+                (_, None) => self.check_expression(object),
+            },
+        }
+    }
+
+    /// What a parameter holds: a value of its type, if it has one.
+    fn parameter(&self, param: &'a Param) -> Value<'a> {
+        match &param.ty {
+            _ if param.rest => Value::Of(Type::Array),
+            Some(ty) => match self.structs.get_key_value(ty.as_str()) {
+                Some((&structure, _)) => Value::Struct(structure),
+                None => Value::Of(Type::value(ty).unwrap_or(Type::Unknown)),
+            },
+            None => Value::Of(Type::Unknown),
+        }
+    }
+
+    /// Check `path.field = value`: `path` is a struct with the field, and
+    /// the value suits it.
+    fn check_field_assignment(
+        &self,
+        path: &str,
+        field: &str,
+        value: &'a Expression,
+    ) -> SemaResult<()> {
+        let Some(Value::Struct(structure)) = self.resolve(path, true)? else {
+            return Err(SemaError::NotAStruct {
+                name: path.to_string(),
+            });
+        };
+        match self.structs[structure]
+            .iter()
+            .find(|declared| declared.name == field)
+        {
+            Some(declared) => self.check_field(structure, declared, value),
+            None => Err(SemaError::UnknownField {
+                structure: structure.to_string(),
+                field: field.to_string(),
+            }),
+        }
+    }
+
+    /// Check that `value` suits the field `declared` of `structure`.
+    fn check_field(
+        &self,
+        structure: &str,
+        declared: &Field,
+        value: &'a Expression,
+    ) -> SemaResult<()> {
+        // Structs and enum variants have the type they name.
+        let named = match (self.value_of(value), value) {
+            (Value::Struct(name), _) => Some(name),
+            (_, Expression::Field { object, .. }) => match object.as_ref() {
+                Expression::Identifier(name) if self.enums.contains_key(name.as_str()) => {
+                    Some(name.as_str())
+                }
+                _ => None,
+            },
+            (_, Expression::FunctionCall { name, .. })
+                if self.enums.contains_key(name.as_str()) =>
+            {
+                Some(name.as_str())
+            }
+            _ => None,
+        };
+        let inferred = infer_type(value);
+        let matches = match (Type::value(&declared.ty), named) {
+            (_, None) if matches!(inferred, Type::Unknown | Type::Null) => true,
+            (Some(Type::Float), None) => inferred.is_number(),
+            (Some(expected), None) => expected == inferred,
+            (None, Some(named)) => declared.ty == named,
+            _ => false,
+        };
+        if !matches {
+            return Err(SemaError::FieldType {
+                structure: structure.to_string(),
+                field: declared.name.clone(),
+                expected: declared.ty.clone(),
+                found: named.unwrap_or(inferred.name()).to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that `Name.variant` names a variant when `Name` is an enum.
+    fn check_variant(&self, name: &str, variant: &str) -> SemaResult<()> {
+        match self.enums.get(name) {
+            Some(variants) if !variants.iter().any(|known| known == variant) => {
+                Err(SemaError::UnknownVariant {
+                    enumeration: name.to_string(),
+                    variant: variant.to_string(),
+                    variants: variants.join(", "),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check a match whose patterns are variants of an enum: every pattern
+    /// is one of its variants, and together they cover all of them unless
+    /// an arm matches anything.
+    fn check_match(&self, arms: &[MatchArm]) -> SemaResult<()> {
+        let patterns: Vec<&Pattern> = arms.iter().flat_map(|arm| &arm.patterns).collect();
+        let Some(enumeration) = patterns.iter().find_map(|pattern| match pattern {
+            Pattern::Variant { enumeration, .. } => Some(enumeration),
+            _ => None,
+        }) else {
+            return Ok(());
+        };
+        let Some(variants) = self.enums.get(enumeration.as_str()) else {
+            return Err(SemaError::UnknownType {
+                name: enumeration.clone(),
+            });
+        };
+        let mut covered = HashSet::new();
+        let mut wildcard = false;
+        for pattern in patterns {
+            match pattern {
+                Pattern::Variant {
+                    enumeration: other,
+                    variant,
+                } if other == enumeration => {
+                    self.check_variant(enumeration, variant)?;
+                    covered.insert(variant.as_str());
+                }
+                Pattern::Variant {
+                    enumeration: other,
+                    variant,
+                } => {
+                    return Err(SemaError::EnumMatchPattern {
+                        enumeration: enumeration.clone(),
+                        pattern: format!("{}.{}", other, variant),
+                    })
+                }
+                Pattern::Glob(glob) => {
+                    return Err(SemaError::EnumMatchPattern {
+                        enumeration: enumeration.clone(),
+                        pattern: format!("'{}'", glob),
+                    })
+                }
+                Pattern::Wildcard => wildcard = true,
+            }
+        }
+        let missing: Vec<String> = variants
+            .iter()
+            .filter(|variant| !covered.contains(variant.as_str()))
+            .map(|variant| format!("{}.{}", enumeration, variant))
+            .collect();
+        if !wildcard && !missing.is_empty() {
+            return Err(SemaError::NonExhaustiveMatch {
+                enumeration: enumeration.clone(),
+                missing: missing.join(", "),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that a struct literal gives each field of the struct once,
+    /// with a value of its type where that is known.
+    fn check_struct_literal(
+        &self,
+        name: &str,
+        values: &'a [(String, Expression)],
+    ) -> SemaResult<()> {
+        let Some(fields) = self.structs.get(name) else {
+            return Err(SemaError::UnknownStruct {
+                name: name.to_string(),
+            });
+        };
+        for (i, (field, value)) in values.iter().enumerate() {
+            let Some(declared) = fields.iter().find(|declared| &declared.name == field) else {
+                return Err(SemaError::UnknownField {
+                    structure: name.to_string(),
+                    field: field.clone(),
+                });
+            };
+            if values[..i].iter().any(|(earlier, _)| earlier == field) {
+                return Err(SemaError::DuplicateField {
+                    structure: name.to_string(),
+                    field: field.clone(),
+                });
+            }
+            self.check_field(name, declared, value)?;
+        }
+        if let Some(missing) = fields
+            .iter()
+            .find(|declared| values.iter().all(|(field, _)| *field != declared.name))
+        {
+            return Err(SemaError::MissingField {
+                structure: name.to_string(),
+                field: missing.name.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check the arguments of a call to a builtin or a function the
+    /// program defines. Other commands take anything.
+    fn check_call(&self, name: &str, args: &'a [Expression]) -> SemaResult<()> {
+        let named = args.iter().filter_map(|arg| match arg {
+            Expression::NamedArgument { name, .. } => Some(name),
+            _ => None,
+        });
+        if let Some(builtin) = builtins::lookup(name) {
+            if named.count() > 0 {
+                return Err(SemaError::NamedBuiltinArgument {
+                    function: name.to_string(),
+                });
+            }
+            if !builtin.accepts(args.len()) {
+                return Err(SemaError::WrongArity {
+                    name: name.to_string(),
+                    expected: builtin.arity(),
+                    found: args.len(),
+                    signature: builtin.signature(),
+                });
+            }
+            return match name {
+                "reduce" => self.check_callable(name, &args[1], 2),
+                "map" | "filter" | "each" | "sort_by" => self.check_callable(name, &args[1], 1),
+                _ => Ok(()),
+            };
+        }
+        // `Name(text)` converts to an enum.
+        if self.enums.contains_key(name) {
+            if args.len() != 1 {
+                return Err(SemaError::WrongArity {
+                    name: name.to_string(),
+                    expected: "1".to_string(),
+                    found: args.len(),
+                    signature: format!("{}(text)", name),
+                });
+            }
+            return Ok(());
+        }
+        if let Some(Value::Lambda(params)) = self.value(name) {
+            if let Some(arg) = named.clone().next() {
+                return Err(SemaError::UnknownArgument {
+                    function: name.to_string(),
+                    name: arg.clone(),
+                    signature: format!("{}({})", name, params.join(", ")),
+                });
+            }
+            if args.len() != params.len() {
+                return Err(SemaError::WrongArity {
+                    name: name.to_string(),
+                    expected: params.len().to_string(),
+                    found: args.len(),
+                    signature: format!("{}({})", name, params.join(", ")),
+                });
+            }
+            return Ok(());
+        }
+        let Some(params) = self.functions.get(name) else {
+            return Ok(());
+        };
+
+        let rest = params.last().is_some_and(|param| param.rest);
+        let fixed = &params[..params.len() - usize::from(rest)];
+        let positional = args.len() - named.clone().count();
+        let mut passed = vec![false; fixed.len()];
+        passed.iter_mut().take(positional).for_each(|p| *p = true);
+        for arg in named {
+            let Some(i) = fixed.iter().position(|param| &param.name == arg) else {
+                return Err(SemaError::UnknownArgument {
+                    function: name.to_string(),
+                    name: arg.clone(),
+                    signature: signature(name, params),
+                });
+            };
+            if passed[i] {
+                return Err(SemaError::DuplicateArgument {
+                    function: name.to_string(),
+                    name: arg.clone(),
+                });
+            }
+            passed[i] = true;
+        }
+
+        let required = fixed.iter().filter(|param| param.default.is_none()).count();
+        let missing = fixed
+            .iter()
+            .zip(&passed)
+            .any(|(param, passed)| param.default.is_none() && !passed);
+        if missing || (!rest && positional > fixed.len()) {
+            return Err(SemaError::WrongArity {
+                name: name.to_string(),
+                expected: builtins::arity(required, fixed.len(), rest),
+                found: args.len(),
+                signature: signature(name, params),
+            });
+        }
+
+        let counted = &self.counted[name];
+        for (i, arg) in args.iter().enumerate() {
+            let (param, value) = match arg {
+                Expression::NamedArgument { name, value } => (
+                    fixed.iter().find(|param| &param.name == name),
+                    value.as_ref(),
+                ),
+                _ => (fixed.get(i), arg),
+            };
+            let Some(param) = param else { continue };
+            let function = || name.to_string();
+            match (&param.ty, self.value_of(value)) {
+                // Structs are passed by their fields, so the parameter
+                // must say which struct it takes.
+                (None, Value::Struct(structure)) => {
+                    return Err(SemaError::UntypedStructArgument {
+                        function: function(),
+                        name: param.name.clone(),
+                        structure: structure.to_string(),
+                    })
+                }
+                (Some(ty), value)
+                    if self.structs.contains_key(ty.as_str())
+                        && !matches!(value, Value::Struct(structure) if structure == ty) =>
+                {
+                    return Err(SemaError::StructArgument {
+                        function: function(),
+                        name: param.name.clone(),
+                        structure: ty.clone(),
+                    })
+                }
+                // An untyped parameter is a whole number to arithmetic.
+                (None, _)
+                    if counted.contains(&param.name.as_str())
+                        && self.type_of(value) == Type::Float =>
+                {
+                    return Err(SemaError::UntypedFloatArgument {
+                        function: function(),
+                        name: param.name.clone(),
+                    })
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the higher-order builtin `builtin` is given a function
+    /// it can call with `count` arguments.
+    fn check_callable(&self, builtin: &str, arg: &Expression, count: usize) -> SemaResult<()> {
+        let arity = |callee: String, expected: String| SemaError::CallableArity {
+            function: builtin.to_string(),
+            callee,
+            expected,
+            found: count,
+        };
+        match arg {
+            Expression::Lambda { params, .. } if params.len() != count => {
+                Err(arity("the lambda".to_string(), params.len().to_string()))
+            }
+            Expression::Lambda { .. } => Ok(()),
+            Expression::Identifier(name) => match self.value(name) {
+                Some(Value::Lambda(params)) if params.len() != count => {
+                    Err(arity(format!("'{}'", name), params.len().to_string()))
+                }
+                Some(Value::Lambda(_)) | Some(Value::Of(Type::Unknown | Type::Fn)) => Ok(()),
+                Some(Value::Of(found)) => Err(SemaError::NotCallable {
+                    function: builtin.to_string(),
+                    found: found.to_string(),
+                }),
+                Some(Value::Struct(found)) => Err(SemaError::NotCallable {
+                    function: builtin.to_string(),
+                    found: found.to_string(),
+                }),
+                None => match self.functions.get(name.as_str()) {
+                    Some(params) => {
+                        let rest = params.last().is_some_and(|param| param.rest);
+                        let fixed = params.len() - usize::from(rest);
+                        let required = params
+                            .iter()
+                            .filter(|param| !param.rest && param.default.is_none())
+                            .count();
+                        if count < required || (!rest && count > fixed) {
+                            return Err(arity(
+                                format!("'{}'", name),
+                                builtins::arity(required, fixed, rest),
+                            ));
+                        }
+                        Ok(())
+                    }
+                    None => Ok(()),
+                },
+            },
+            _ => match self.type_of(arg) {
+                Type::Unknown | Type::Fn => Ok(()),
+                found => Err(SemaError::NotCallable {
+                    function: builtin.to_string(),
+                    found: found.to_string(),
+                }),
+            },
+        }
+    }
+}
+
+/// `name(a, b?, ...rest)` for a function the program defines.
+fn signature(name: &str, params: &[Param]) -> String {
+    let params: Vec<String> = params
+        .iter()
+        .map(|param| {
+            if param.rest {
+                format!("...{}", param.name)
+            } else if param.default.is_some() {
+                format!("{}?", param.name)
+            } else {
+                param.name.clone()
+            }
+        })
+        .collect();
+    format!("{}({})", name, params.join(", "))
+}
@@ -0,0 +1,312 @@
+//! Which variables belong to a function, which names a program binds, and
+//! which functions call themselves.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::ast::{BinaryOperator, Expression, Literal, Param, Statement, UnaryOperator};
+
+/// The names `global` declares in a function body.
+pub fn globals(body: &[Statement]) -> BTreeSet<&str> {
+    let mut names = BTreeSet::new();
+    walk(body, &mut |statement| {
+        if let Statement::Global { names: declared } = statement {
+            names.extend(declared.iter().map(String::as_str));
+        }
+    });
+    names
+}
+
+/// The variables of a function, in order of first assignment: its
+/// parameters and every variable its body assigns that is not declared
+/// `global`.
+pub fn locals(params: &[Param], body: &[Statement]) -> Vec<String> {
+    let globals = globals(body);
+    let mut names: Vec<String> = params.iter().map(|param| param.name.clone()).collect();
+    walk(body, &mut |statement| {
+        let assigned: Vec<&str> = match statement {
+            Statement::Assignment { name, .. } => vec![variable(name)],
+            Statement::For {
+                index, variable, ..
+            } => index.iter().chain([variable]).map(String::as_str).collect(),
+            Statement::Try { catch_var, .. } => vec![catch_var],
+            _ => Vec::new(),
+        };
+        for name in assigned {
+            if !globals.contains(name) && !names.iter().any(|local| local == name) {
+                names.push(name.to_string());
+            }
+        }
+    });
+    names
+}
+
+/// Every name `statements` bind, at any depth and once per binding:
+/// variables assigned or declared, loop and catch variables, and the
+/// parameters of functions and lambdas.
+pub fn bindings(statements: &[Statement]) -> Vec<&str> {
+    let mut names = Vec::new();
+    collect_bindings(statements, &mut names);
+    names
+}
+
+fn collect_bindings<'a>(statements: &'a [Statement], names: &mut Vec<&'a str>) {
+    walk(statements, &mut |statement| {
+        bound_by(statement, names);
+        for expr in expressions(statement) {
+            visit(expr, &mut |expr| {
+                if let Expression::Lambda { params, body, .. } = expr {
+                    names.extend(params.iter().map(String::as_str));
+                    // `visit` has seen the expressions of the body.
+                    walk(body, &mut |statement| bound_by(statement, names));
+                }
+            });
+        }
+    });
+}
+
+/// The names `statement` binds itself, with those of a function it
+/// defines.
+fn bound_by<'a>(statement: &'a Statement, names: &mut Vec<&'a str>) {
+    match statement {
+        Statement::Assignment { name, .. } => names.push(variable(name)),
+        Statement::Let { name, .. } | Statement::Const { name, .. } => names.push(name),
+        Statement::For {
+            index, variable, ..
+        } => names.extend(index.iter().chain([variable]).map(String::as_str)),
+        Statement::Try { catch_var, .. } => names.push(catch_var),
+        Statement::FunctionDef { params, body, .. } => {
+            names.extend(params.iter().map(|param| param.name.as_str()));
+            collect_bindings(body, names);
+        }
+        _ => {}
+    }
+}
+
+/// The variable an assignment changes: `s` for the field `s.port`.
+pub fn variable(name: &str) -> &str {
+    name.split('.').next().unwrap_or(name)
+}
+
+/// Whether evaluating `expr` reads the variable `name`.
+pub fn reads(expr: &Expression, name: &str) -> bool {
+    let mut found = false;
+    visit(expr, &mut |expr| {
+        found |= matches!(expr, Expression::Identifier(read) if read == name);
+    });
+    found
+}
+
+/// Whether `body` does arithmetic on the variable `name`, which the shell
+/// does on whole numbers only.
+pub fn counts(body: &[Statement], name: &str) -> bool {
+    let operand = |expr: &Expression| matches!(expr, Expression::Identifier(read) if read == name);
+    let text = |expr: &Expression| {
+        matches!(
+            expr,
+            Expression::Literal(Literal::String(_)) | Expression::InterpolatedString { .. }
+        )
+    };
+    let mut found = false;
+    walk(body, &mut |statement| {
+        for expr in expressions(statement) {
+            visit(expr, &mut |expr| {
+                found |= match expr {
+                    Expression::BinaryOp {
+                        op: BinaryOperator::Add,
+                        left,
+                        right,
+                    } => (operand(left) || operand(right)) && !text(left) && !text(right),
+                    Expression::BinaryOp {
+                        op:
+                            BinaryOperator::Subtract
+                            | BinaryOperator::Multiply
+                            | BinaryOperator::Divide
+                            | BinaryOperator::Modulo,
+                        left,
+                        right,
+                    } => operand(left) || operand(right),
+                    Expression::UnaryOp {
+                        op: UnaryOperator::Negate,
+                        expr,
+                    } => operand(expr),
+                    _ => false,
+                };
+            });
+        }
+    });
+    found
+}
+
+/// The functions defined in `statements` that can call themselves, directly
+/// or through other functions.
+pub fn recursive(statements: &[Statement]) -> BTreeSet<&str> {
+    let mut graph = HashMap::new();
+    collect_calls(statements, &mut graph);
+    graph
+        .keys()
+        .copied()
+        .filter(|&function| {
+            let mut seen = BTreeSet::new();
+            let mut pending: Vec<&str> = graph[function].iter().copied().collect();
+            while let Some(called) = pending.pop() {
+                if called == function {
+                    return true;
+                }
+                if seen.insert(called) {
+                    pending.extend(graph.get(called).into_iter().flatten().copied());
+                }
+            }
+            false
+        })
+        .collect()
+}
+
+/// Map each function defined in `statements` to the names its body may
+/// call. A name passed as a value counts, as it may be called by a
+/// higher-order builtin.
+fn collect_calls<'a>(statements: &'a [Statement], graph: &mut HashMap<&'a str, BTreeSet<&'a str>>) {
+    for_each_function(statements, &mut |name, body| {
+        let mut called = BTreeSet::new();
+        walk(body, &mut |statement| {
+            if let Statement::Command { name, .. } = statement {
+                called.insert(name.as_str());
+            }
+            for expr in expressions(statement) {
+                visit(expr, &mut |expr| match expr {
+                    Expression::FunctionCall { name, .. }
+                    | Expression::Command { name, .. }
+                    | Expression::Identifier(name) => {
+                        called.insert(name.as_str());
+                    }
+                    _ => {}
+                });
+            }
+        });
+        graph.insert(name, called);
+    });
+}
+
+/// Call `f` with the name and body of every function defined in
+/// `statements`, at any depth.
+fn for_each_function<'a>(statements: &'a [Statement], f: &mut dyn FnMut(&'a str, &'a [Statement])) {
+    walk(statements, &mut |statement| {
+        if let Statement::FunctionDef { name, body, .. } = statement {
+            f(name, body);
+            for_each_function(body, f);
+        }
+    });
+}
+
+/// Call `f` with each statement in `statements` and the blocks nested in
+/// them, but not in the bodies of functions or lambdas they define.
+fn walk<'a>(statements: &'a [Statement], f: &mut dyn FnMut(&'a Statement)) {
+    for statement in statements {
+        f(statement);
+        match statement {
+            Statement::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                walk(then_branch, f);
+                if let Some(body) = else_branch {
+                    walk(body, f);
+                }
+            }
+            Statement::While { body, .. } | Statement::For { body, .. } => walk(body, f),
+            Statement::Try {
+                body, catch_body, ..
+            } => {
+                walk(body, f);
+                walk(catch_body, f);
+            }
+            Statement::Match { arms, .. } => {
+                for arm in arms {
+                    walk(&arm.body, f);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The expressions a statement evaluates itself, not those of its blocks.
+fn expressions(statement: &Statement) -> Vec<&Expression> {
+    match statement {
+        Statement::Assignment { value, .. }
+        | Statement::Let { value, .. }
+        | Statement::Const { value, .. } => vec![value],
+        Statement::Command { args, .. } => args.iter().collect(),
+        Statement::If { condition, .. } | Statement::While { condition, .. } => vec![condition],
+        Statement::For { iterable, .. } => vec![iterable],
+        Statement::FunctionDef { params, .. } => params
+            .iter()
+            .filter_map(|param| param.default.as_ref())
+            .collect(),
+        Statement::Return { value } => value.iter().collect(),
+        Statement::Match { value, .. } => vec![value],
+        Statement::ExpressionStatement(expr) => vec![expr],
+        _ => Vec::new(),
+    }
+}
+
+/// Call `f` with `expr` and every expression in it, including those in
+/// the bodies of lambdas.
+fn visit<'a>(expr: &'a Expression, f: &mut dyn FnMut(&'a Expression)) {
+    f(expr);
+    match expr {
+        Expression::Literal(Literal::Array(elements)) => {
+            elements.iter().for_each(|element| visit(element, f))
+        }
+        Expression::Literal(Literal::Map(pairs)) => {
+            for (key, value) in pairs {
+                visit(key, f);
+                visit(value, f);
+            }
+        }
+        Expression::BinaryOp { left, right, .. } => {
+            visit(left, f);
+            visit(right, f);
+        }
+        Expression::UnaryOp { expr, .. } | Expression::Length { expr } => visit(expr, f),
+        Expression::NamedArgument { value, .. } => visit(value, f),
+        Expression::Field { object, .. } => visit(object, f),
+        Expression::StructLiteral { fields, .. } => {
+            fields.iter().for_each(|(_, value)| visit(value, f))
+        }
+        Expression::ArrayIndex { array, index } => {
+            visit(array, f);
+            visit(index, f);
+        }
+        Expression::MapIndex { map, key } => {
+            visit(map, f);
+            visit(key, f);
+        }
+        Expression::FunctionCall { args, .. } | Expression::Command { args, .. } => {
+            args.iter().for_each(|arg| visit(arg, f))
+        }
+        Expression::InterpolatedString { parts } => parts.iter().for_each(|part| visit(part, f)),
+        Expression::Range {
+            start, end, step, ..
+        } => {
+            visit(start, f);
+            visit(end, f);
+            if let Some(step) = step {
+                visit(step, f);
+            }
+        }
+        Expression::Lambda {
+            body, return_value, ..
+        } => {
+            walk(body, &mut |statement| {
+                expressions(statement)
+                    .into_iter()
+                    .for_each(|expr| visit(expr, f))
+            });
+            if let Some(value) = return_value {
+                visit(value, f);
+            }
+        }
+        Expression::Literal(_) | Expression::Identifier(_) => {}
+    }
+}
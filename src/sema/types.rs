@@ -0,0 +1,137 @@
+//! Static types of expressions, as far as they can be known without
+//! running the program.
+
+use std::fmt;
+
+use crate::ast::{BinaryOperator, Expression, Literal, UnaryOperator};
+use crate::sema::builtins;
+
+/// The static type of a value. Struct and enum types are checked by name
+/// and are not represented here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Type {
+    String,
+    Int,
+    Float,
+    Bool,
+    Null,
+    Array,
+    Map,
+    /// The result of `json_parse`: a map or an array, known at run time.
+    Json,
+    Struct,
+    Fn,
+    Range,
+    /// The result of a numeric builtin such as `abs`: an int or a float,
+    /// depending on its arguments.
+    Number,
+    Any,
+    /// The result of a builtin called for its effect.
+    Void,
+    Unknown,
+}
+
+impl Type {
+    /// The types a value can have, as written in a struct field or a
+    /// parameter. The name of a struct or an enum is a type too.
+    pub const VALUES: &'static [Type] = &[Type::String, Type::Int, Type::Float, Type::Bool];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Type::String => "string",
+            Type::Int => "int",
+            Type::Float => "float",
+            Type::Bool => "bool",
+            Type::Null => "null",
+            Type::Array => "array",
+            Type::Map => "map",
+            Type::Json => "json",
+            Type::Struct => "struct",
+            Type::Fn => "fn",
+            Type::Range => "range",
+            Type::Number => "number",
+            Type::Any => "any",
+            Type::Void => "void",
+            Type::Unknown => "unknown",
+        }
+    }
+
+    /// The value type written as `name`, if there is one.
+    pub fn value(name: &str) -> Option<Type> {
+        Type::VALUES.iter().copied().find(|ty| ty.name() == name)
+    }
+
+    pub fn is_number(self) -> bool {
+        matches!(self, Type::Int | Type::Float)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Infer a display type for an expression without evaluating it.
+pub fn infer_type(expr: &Expression) -> Type {
+    infer_type_with(expr, &|_| None)
+}
+
+/// Like `infer_type`, with `variables` giving the types of identifiers.
+pub fn infer_type_with(expr: &Expression, variables: &dyn Fn(&str) -> Option<Type>) -> Type {
+    let infer = |expr| infer_type_with(expr, variables);
+    match expr {
+        Expression::Literal(literal) => match literal {
+            Literal::Integer(_) => Type::Int,
+            Literal::Float(_) => Type::Float,
+            Literal::Boolean(_) => Type::Bool,
+            Literal::Null => Type::Null,
+            Literal::String(_) => Type::String,
+            Literal::Array(_) => Type::Array,
+            Literal::Map(_) => Type::Map,
+        },
+        Expression::Identifier(name) => variables(name).unwrap_or(Type::Unknown),
+        Expression::Field { .. } => expr
+            .path()
+            .and_then(|path| variables(&path))
+            .unwrap_or(Type::Unknown),
+        Expression::StructLiteral { .. } => Type::Struct,
+        Expression::InterpolatedString { .. } => Type::String,
+        Expression::BinaryOp { op, left, right } => match op {
+            // `+` joins strings.
+            BinaryOperator::Add if infer(left) == Type::String || infer(right) == Type::String => {
+                Type::String
+            }
+            BinaryOperator::Add
+            | BinaryOperator::Subtract
+            | BinaryOperator::Multiply
+            | BinaryOperator::Divide
+            | BinaryOperator::Modulo => match (infer(left), infer(right)) {
+                (Type::Float, _) | (_, Type::Float) => Type::Float,
+                _ => Type::Int,
+            },
+            _ => Type::Bool,
+        },
+        Expression::UnaryOp { op, expr } => match op {
+            UnaryOperator::Not => Type::Bool,
+            UnaryOperator::Negate => infer(expr),
+        },
+        Expression::FunctionCall { name, args } => match builtins::lookup(name) {
+            // Numeric builtins such as `abs` keep the type of their arguments.
+            Some(builtin) if builtin.returns == Type::Number => {
+                if args.iter().any(|arg| infer(arg) == Type::Float) {
+                    Type::Float
+                } else {
+                    Type::Int
+                }
+            }
+            Some(builtin) => builtin.returns,
+            None => Type::Unknown,
+        },
+        Expression::Length { .. } => Type::Int,
+        Expression::Command { .. } => Type::Bool,
+        Expression::Lambda { .. } => Type::Fn,
+        Expression::Range { .. } => Type::Range,
+        _ => Type::Unknown,
+    }
+}
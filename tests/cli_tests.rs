@@ -6,7 +6,7 @@ fn run_shard(args: &[&str]) -> (bool, String, String) {
     let result = Command::new("cargo")
         .args(["run", "--"])
         .args(args)
-        .current_dir("/Users/hdzilyes/projects/shard")
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
         .output()
         .expect("Failed to run cargo");
 
@@ -183,3 +183,160 @@ fn test_cli_full_example() {
     assert!(stdout.contains("__shard_name='Shard'"));
     assert!(stdout.contains("__shard_status=$?"));
 }
+
+#[test]
+fn test_cli_transpile_target() {
+    let temp = TempDir::new().unwrap();
+    let input_file = temp.child("test.shard");
+    input_file.write_str("items = [1, 2]").unwrap();
+
+    let (success, stdout, stderr) = run_shard(&[
+        "transpile",
+        "-i",
+        input_file.path().to_str().unwrap(),
+        "--target",
+        "bash",
+    ]);
+    assert!(success, "stderr: {}", stderr);
+    assert!(stdout.contains("#!/usr/bin/env bash"));
+    assert!(stdout.contains("__shard_items=(1 2)"));
+}
+
+#[test]
+fn test_cli_compliance_warn() {
+    let temp = TempDir::new().unwrap();
+    let input_file = temp.child("test.shard");
+    input_file
+        .write_str("fn app.info(msg) { echo msg }")
+        .unwrap();
+    let path = input_file.path().to_str().unwrap();
+
+    let (success, _, stderr) = run_shard(&["transpile", "-i", path]);
+    assert!(!success);
+    assert!(stderr.contains("UnsupportedFeature"), "stderr: {}", stderr);
+
+    let (success, stdout, stderr) = run_shard(&["transpile", "-i", path, "--compliance", "warn"]);
+    assert!(success, "stderr: {}", stderr);
+    assert!(stderr.contains("warning:"));
+    assert!(stdout.contains("app.info() {"));
+}
+
+#[test]
+fn test_cli_build_source_map() {
+    let temp = TempDir::new().unwrap();
+    let input_file = temp.child("test.shard");
+    input_file.write_str("x = 10\necho x\n").unwrap();
+    let output_file = temp.child("test.sh");
+
+    let (success, _, stderr) = run_shard(&[
+        "build",
+        "-i",
+        input_file.path().to_str().unwrap(),
+        "-o",
+        output_file.path().to_str().unwrap(),
+        "--source-map",
+    ]);
+    assert!(success, "stderr: {}", stderr);
+
+    let map = std::fs::read_to_string(temp.child("test.sh.map").path()).unwrap();
+    let map: serde_json::Value = serde_json::from_str(&map).unwrap();
+    assert_eq!(map["file"], "test.sh");
+    assert_eq!(map["mappings"][0]["source_line"], 1);
+}
+
+#[test]
+fn test_cli_build_opt_level() {
+    let temp = TempDir::new().unwrap();
+    let input_file = temp.child("test.shard");
+    input_file.write_str("x = 1 + 2\necho x\n").unwrap();
+    let output_file = temp.child("test.sh");
+    let input = input_file.path().to_str().unwrap();
+    let output = output_file.path().to_str().unwrap();
+
+    let (success, _, stderr) = run_shard(&["build", "-i", input, "-o", output]);
+    assert!(success, "stderr: {}", stderr);
+    let shell = std::fs::read_to_string(output_file.path()).unwrap();
+    assert!(shell.contains("__shard_x=3\n"), "{}", shell);
+
+    let (success, _, stderr) = run_shard(&["build", "-i", input, "-o", output, "--opt-level", "0"]);
+    assert!(success, "stderr: {}", stderr);
+    let shell = std::fs::read_to_string(output_file.path()).unwrap();
+    assert!(shell.contains("__shard_x=$((1 + 2))\n"), "{}", shell);
+}
+
+#[test]
+fn test_cli_transpile_layout_flags() {
+    let temp = TempDir::new().unwrap();
+    let input_file = temp.child("test.shard");
+    input_file
+        .write_str("x = 1\nif x == 1 {\n  echo x\n}\n")
+        .unwrap();
+    let path = input_file.path().to_str().unwrap();
+
+    let (success, stdout, stderr) = run_shard(&[
+        "transpile",
+        "-i",
+        path,
+        "--indent",
+        "4",
+        "--source-comments",
+    ]);
+    assert!(success, "stderr: {}", stderr);
+    assert!(stdout.contains("# if x == 1 {\n"), "{}", stdout);
+    assert!(
+        stdout.contains("\n    # echo x\n    __shard_stdout_tmp"),
+        "{}",
+        stdout
+    );
+
+    let (success, stdout, stderr) = run_shard(&["transpile", "-i", path, "--minify"]);
+    assert!(success, "stderr: {}", stderr);
+    assert!(stdout.contains("_s_x=1\n"), "{}", stdout);
+}
+
+#[test]
+fn test_cli_build_external_runtime() {
+    let temp = TempDir::new().unwrap();
+    let input_file = temp.child("test.shard");
+    input_file
+        .write_str("items = ['a', 'b']\ncount = len(items)\nexit count\n")
+        .unwrap();
+    let output_file = temp.child("test.sh");
+
+    let (success, _, stderr) = run_shard(&[
+        "build",
+        "-i",
+        input_file.path().to_str().unwrap(),
+        "-o",
+        output_file.path().to_str().unwrap(),
+        "--runtime",
+        "external",
+    ]);
+    assert!(success, "stderr: {}", stderr);
+
+    let runtime = std::fs::read_to_string(temp.child("shard-runtime.sh").path()).unwrap();
+    assert!(runtime.contains("__shard_len() {"), "{}", runtime);
+    let output = Command::new("sh").arg(output_file.path()).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_cli_transpile_external_runtime() {
+    let temp = TempDir::new().unwrap();
+    let input_file = temp.child("test.shard");
+    input_file
+        .write_str("items = ['a']\nn = len(items)\n")
+        .unwrap();
+    let path = input_file.path().to_str().unwrap();
+
+    let (success, stdout, stderr) = run_shard(&["transpile", "-i", path, "--runtime", "external"]);
+    assert!(!success);
+    assert!(stdout.is_empty(), "{}", stdout);
+    assert!(stderr.contains("build --runtime external"), "{}", stderr);
+    assert!(!temp.child("shard-runtime.sh").path().exists());
+
+    // A script that needs no helpers sources nothing.
+    input_file.write_str("n = 1\n").unwrap();
+    let (success, _, stderr) = run_shard(&["transpile", "-i", path, "--runtime", "external"]);
+    assert!(success, "stderr: {}", stderr);
+}
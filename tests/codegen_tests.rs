@@ -1,4 +1,4 @@
-use shard::{generate, parse, tokenize};
+use shard::{generate, generate_with_options, parse, tokenize, CodegenOptions, Target};
 
 #[test]
 fn test_codegen_simple_assignment() {
@@ -43,7 +43,7 @@ fn test_codegen_null() {
     let ast = parse(tokens).unwrap();
     let output = generate(&ast).unwrap();
 
-    assert!(output.contains("__shard_val=null"));
+    assert!(output.contains("__shard_val=''"));
 }
 
 #[test]
@@ -183,3 +183,102 @@ fn test_codegen_underscore_variable() {
 
     assert!(output.contains("__shard__private=42"));
 }
+
+fn generate_for(input: &str, target: Target) -> String {
+    let tokens = tokenize(input).unwrap();
+    let ast = parse(tokens).unwrap();
+    generate_with_options(&ast, &CodegenOptions::for_target(target)).unwrap()
+}
+
+#[test]
+fn test_codegen_default_target_is_posix() {
+    let tokens = tokenize("x = 1").unwrap();
+    let ast = parse(tokens).unwrap();
+    assert_eq!(
+        generate(&ast).unwrap(),
+        generate_for("x = 1", Target::Posix)
+    );
+}
+
+#[test]
+fn test_codegen_target_shebangs() {
+    assert!(generate_for("x = 1", Target::Posix).starts_with("#!/bin/sh\n"));
+    assert!(generate_for("x = 1", Target::Bash).starts_with("#!/usr/bin/env bash\n"));
+    assert!(generate_for("x = 1", Target::Zsh).starts_with("#!/usr/bin/env zsh\n"));
+    assert!(generate_for("x = 1", Target::Busybox).starts_with("#!/bin/sh\n"));
+}
+
+#[test]
+fn test_codegen_target_pipefail() {
+    assert!(!generate_for("x = 1", Target::Posix).contains("pipefail"));
+    assert!(generate_for("x = 1", Target::Bash).contains("set -o pipefail"));
+    assert!(generate_for("x = 1", Target::Busybox).contains("set -o pipefail"));
+}
+
+#[test]
+fn test_codegen_posix_function_has_no_local() {
+    let output = generate_for("fn greet(name) { echo name }", Target::Posix);
+    assert!(!output.contains("local"));
+    assert!(output.contains("__shard_greet_name=$1"));
+}
+
+#[test]
+fn test_codegen_local_where_supported() {
+    for target in [Target::Bash, Target::Zsh, Target::Busybox] {
+        let output = generate_for("fn greet(name) { echo name }", target);
+        assert!(
+            output.contains("local __shard_greet_name=\"$1\""),
+            "{}",
+            target
+        );
+    }
+}
+
+#[test]
+fn test_codegen_posix_array_is_newline_separated() {
+    let output = generate_for("arr = [1, 'two', 3]", Target::Posix);
+    assert!(output.contains("__shard_arr=$(printf '%s\\n' 1 'two' 3)"));
+}
+
+#[test]
+fn test_codegen_bash_native_arrays() {
+    let output = generate_for("arr = [1, 2]\nn = len(arr)", Target::Bash);
+    assert!(output.contains("__shard_arr=(1 2)"));
+    assert!(output.contains("__shard_n=${#__shard_arr[@]}"));
+}
+
+#[test]
+fn test_codegen_bash_double_brackets() {
+    let output = generate_for("if x == 1 { echo one }", Target::Bash);
+    assert!(output.contains("if [[ \"$__shard_x\" -eq 1 ]]; then"));
+    let output = generate_for("if x == 1 { echo one }", Target::Posix);
+    assert!(output.contains("if [ \"$__shard_x\" -eq 1 ]; then"));
+}
+
+#[test]
+fn test_codegen_assoc_arrays() {
+    let output = generate_for("m = {a: 1}", Target::Bash);
+    assert!(output.contains("declare -gA __shard_m=(['a']=1)"));
+    let output = generate_for("m = {a: 1}", Target::Zsh);
+    assert!(output.contains("typeset -gA __shard_m"));
+    let output = generate_for("m = {a: 1}", Target::Posix);
+    assert!(output.contains("printf '%s=%s\\n' 'a' 1"));
+}
+
+#[test]
+fn test_codegen_logical_conditions() {
+    let output = generate(
+        &parse(tokenize("if x > 1 and not y > 2 or z == 5 {\n  echo yes\n}").unwrap()).unwrap(),
+    )
+    .unwrap();
+    assert!(output.contains(
+        "if [ \"$__shard_x\" -gt 1 ] && ! [ \"$__shard_y\" -gt 2 ] || [ \"$__shard_z\" -eq 5 ]; then"
+    ));
+}
+
+#[test]
+fn test_codegen_command_condition() {
+    let input = "if exists('f') and grep -q 'needle' 'f' {\n  echo found\n}";
+    let output = generate(&parse(tokenize(input).unwrap()).unwrap()).unwrap();
+    assert!(output.contains("if [ -e 'f' ] && grep '-q' 'needle' 'f'; then"));
+}
@@ -0,0 +1,70 @@
+//! Helpers shared by the test files that run generated scripts.
+
+// Each test file uses only some of them.
+#![allow(dead_code)]
+
+use assert_fs::TempDir;
+use shard::codegen::generate_script;
+use shard::sema::SemaError;
+use shard::{check, parse, tokenize, CodegenOptions, Target};
+use std::process::{Command, Output};
+
+/// Parse `input` and run the semantic checks on it.
+pub fn check_source(input: &str) -> Result<(), SemaError> {
+    check(&parse(tokenize(input).unwrap()).unwrap())
+}
+
+/// Run `program` on the POSIX target with `env` set.
+pub fn run(program: &str, env: &[(&str, &str)]) -> Output {
+    let ast = parse(tokenize(program).unwrap()).unwrap();
+    check(&ast).unwrap();
+    let script = generate_script(&ast, &CodegenOptions::default()).unwrap();
+    Command::new("sh")
+        .args(["-c", &script.text])
+        .env_remove("SHARD_LOG_LEVEL")
+        .env_remove("SHARD_LOG_TIMESTAMPS")
+        .envs(env.iter().copied())
+        .output()
+        .unwrap()
+}
+
+/// Run `program` on `target` in an empty directory and return the final
+/// value of each of `vars`.
+pub fn values(program: &str, target: Target, vars: &[&str]) -> Vec<String> {
+    let ast = parse(tokenize(program).unwrap()).unwrap();
+    check(&ast).unwrap();
+    let script = generate_script(&ast, &CodegenOptions::for_target(target)).unwrap();
+    let mut shell = script.text;
+    for var in vars {
+        shell.push_str(&format!("printf '%s\\n' \"$__shard_{}\"\n", var));
+    }
+    let interpreter = match target {
+        Target::Bash => "bash",
+        _ => "sh",
+    };
+    let dir = TempDir::new().unwrap();
+    let output = Command::new(interpreter)
+        .args(["-c", &shell])
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Run `program` on the POSIX and bash targets and check the final value
+/// of each variable on both.
+pub fn assert_values(program: &str, expected: &[(&str, &str)]) {
+    let vars: Vec<&str> = expected.iter().map(|(var, _)| *var).collect();
+    let want: Vec<String> = expected.iter().map(|(_, v)| v.to_string()).collect();
+    for target in [Target::Posix, Target::Bash] {
+        assert_eq!(values(program, target, &vars), want, "on {}", target);
+    }
+}
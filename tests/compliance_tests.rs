@@ -0,0 +1,107 @@
+use shard::codegen::{generate_script, CodegenError, Compliance, Feature};
+use shard::{parse, tokenize, CodegenOptions, Target};
+
+const PROGRAM: &str = "servers = ['web', 'db']
+ports = {web: 80, db: 5432}
+fn deploy(server) {
+    echo server
+}
+for s in servers {
+    deploy s
+}
+if len(servers) > 1 {
+    echo 'many'
+}";
+
+fn options(target: Target, compliance: Compliance) -> CodegenOptions {
+    let mut options = CodegenOptions::for_target(target);
+    options.compliance = compliance;
+    options
+}
+
+fn script_for(
+    input: &str,
+    options: &CodegenOptions,
+) -> Result<shard::codegen::Script, CodegenError> {
+    let ast = parse(tokenize(input).unwrap()).unwrap();
+    generate_script(&ast, options)
+}
+
+#[test]
+fn test_posix_output_uses_no_features() {
+    let script = script_for(PROGRAM, &CodegenOptions::for_target(Target::Posix)).unwrap();
+    assert!(script.features.is_empty(), "{:?}", script.features);
+    assert!(script.warnings.is_empty());
+}
+
+#[test]
+fn test_bash_output_records_features() {
+    let script = script_for(PROGRAM, &CodegenOptions::for_target(Target::Bash)).unwrap();
+    for feature in [
+        Feature::Local,
+        Feature::IndexedArrays,
+        Feature::AssocArrays,
+        Feature::DoubleBrackets,
+        Feature::Pipefail,
+    ] {
+        assert!(script.features.contains(&feature), "missing {:?}", feature);
+    }
+}
+
+#[test]
+fn test_busybox_output_records_only_ash_features() {
+    let script = script_for(PROGRAM, &CodegenOptions::for_target(Target::Busybox)).unwrap();
+    let features: Vec<Feature> = script.features.into_iter().collect();
+    assert_eq!(features, vec![Feature::Local, Feature::Pipefail]);
+}
+
+#[test]
+fn test_recorded_features_are_supported_by_target() {
+    for target in [Target::Posix, Target::Bash, Target::Zsh, Target::Busybox] {
+        let script = script_for(PROGRAM, &CodegenOptions::for_target(target)).unwrap();
+        for feature in &script.features {
+            assert!(target.supports(*feature), "{} uses {}", target, feature);
+        }
+    }
+}
+
+#[test]
+fn test_strict_rejects_non_posix_function_name() {
+    let result = script_for(
+        "fn log.info(msg) { echo msg }",
+        &CodegenOptions::for_target(Target::Posix),
+    );
+    match result {
+        Err(CodegenError::UnsupportedFeature {
+            construct, target, ..
+        }) => {
+            assert!(construct.contains("log.info"));
+            assert_eq!(target, "posix");
+        }
+        other => panic!("expected UnsupportedFeature, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_warn_mode_emits_and_reports() {
+    let script = script_for(
+        "fn log.info(msg) { echo msg }",
+        &options(Target::Posix, Compliance::Warn),
+    )
+    .unwrap();
+    assert!(script.text.contains("log.info() {"));
+    assert_eq!(script.warnings.len(), 1);
+    assert_eq!(script.warnings[0].feature, Feature::ExtendedFunctionNames);
+    assert!(script.warnings[0].to_string().contains("posix"));
+}
+
+#[test]
+fn test_bash_allows_extended_function_name() {
+    let script = script_for(
+        "fn log.info(msg) { echo msg }",
+        &CodegenOptions::for_target(Target::Bash),
+    )
+    .unwrap();
+    assert!(script.features.contains(&Feature::ExtendedFunctionNames));
+    assert!(script.warnings.is_empty());
+}
@@ -1,5 +1,10 @@
+mod common;
+
+use common::{assert_values, check_source, run};
+use shard::ast::{BinaryOperator, Expression, Literal, Pattern, Statement, UnaryOperator};
 use shard::lexer::tokenize;
 use shard::parser::parse;
+use shard::sema::SemaError;
 
 #[test]
 fn test_parse_if_statement() {
@@ -85,3 +90,293 @@ fn test_parse_nested_control_flow() {
     let ast = parse(tokens).unwrap();
     assert_eq!(ast.0.len(), 1);
 }
+
+#[test]
+fn test_parse_match() {
+    let input = "match os {\n  'linux' | 'freebsd' => { echo unix }\n  'win*' => echo windows,\n  _ => {\n    echo other\n  }\n}";
+    let ast = parse(tokenize(input).unwrap()).unwrap();
+    let Statement::Match { arms, .. } = &ast.0[0] else {
+        panic!("Expected match, got {:?}", ast.0[0]);
+    };
+    assert_eq!(arms.len(), 3);
+    assert_eq!(
+        arms[0].patterns,
+        vec![
+            Pattern::Glob("linux".to_string()),
+            Pattern::Glob("freebsd".to_string())
+        ]
+    );
+    assert_eq!(arms[1].patterns, vec![Pattern::Glob("win*".to_string())]);
+    assert_eq!(arms[1].body.len(), 1);
+    assert_eq!(arms[2].patterns, vec![Pattern::Wildcard]);
+}
+
+#[test]
+fn test_parse_match_without_arrow() {
+    assert!(parse(tokenize("match x { 'a' { echo a } }").unwrap()).is_err());
+}
+
+#[test]
+fn test_parse_for_range_with_step() {
+    let ast = parse(tokenize("for i in 0..=10 step 2 {\n  echo i\n}").unwrap()).unwrap();
+    let Statement::For { iterable, .. } = &ast.0[0] else {
+        panic!("Expected for, got {:?}", ast.0[0]);
+    };
+    assert_eq!(
+        *iterable,
+        Expression::Range {
+            start: Box::new(Expression::Literal(Literal::Integer(0))),
+            end: Box::new(Expression::Literal(Literal::Integer(10))),
+            inclusive: true,
+            step: Some(Box::new(Expression::Literal(Literal::Integer(2)))),
+        }
+    );
+}
+
+#[test]
+fn test_parse_for_with_index() {
+    let ast = parse(tokenize("for i, item in items {\n  echo item\n}").unwrap()).unwrap();
+    let Statement::For {
+        index, variable, ..
+    } = &ast.0[0]
+    else {
+        panic!("Expected for, got {:?}", ast.0[0]);
+    };
+    assert_eq!(index.as_deref(), Some("i"));
+    assert_eq!(variable, "item");
+}
+
+#[test]
+fn test_parse_not_keyword_applies_to_comparison() {
+    let ast = parse(tokenize("ok = not x > 1").unwrap()).unwrap();
+    let Statement::Assignment { value, .. } = &ast.0[0] else {
+        panic!("Expected assignment, got {:?}", ast.0[0]);
+    };
+    let Expression::UnaryOp {
+        op: UnaryOperator::Not,
+        expr,
+    } = value
+    else {
+        panic!("Expected not, got {:?}", value);
+    };
+    assert!(matches!(
+        expr.as_ref(),
+        Expression::BinaryOp {
+            op: BinaryOperator::Greater,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_parse_command_in_condition() {
+    let ast = parse(tokenize("while not test -f done {\n  sleep 1\n}").unwrap()).unwrap();
+    let Statement::While { condition, .. } = &ast.0[0] else {
+        panic!("Expected while, got {:?}", ast.0[0]);
+    };
+    let Expression::UnaryOp { expr, .. } = condition else {
+        panic!("Expected not, got {:?}", condition);
+    };
+    assert_eq!(
+        **expr,
+        Expression::Command {
+            name: "test".to_string(),
+            args: vec![
+                Expression::Literal(Literal::String("-f".to_string())),
+                Expression::Identifier("done".to_string()),
+            ],
+        }
+    );
+}
+
+#[test]
+fn test_parse_subtraction_is_not_a_command() {
+    let ast = parse(tokenize("if x - 1 {\n  echo x\n}").unwrap()).unwrap();
+    let Statement::If { condition, .. } = &ast.0[0] else {
+        panic!("Expected if, got {:?}", ast.0[0]);
+    };
+    assert!(matches!(
+        condition,
+        Expression::BinaryOp {
+            op: BinaryOperator::Subtract,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_conditions_short_circuit() {
+    assert_values(
+        "\
+n = 0
+if n > 0 and mkdir_p('never') {
+  n = 10
+}
+made = 0
+if exists('never') {
+  made = 1
+}
+if not n > 0 and test -d '.' {
+  n = n + 1
+}
+if n > 5 or test -d '.' {
+  n = n + 1
+}
+",
+        &[("n", "2"), ("made", "0")],
+    );
+}
+
+#[test]
+fn test_for_over_map_yields_keys_on_every_target() {
+    assert_values(
+        "\
+m = {'a': 1, 'b': 2}
+count = 0
+for key in m {
+  count = count + 1
+  if key == 'a' {
+    a = key
+  }
+  if key == 'b' {
+    b = key
+  }
+}
+",
+        &[("count", "2"), ("a", "a"), ("b", "b")],
+    );
+}
+
+#[test]
+fn test_for_lines_leaves_stdin_to_the_body() {
+    assert_values(
+        "\
+got = ''
+for v in lines('a
+b') {
+  x = prompt('value', 'default')
+  got = format('{}{}={};', got, v, x)
+}
+",
+        &[("got", "a=default;b=default;")],
+    );
+}
+
+#[test]
+fn test_for_keeps_its_end_through_calls() {
+    assert_values(
+        "\
+out = ''
+fn inner() {
+  global out
+  for i in 0..1 + 1 {
+    out = format('{}i{};', out, i)
+  }
+}
+fn walk(depth) {
+  global out
+  for i in 0..depth + 1 {
+    out = format('{}{}{};', out, depth, i)
+    if depth > 0 {
+      walk(depth - 1)
+    }
+    inner()
+  }
+}
+walk(1)
+",
+        &[("out", "10;00;i0;i1;i0;i1;11;00;i0;i1;i0;i1;")],
+    );
+}
+
+#[test]
+fn test_for_step_known_at_run_time() {
+    assert_values(
+        "\
+down = ''
+by = -2
+for i in 6..0 step by {
+  down = format('{}{},', down, i)
+}
+up = ''
+by = 3
+for i in 0..=6 step by {
+  up = format('{}{},', up, i)
+}
+",
+        &[("down", "6,4,2,"), ("up", "0,3,6,")],
+    );
+}
+
+#[test]
+fn test_for_ranges() {
+    assert_values(
+        "\
+up = ''
+for i in 0..3 {
+  up = format('{}{},', up, i)
+}
+down = ''
+for i in 10..=0 step -5 {
+  down = format('{}{},', down, i)
+}
+n = 3
+odd = ''
+for i in 1..n * 2 step 2 {
+  if i == 3 {
+    continue
+  }
+  odd = format('{}{},', odd, i)
+}
+",
+        &[("up", "0,1,2,"), ("down", "10,5,0,"), ("odd", "1,5,")],
+    );
+}
+
+#[test]
+fn test_for_lines_with_index() {
+    assert_values(
+        "\
+out = 'x y
+second
+
+last'
+got = ''
+for n, line in lines(out) {
+  got = format('{}{}:{}|', got, n, line)
+}
+empty = 0
+for line in lines('') {
+  empty = empty + 1
+}
+",
+        &[("got", "0:x y|1:second|2:|3:last|"), ("empty", "0")],
+    );
+}
+
+#[test]
+fn test_for_step_of_zero() {
+    assert!(matches!(
+        check_source("for i in 0..3 step 0 {\n  echo i\n}\n"),
+        Err(SemaError::ZeroStep)
+    ));
+    assert!(matches!(
+        check_source("for i in 3..0 step -0 {\n  echo i\n}\n"),
+        Err(SemaError::ZeroStep)
+    ));
+
+    let output = run(
+        "\
+by = 0
+for i in 0..=3 step by {
+  echo i
+}
+",
+        &[],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "ERROR for: the step of a range is 0\n"
+    );
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::{assert_values, check_source};
+use shard::ast::{Expression, Literal, Statement};
+use shard::sema::SemaError;
+use shard::{parse, tokenize};
+
+#[test]
+fn test_parse_let_and_const() {
+    let ast = parse(tokenize("const URL = 'x'\nlet n = 1\n").unwrap()).unwrap();
+    assert_eq!(
+        ast.0,
+        vec![
+            Statement::Const {
+                name: "URL".to_string(),
+                value: Expression::Literal(Literal::String("x".to_string())),
+            },
+            Statement::Let {
+                name: "n".to_string(),
+                value: Expression::Literal(Literal::Integer(1)),
+            },
+        ]
+    );
+    assert!(parse(tokenize("const URL\n").unwrap()).is_err());
+}
+
+#[test]
+fn test_constants_are_never_assigned() {
+    for input in [
+        "const A = 1\nA = 2\n",
+        "const A = 1\nfor A in 0..2 {\n  echo A\n}\n",
+        "fn f() {\n  A = 2\n}\nconst A = 1\n",
+        "if true {\n  const A = 1\n  while true {\n    A = 2\n  }\n}\n",
+    ] {
+        assert!(
+            matches!(check_source(input), Err(SemaError::AssignToConst { name }) if name == "A"),
+            "{}",
+            input
+        );
+    }
+    // A declaration in a nested block, or a parameter, shadows it.
+    assert!(check_source("const A = 1\nif true {\n  let A = 2\n  A = 3\n}\n").is_ok());
+    assert!(check_source("const A = 1\nfn f(A) {\n  A = 2\n}\n").is_ok());
+}
+
+#[test]
+fn test_declarations_are_scoped_to_their_block() {
+    assert!(check_source("let a = 1\ntry {\n  let a = 2\n} catch e {\n  let a = 3\n}\n").is_ok());
+    for input in [
+        "let a = 1\nconst a = 2\n",
+        "if true {\n  let a = 1\n  let a = 2\n}\n",
+        "fn f() {\n  const a = 1\n  let a = 2\n}\n",
+    ] {
+        assert!(
+            matches!(check_source(input), Err(SemaError::DuplicateDeclaration { name }) if name == "a"),
+            "{}",
+            input
+        );
+    }
+    assert!(matches!(
+        check_source("a = 1\nif true {\n  let a = a + 1\n}\n"),
+        Err(SemaError::SelfReferentialDeclaration { name }) if name == "a"
+    ));
+}
+
+#[test]
+fn test_block_declarations_shadow() {
+    assert_values(
+        "\
+const LIMIT = 3
+x = 'outer'
+total = 0
+for i in 0..LIMIT {
+  let x = i * 10
+  total = total + x
+}
+if true {
+  let x = 'if'
+  seen = x
+}
+fn f(n) {
+  let x = n
+  if true {
+    let x = 'nested'
+  }
+  global last
+  last = x
+}
+f('arg')
+",
+        &[
+            ("x", "outer"),
+            ("total", "30"),
+            ("seen", "if"),
+            ("last", "arg"),
+        ],
+    );
+}
@@ -0,0 +1,223 @@
+mod common;
+
+use common::{assert_values, check_source, run};
+use shard::ast::{Pattern, Statement};
+use shard::codegen::generate;
+use shard::lexer::tokenize;
+use shard::parser::parse;
+use shard::sema::SemaError;
+
+const ENV: &str = "enum Env {\n  Dev, Staging\n  Prod\n}\n";
+
+fn variant(name: &str) -> Pattern {
+    Pattern::Variant {
+        enumeration: "Env".to_string(),
+        variant: name.to_string(),
+    }
+}
+
+#[test]
+fn test_parse_enum_declaration() {
+    let ast = parse(tokenize(ENV).unwrap()).unwrap();
+    assert_eq!(
+        ast.0[0],
+        Statement::Enum {
+            name: "Env".to_string(),
+            variants: vec!["Dev".to_string(), "Staging".to_string(), "Prod".to_string()],
+        }
+    );
+    assert!(parse(tokenize("enum Empty { }").unwrap()).is_err());
+    assert!(parse(tokenize("enum Twice { A, A }").unwrap()).is_err());
+}
+
+#[test]
+fn test_parse_variant_patterns() {
+    let source = format!(
+        "{}match env {{\n  Env.Dev | Env.Staging => echo test\n  Env.Prod => echo live\n}}\n",
+        ENV
+    );
+    let ast = parse(tokenize(&source).unwrap()).unwrap();
+    let Statement::Match { arms, .. } = &ast.0[1] else {
+        panic!("Expected match, got {:?}", ast.0[1]);
+    };
+    assert_eq!(arms[0].patterns, vec![variant("Dev"), variant("Staging")]);
+    assert_eq!(arms[1].patterns, vec![variant("Prod")]);
+}
+
+#[test]
+fn test_enum_declarations_and_variants() {
+    let env = "enum Env { Dev, Staging, Prod }\n";
+    let checked = |body: &str| check_source(&format!("{}{}", env, body));
+    assert!(
+        checked("e = Env.Dev\nf = Env('Prod')\nfn deploy(env: Env) {\n  echo env\n}\n").is_ok()
+    );
+    assert!(matches!(
+        checked("e = Env.Test\n"),
+        Err(SemaError::UnknownVariant { variant, variants, .. })
+            if variant == "Test" && variants == "Dev, Staging, Prod"
+    ));
+    assert!(matches!(
+        checked("e = Env('a', 'b')\n"),
+        Err(SemaError::WrongArity { name, found: 2, .. }) if name == "Env"
+    ));
+    assert!(matches!(
+        checked("enum Env { Test }\n"),
+        Err(SemaError::DuplicateEnum { name }) if name == "Env"
+    ));
+    assert!(matches!(
+        checked("struct Env { name: string }\n"),
+        Err(SemaError::DuplicateEnum { name }) if name == "Env"
+    ));
+    assert!(matches!(
+        checked("struct Target { env: Env }\nt = Target { env: 'prod' }\n"),
+        Err(SemaError::FieldType { expected, found, .. }) if expected == "Env" && found == "string"
+    ));
+    assert!(checked("struct Target { env: Env }\nt = Target { env: Env.Prod }\n").is_ok());
+}
+
+#[test]
+fn test_enum_match_is_exhaustive() {
+    let env = "enum Env { Dev, Staging, Prod }\nenum Os { Linux }\n";
+    let checked = |arms: &str| check_source(&format!("{}match e {{\n{}}}\n", env, arms));
+    assert!(checked("  Env.Dev | Env.Staging => echo test\n  Env.Prod => echo live\n").is_ok());
+    assert!(checked("  Env.Prod => echo live\n  _ => echo test\n").is_ok());
+    assert!(matches!(
+        checked("  Env.Dev => echo test\n"),
+        Err(SemaError::NonExhaustiveMatch { enumeration, missing })
+            if enumeration == "Env" && missing == "Env.Staging, Env.Prod"
+    ));
+    assert!(matches!(
+        checked("  Env.Dev => echo test\n  Env.Test => echo test\n  _ => echo other\n"),
+        Err(SemaError::UnknownVariant { variant, .. }) if variant == "Test"
+    ));
+    assert!(matches!(
+        checked("  Env.Dev => echo test\n  Os.Linux => echo linux\n  _ => echo other\n"),
+        Err(SemaError::EnumMatchPattern { pattern, .. }) if pattern == "Os.Linux"
+    ));
+    assert!(matches!(
+        checked("  Env.Dev => echo test\n  'prod' => echo live\n  _ => echo other\n"),
+        Err(SemaError::EnumMatchPattern { pattern, .. }) if pattern == "'prod'"
+    ));
+    assert!(matches!(
+        checked("  Mode.Fast => echo fast\n"),
+        Err(SemaError::UnknownType { name }) if name == "Mode"
+    ));
+}
+
+#[test]
+fn test_codegen_enum_values() {
+    let source = format!(
+        "{}env = Env.Prod\nchosen = Env(text)\nmatch env {{\n  Env.Dev | Env.Staging => echo test\n  Env.Prod => echo live\n}}\n",
+        ENV
+    );
+    let output = generate(&parse(tokenize(&source).unwrap()).unwrap()).unwrap();
+    assert!(output.contains("__shard_env='Prod'\n"));
+    assert!(output.contains(
+        "__shard_chosen=$(__shard_enum 'Env' \"$__shard_text\" 'Dev' 'Staging' 'Prod') || exit 1\n"
+    ));
+    assert!(output.contains("  'Dev' | 'Staging')\n"));
+    assert!(output.contains("  'Prod')\n"));
+}
+
+#[test]
+fn test_codegen_validates_enum_parameters() {
+    let source = format!(
+        "{}fn deploy(env: Env) {{\n  echo env\n}}\ndeploy(Env.Dev)\n",
+        ENV
+    );
+    let output = generate(&parse(tokenize(&source).unwrap()).unwrap()).unwrap();
+    assert!(output.contains(
+        "__shard_deploy_env=$(__shard_enum 'Env' \"$__shard_deploy_env\" 'Dev' 'Staging' 'Prod') || exit 1\n"
+    ));
+    assert!(output.contains("deploy 'Dev'"));
+}
+
+#[test]
+fn test_codegen_rejects_unknown_variant() {
+    let source = format!("{}env = Env.Test\n", ENV);
+    assert!(generate(&parse(tokenize(&source).unwrap()).unwrap()).is_err());
+}
+
+#[test]
+fn test_enums_match_and_convert() {
+    assert_values(
+        "\
+enum Env { Dev, Staging, Prod }
+fn label(env: Env) {
+  global seen
+  match env {
+    Env.Dev | Env.Staging => { seen = format('{}test,', seen) }
+    Env.Prod => { seen = format('{}live,', seen) }
+  }
+}
+seen = ''
+env = Env('Prod')
+label(env)
+label(Env.Staging)
+try {
+  bad = Env('prod')
+} catch err {
+  failed = err
+}
+",
+        &[("env", "Prod"), ("seen", "live,test,"), ("failed", "1")],
+    );
+}
+
+#[test]
+fn test_nested_enum_conversion_exits() {
+    assert_values(
+        "\
+enum Env { Dev, Prod }
+m = format('{}!', Env('Prod'))
+try {
+  bad = format('{}!', Env('prod'))
+} catch err {
+  failed = err
+}
+",
+        &[("m", "Prod!"), ("failed", "1")],
+    );
+    let output = run(
+        "\
+enum Env { Dev, Prod }
+text = 'Dev'
+while Env(text) == Env.Dev {
+  text = 'dev'
+}
+log.info('unreachable')
+",
+        &[],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Env: not one of Dev Prod: dev"),
+        "{}",
+        stderr
+    );
+    assert!(!stderr.contains("unreachable"));
+}
+
+#[test]
+fn test_invalid_enum_argument_exits() {
+    let output = run(
+        "\
+enum Env { Dev, Prod }
+fn deploy(env: Env) {
+  echo env
+}
+deploy('Test')
+log.info('unreachable')
+",
+        &[],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Env: not one of Dev Prod: Test"),
+        "{}",
+        stderr
+    );
+    assert!(!stderr.contains("unreachable"));
+}
@@ -1,3 +1,6 @@
+mod common;
+
+use common::assert_values;
 use shard::lexer::tokenize;
 use shard::parser::parse;
 
@@ -123,3 +126,106 @@ fn test_parse_complex_expression() {
     let ast = parse(tokens).unwrap();
     assert_eq!(ast.0.len(), 1);
 }
+
+#[test]
+fn test_truthiness() {
+    assert_values(
+        "\
+seen = ''
+flag = false
+if flag {
+  seen = 'wrong'
+}
+for i, v in [1, 0] {
+  if v {
+    seen = format('{}{}', seen, i)
+  }
+}
+n = 0
+if n {
+  seen = 'wrong'
+}
+f = 0.0
+if not f {
+  seen = format('{}f', seen)
+}
+s = ''
+if not s {
+  seen = format('{}s', seen)
+}
+nothing = null
+if not nothing {
+  seen = format('{}n', seen)
+}
+empty = []
+if not empty {
+  seen = format('{}e', seen)
+}
+full = ['x']
+if full {
+  seen = format('{}a', seen)
+}
+if not trim('  ') {
+  seen = format('{}t', seen)
+}
+",
+        &[("seen", "0fsneat")],
+    );
+}
+
+#[test]
+fn test_string_concatenation() {
+    assert_values(
+        "\
+joined = 'foo' + 'bar'
+name = 'prod'
+label = name + '-' + 1
+both = true and 'yes'
+if name + '!' == 'prod!' {
+  matched = 'yes'
+}
+",
+        &[
+            ("joined", "foobar"),
+            ("label", "prod-1"),
+            ("both", "true"),
+            ("matched", "yes"),
+        ],
+    );
+}
+
+#[test]
+fn test_comparisons_by_type() {
+    assert_values(
+        "\
+name = 'prod'
+n = 10
+r = ''
+if name == 'prod' {
+  r = format('{}a', r)
+}
+if name != 'dev' {
+  r = format('{}b', r)
+}
+if n == 10.0 {
+  r = format('{}c', r)
+}
+nothing = null
+if nothing == null {
+  r = format('{}d', r)
+}
+if n > 9 and not name == 'dev' {
+  r = format('{}e', r)
+}
+if 'abc' < 'abd' and name >= 'prod' and not 'b' <= 'a' {
+  r = format('{}f', r)
+}
+ten = '10'
+if not ten > '9' and ten < '9' {
+  r = format('{}g', r)
+}
+later = name > 'dev'
+",
+        &[("r", "abcdefg"), ("later", "true")],
+    );
+}
@@ -1,6 +1,11 @@
+mod common;
+
+use common::{assert_values, check_source};
+use shard::ast::{Expression, Literal, Param, Statement};
 use shard::codegen::generate;
 use shard::lexer::tokenize;
 use shard::parser::parse;
+use shard::sema::SemaError;
 
 #[test]
 fn test_parse_function_definition() {
@@ -67,3 +72,353 @@ fn test_codegen_function_with_return() {
     let output = generate(&ast).unwrap();
     assert!(output.contains("return"));
 }
+
+#[test]
+fn test_parse_lambda() {
+    let tokens = tokenize("doubled = map(nums, fn(x) { x * 2 })").unwrap();
+    let ast = parse(tokens).unwrap();
+    let Statement::Assignment { value, .. } = &ast.0[0] else {
+        panic!("Expected assignment, got {:?}", ast.0[0]);
+    };
+    let Expression::FunctionCall { args, .. } = value else {
+        panic!("Expected call, got {:?}", value);
+    };
+    let Expression::Lambda {
+        params,
+        body,
+        return_value,
+    } = &args[1]
+    else {
+        panic!("Expected lambda, got {:?}", args[1]);
+    };
+    assert_eq!(params, &vec!["x".to_string()]);
+    assert!(body.is_empty());
+    assert!(return_value.is_some());
+}
+
+#[test]
+fn test_codegen_lambda_is_hoisted() {
+    let tokens = tokenize("each(items, fn(item) {\n  echo item\n})").unwrap();
+    let ast = parse(tokens).unwrap();
+    let output = generate(&ast).unwrap();
+    assert!(output.contains("__shard_lambda_1() {"));
+    assert!(output.contains("__shard_each __shard_lambda_1"));
+}
+
+#[test]
+fn test_higher_order_builtins() {
+    assert_values(
+        "\
+nums = [3, 1, 2]
+factor = 10
+x = 'outer'
+doubled = join(map(nums, fn(x) { x * factor }), ',')
+big = join(filter(nums, fn(n) { n > 1 }), ',')
+sum = reduce(nums, fn(acc, n) { acc + n }, 0)
+total = 0
+each(nums, fn(n) {
+  total = total + n
+})
+words = ['pear', 'fig', 'banana']
+by_name = join(sort_by(words, fn(w) { w }), ',')
+by_number = join(sort_by([10, 9, 100, -1], fn(n) { n }), ',')
+",
+        &[
+            ("doubled", "30,10,20"),
+            ("big", "3,2"),
+            ("sum", "6"),
+            ("total", "6"),
+            ("by_name", "banana,fig,pear"),
+            ("by_number", "-1,9,10,100"),
+            ("x", "outer"),
+        ],
+    );
+}
+
+#[test]
+fn test_parse_default_and_rest_parameters() {
+    let tokens = tokenize("fn deploy(env, region = 'eu', ...extra) {\n  echo env\n}").unwrap();
+    let ast = parse(tokens).unwrap();
+    let Statement::FunctionDef { params, .. } = &ast.0[0] else {
+        panic!("Expected function, got {:?}", ast.0[0]);
+    };
+    assert_eq!(
+        params,
+        &vec![
+            Param::new("env"),
+            Param {
+                default: Some(Expression::Literal(Literal::String("eu".to_string()))),
+                ..Param::new("region")
+            },
+            Param {
+                rest: true,
+                ..Param::new("extra")
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_parse_invalid_parameters() {
+    for input in [
+        "fn f(...rest, a) { echo a }",
+        "fn f(a = 1, b) { echo a }",
+        "fn f(...rest = 1) { echo a }",
+        "fn f(a, a) { echo a }",
+        "fn f(1) { echo a }",
+    ] {
+        assert!(parse(tokenize(input).unwrap()).is_err(), "{}", input);
+    }
+}
+
+#[test]
+fn test_parse_named_arguments() {
+    let tokens = tokenize("x = deploy('prod', region: 'us')").unwrap();
+    let ast = parse(tokens).unwrap();
+    let Statement::Assignment {
+        value: Expression::FunctionCall { args, .. },
+        ..
+    } = &ast.0[0]
+    else {
+        panic!("Expected call, got {:?}", ast.0[0]);
+    };
+    assert_eq!(
+        args[1],
+        Expression::NamedArgument {
+            name: "region".to_string(),
+            value: Box::new(Expression::Literal(Literal::String("us".to_string()))),
+        }
+    );
+    assert!(parse(tokenize("x = deploy(region: 'us', 'prod')").unwrap()).is_err());
+}
+
+#[test]
+fn test_codegen_default_parameters() {
+    let tokens = tokenize("fn deploy(env, region = 'eu') {\n  echo env\n}\ndeploy('prod', region: 'us')\ndeploy(region: 'ap', env: 'dev')\n").unwrap();
+    let ast = parse(tokens).unwrap();
+    let output = generate(&ast).unwrap();
+    assert!(output.contains("__shard_deploy_region=${2:-\"eu\"}"));
+    assert!(output.contains("deploy 'prod' 'us' >"));
+    assert!(output.contains("deploy 'dev' 'ap' >"));
+    assert!(!output.contains("$(deploy"));
+}
+
+#[test]
+fn test_default_named_and_rest_parameters() {
+    assert_values(
+        r#"
+got = ''
+fn deploy(env, region = 'e}u "x"', ...extra) {
+  global got
+  got = format('{}{}|{}|{}|{};', got, env, region, len(extra), join(extra, ','))
+}
+deploy('prod')
+deploy('prod', region: 'us')
+deploy('prod', 'ap', 'a b', 'c')
+deploy('dev', '', 'x')
+fn pair(a, b = 2, c = 3) {
+  global got
+  got = format('{}{}{}{};', got, a, b, c)
+}
+pair(1, c: 9)
+pair(c: 'z', a: 'y')
+"#,
+        &[(
+            "got",
+            r#"prod|e}u "x"|0|;prod|us|0|;prod|ap|2|a b,c;dev|e}u "x"|1|x;129;y2z;"#,
+        )],
+    );
+}
+
+#[test]
+fn test_lambdas_held_in_variables() {
+    assert_values(
+        "\
+double = fn(x) { x * 2 }
+doubled = join(map([1, 2], double), ',')
+f = fn(x) { x + 1 }
+y = f(1)
+add = fn(a, b) { a + b }
+sum = reduce([1, 2, 3], add, 0)
+",
+        &[("doubled", "2,4"), ("y", "2"), ("sum", "6")],
+    );
+}
+
+#[test]
+fn test_higher_order_arguments_are_checked() {
+    assert!(check_source("fn twice(x) { return x * 2 }\nys = map([1], twice)\n").is_ok());
+    assert!(matches!(
+        check_source("ys = map([1], 5)\n"),
+        Err(SemaError::NotCallable { found, .. }) if found == "int"
+    ));
+    assert!(matches!(
+        check_source("n = 'x'\nys = filter([1], n)\n"),
+        Err(SemaError::NotCallable { found, .. }) if found == "string"
+    ));
+    assert!(matches!(
+        check_source("ys = map([1], fn(a, b) { a })\n"),
+        Err(SemaError::CallableArity { expected, found: 1, .. }) if expected == "2"
+    ));
+    assert!(matches!(
+        check_source("add = fn(a) { a }\ns = reduce([1], add, 0)\n"),
+        Err(SemaError::CallableArity { found: 2, .. })
+    ));
+    assert!(matches!(
+        check_source("fn pair(a, b) { return a }\nys = map([1], pair)\n"),
+        Err(SemaError::CallableArity { found: 1, .. })
+    ));
+    assert!(matches!(
+        check_source("f = fn(x) { x }\ny = f(1, 2)\n"),
+        Err(SemaError::WrongArity { found: 2, signature, .. }) if signature == "f(x)"
+    ));
+}
+
+#[test]
+fn test_function_calls_are_checked() {
+    let define = "\
+fn deploy(env, region = 'eu', dry = false) {
+  echo env
+}
+";
+    let check_call = |call: &str| check_source(&format!("{}{}\n", define, call));
+    assert!(check_call("deploy('prod')").is_ok());
+    assert!(check_call("deploy('prod', dry: true)").is_ok());
+    assert!(check_call("deploy(region: 'us', env: 'prod')").is_ok());
+    match check_call("deploy(region: 'us')") {
+        Err(SemaError::WrongArity {
+            expected,
+            signature,
+            ..
+        }) => {
+            assert_eq!(expected, "1 to 3");
+            assert_eq!(signature, "deploy(env, region?, dry?)");
+        }
+        other => panic!("Expected arity error, got {:?}", other),
+    }
+    assert!(matches!(
+        check_call("deploy('a', 'b', true, 'c')"),
+        Err(SemaError::WrongArity { found: 4, .. })
+    ));
+    assert!(matches!(
+        check_call("deploy('prod', zone: 'us')"),
+        Err(SemaError::UnknownArgument { name, .. }) if name == "zone"
+    ));
+    assert!(matches!(
+        check_call("deploy('prod', env: 'dev')"),
+        Err(SemaError::DuplicateArgument { name, .. }) if name == "env"
+    ));
+}
+
+#[test]
+fn test_untyped_parameters_reject_floats_in_arithmetic() {
+    let define = "\
+fn dbl(x, label = 'n') {
+  out = x * 2
+  echo label
+}
+";
+    let check_call = |call: &str| check_source(&format!("{}{}\n", define, call));
+    assert!(check_call("dbl(1)").is_ok());
+    assert!(check_call("dbl(2, 1.5)").is_ok());
+    for call in ["dbl(1.5)", "f = 0.5\ndbl(f)", "dbl(x: 2.5)"] {
+        assert!(matches!(
+            check_call(call),
+            Err(SemaError::UntypedFloatArgument { name, .. }) if name == "x"
+        ));
+    }
+    assert!(check_source("fn dbl(x: float) {\n  out = x * 2\n}\ndbl(1.5)\n").is_ok());
+    assert!(check_source("fn greet(x) {\n  out = 'hi ' + x\n}\ngreet(1.5)\n").is_ok());
+}
+
+#[test]
+fn test_rest_parameters_take_any_number_of_arguments() {
+    let define = "\
+fn run(cmd, ...args) {
+  echo cmd
+}
+";
+    assert!(check_source(&format!("{}run('ls', '-l', '-a')\n", define)).is_ok());
+    assert!(matches!(
+        check_source(&format!("{}x = run()\n", define)),
+        Err(SemaError::WrongArity { expected, .. }) if expected == "at least 1"
+    ));
+}
+
+#[test]
+fn test_parse_global() {
+    let tokens = tokenize("fn f() {\n  global a, b\n  a = 1\n}").unwrap();
+    let ast = parse(tokens).unwrap();
+    let Statement::FunctionDef { body, .. } = &ast.0[0] else {
+        panic!("Expected function, got {:?}", ast.0[0]);
+    };
+    assert_eq!(
+        body[0],
+        Statement::Global {
+            names: vec!["a".to_string(), "b".to_string()]
+        }
+    );
+}
+
+#[test]
+fn test_codegen_function_variables_are_its_own() {
+    let tokens = tokenize("fn f(n) {\n  global total\n  total = n\n  last = n\n}").unwrap();
+    let ast = parse(tokens).unwrap();
+    let output = generate(&ast).unwrap();
+    assert!(output.contains("__shard_total=$__shard_f_n"));
+    assert!(output.contains("__shard_f_last=$__shard_f_n"));
+    assert!(!output.contains("__shard_enter"));
+}
+
+#[test]
+fn test_codegen_recursive_function_saves_variables() {
+    let tokens = tokenize("fn down(n) {\n  if n > 0 {\n    down(n - 1)\n  }\n}").unwrap();
+    let ast = parse(tokens).unwrap();
+    let output = generate(&ast).unwrap();
+    assert!(!output.contains("local"));
+    assert!(output.contains("__shard_down_run() {"));
+    assert!(output.contains("  __shard_enter __shard_down_n __shard_down_stdout_tmp"));
+    assert!(output.contains("  __shard_down_run \"$@\"\n  set -- \"$?\"\n"));
+}
+
+#[test]
+fn test_function_scoping() {
+    assert_values(
+        "\
+x = 'global'
+count = ''
+result = 1
+fn fact(n) {
+  global result
+  if n > 1 {
+    result = result * n
+    fact(n - 1)
+  }
+}
+fn walk(depth) {
+  global count
+  x = depth
+  if depth < 3 {
+    walk(depth + 1)
+  }
+  count = format('{}{}', count, x)
+}
+fact(5)
+walk(0)
+",
+        &[("result", "120"), ("count", "3210"), ("x", "global")],
+    );
+}
+
+#[test]
+fn test_global_only_in_functions() {
+    assert!(check_source("fn f() {\n  global total\n  total = 1\n}\n").is_ok());
+    assert!(matches!(
+        check_source("global total\n"),
+        Err(SemaError::GlobalOutsideFunction { name }) if name == "total"
+    ));
+    assert!(matches!(
+        check_source("fn f(total) {\n  global total\n}\n"),
+        Err(SemaError::GlobalParameter { name, .. }) if name == "total"
+    ));
+}
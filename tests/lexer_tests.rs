@@ -14,6 +14,13 @@ fn test_tokenize_integer() {
     assert_eq!(tokens[0].1.value, Some("42".to_string()));
 }
 
+#[test]
+fn test_tokenize_float() {
+    let tokens = tokenize("3.25").unwrap();
+    assert_eq!(tokens[0].1.token_type, TokenType::Float);
+    assert_eq!(tokens[0].1.value, Some("3.25".to_string()));
+}
+
 #[test]
 fn test_tokenize_boolean_true() {
     let tokens = tokenize("true").unwrap();
@@ -104,7 +111,7 @@ fn test_tokenize_empty_input() {
 fn test_tokenize_whitespace_only() {
     let tokens = tokenize("   \n\t  ").unwrap();
     // With newlines as tokens, we get: Newline, EOF
-    assert!(tokens.len() >= 1);
+    assert!(!tokens.is_empty());
     assert_eq!(tokens[tokens.len() - 1].1.token_type, TokenType::EOF);
 }
 
@@ -175,3 +182,51 @@ fn test_tokenize_newline_separator() {
         .count();
     assert_eq!(newline_count, 1);
 }
+
+#[test]
+fn test_tokenize_ranges() {
+    let types: Vec<TokenType> = tokenize("0..n a..=10")
+        .unwrap()
+        .into_iter()
+        .map(|t| t.1.token_type)
+        .collect();
+    assert_eq!(
+        types,
+        vec![
+            TokenType::Integer,
+            TokenType::DotDot,
+            TokenType::Identifier,
+            TokenType::Identifier,
+            TokenType::DotDotEq,
+            TokenType::Integer,
+            TokenType::EOF,
+        ]
+    );
+}
+
+#[test]
+fn test_tokenize_fields() {
+    let types: Vec<TokenType> = tokenize("s.owner.name")
+        .unwrap()
+        .into_iter()
+        .map(|t| t.1.token_type)
+        .collect();
+    assert_eq!(
+        types,
+        vec![
+            TokenType::Identifier,
+            TokenType::Dot,
+            TokenType::Identifier,
+            TokenType::Dot,
+            TokenType::Identifier,
+            TokenType::EOF,
+        ]
+    );
+}
+
+#[test]
+fn test_tokenize_identifier_after_non_ascii_text() {
+    let tokens = tokenize("echo '😀' count").unwrap();
+    assert_eq!(tokens[2].1.value.as_deref(), Some("count"));
+    assert_eq!(tokens[2].1.span, (9, 14));
+}
@@ -0,0 +1,369 @@
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+const URI: &str = "file:///tmp/main.shard";
+
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: i64,
+}
+
+impl Client {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_shard"))
+            .arg("lsp")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Failed to start shard lsp");
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut client = Client {
+            child,
+            stdin,
+            stdout,
+            next_id: 1,
+        };
+        client.request("initialize", json!({ "capabilities": {} }));
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    fn send(&mut self, message: Value) {
+        self.send_raw(&message.to_string());
+    }
+
+    fn send_raw(&mut self, body: &str) {
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn read(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            self.stdout.read_line(&mut header).unwrap();
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length: ") {
+                length = value.parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        self.stdout.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        loop {
+            let message = self.read();
+            if message["id"] == json!(id) {
+                return message;
+            }
+        }
+    }
+
+    /// Open a document and return the diagnostics published for it.
+    fn open(&mut self, text: &str) -> Vec<Value> {
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": { "uri": URI, "languageId": "shard", "version": 1, "text": text }
+            }),
+        );
+        let message = self.read();
+        assert_eq!(message["method"], "textDocument/publishDiagnostics");
+        message["params"]["diagnostics"].as_array().unwrap().clone()
+    }
+
+    fn at(&mut self, method: &str, line: u32, character: u32) -> Value {
+        self.request(
+            method,
+            json!({
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character },
+            }),
+        )
+    }
+
+    fn stop(mut self) {
+        let response = self.request("shutdown", Value::Null);
+        assert_eq!(response["result"], Value::Null);
+        self.notify("exit", Value::Null);
+        let status = self.child.wait().unwrap();
+        assert!(status.success());
+    }
+}
+
+#[test]
+fn test_lsp_initialize_capabilities() {
+    let mut client = Client::start();
+    let response = client.request("initialize", json!({ "capabilities": {} }));
+    let capabilities = &response["result"]["capabilities"];
+    assert_eq!(capabilities["definitionProvider"], true);
+    assert_eq!(capabilities["hoverProvider"], true);
+    assert_eq!(capabilities["documentSymbolProvider"], true);
+    assert_eq!(capabilities["documentFormattingProvider"], true);
+    client.stop();
+}
+
+#[test]
+fn test_lsp_diagnostics_clean_document() {
+    let mut client = Client::start();
+    let diagnostics = client.open("x = 10\necho x\n");
+    assert!(diagnostics.is_empty());
+    client.stop();
+}
+
+#[test]
+fn test_lsp_diagnostics_parse_error() {
+    let mut client = Client::start();
+    let diagnostics = client.open("x = 1\nif x == 1\necho x\n");
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("Expected '{'"));
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], 2);
+    client.stop();
+}
+
+#[test]
+fn test_lsp_diagnostics_semantic_errors() {
+    let mut client = Client::start();
+    let cases = [
+        ("const C = 1\necho C\nC = 2\n", 2, "constant 'C'"),
+        (
+            "fn add(a, b) {\n  echo a\n}\nif true {\n  add(1)\n}\n",
+            4,
+            "add() takes 2",
+        ),
+        ("enum Env { Dev }\ne = Env.Test\n", 1, "Test"),
+        (
+            "struct S { host: string }\ns = S { host: 'a', port: 1 }\n",
+            1,
+            "port",
+        ),
+    ];
+    for (text, line, message) in cases {
+        let diagnostics = client.open(text);
+        assert_eq!(diagnostics.len(), 1, "{}", text);
+        assert!(
+            diagnostics[0]["message"]
+                .as_str()
+                .unwrap()
+                .contains(message),
+            "{}",
+            diagnostics[0]
+        );
+        assert_eq!(diagnostics[0]["range"]["start"]["line"], line, "{}", text);
+    }
+    // The range covers the statement's first line.
+    let diagnostics = client.open("const C = 0\nif true {\n  C = 2 # again\n}\n");
+    assert_eq!(diagnostics[0]["range"]["start"]["character"], 2);
+    assert_eq!(diagnostics[0]["range"]["end"]["character"], 7);
+    client.stop();
+}
+
+#[test]
+fn test_lsp_diagnostics_lexer_error() {
+    let mut client = Client::start();
+    let diagnostics = client.open("x = 1\ny = 'unterminated\n");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+    client.stop();
+}
+
+#[test]
+fn test_lsp_diagnostics_cleared_on_change() {
+    let mut client = Client::start();
+    assert_eq!(client.open("if x {\n").len(), 1);
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{ "text": "if x {\n}\n" }],
+        }),
+    );
+    let message = client.read();
+    assert!(message["params"]["diagnostics"]
+        .as_array()
+        .unwrap()
+        .is_empty());
+    client.stop();
+}
+
+#[test]
+fn test_lsp_goto_definition_function() {
+    let mut client = Client::start();
+    client.open("fn greet(name) {\n    echo name\n}\ngreet world\n");
+    let response = client.at("textDocument/definition", 3, 2);
+    let range = &response["result"]["range"];
+    assert_eq!(range["start"], json!({ "line": 0, "character": 3 }));
+    assert_eq!(range["end"], json!({ "line": 0, "character": 8 }));
+    client.stop();
+}
+
+#[test]
+fn test_lsp_goto_definition_prefers_parameter() {
+    let mut client = Client::start();
+    client.open("name = 'global'\nfn greet(name) {\n    echo name\n}\n");
+    let response = client.at("textDocument/definition", 2, 10);
+    let range = &response["result"]["range"];
+    assert_eq!(range["start"], json!({ "line": 1, "character": 9 }));
+    client.stop();
+}
+
+#[test]
+fn test_lsp_goto_definition_variable() {
+    let mut client = Client::start();
+    client.open("count = 1\ncount = count + 1\necho count\n");
+    let response = client.at("textDocument/definition", 2, 6);
+    let range = &response["result"]["range"];
+    assert_eq!(range["start"], json!({ "line": 0, "character": 0 }));
+    client.stop();
+}
+
+#[test]
+fn test_lsp_hover_variable_type() {
+    let mut client = Client::start();
+    client.open("total = 1 + 2\nready = total > 2\necho total ready\n");
+    let hover = client.at("textDocument/hover", 2, 6);
+    let value = hover["result"]["contents"]["value"].as_str().unwrap();
+    assert!(value.contains("total: int"), "{}", value);
+    let hover = client.at("textDocument/hover", 2, 12);
+    let value = hover["result"]["contents"]["value"].as_str().unwrap();
+    assert!(value.contains("ready: bool"), "{}", value);
+    client.stop();
+}
+
+#[test]
+fn test_lsp_hover_function_signature() {
+    let mut client = Client::start();
+    client.open("fn add(a, b) {\n    return a + b\n}\nadd 1 2\n");
+    let hover = client.at("textDocument/hover", 3, 1);
+    let value = hover["result"]["contents"]["value"].as_str().unwrap();
+    assert!(value.contains("fn add(a, b)"), "{}", value);
+    client.stop();
+}
+
+#[test]
+fn test_lsp_document_symbols() {
+    let mut client = Client::start();
+    client.open("x = 1\nfn build() {\n    echo x\n}\nfn deploy(env) {\n    echo env\n}\n");
+    let response = client.request(
+        "textDocument/documentSymbol",
+        json!({ "textDocument": { "uri": URI } }),
+    );
+    let symbols = response["result"].as_array().unwrap();
+    let names: Vec<&str> = symbols
+        .iter()
+        .map(|s| s["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["build", "deploy"]);
+    assert_eq!(symbols[1]["range"]["start"]["line"], 4);
+    assert_eq!(symbols[1]["range"]["end"]["line"], 6);
+    client.stop();
+}
+
+#[test]
+fn test_lsp_completion_keywords_and_scope() {
+    let mut client = Client::start();
+    client.open("version = 1\nfn deploy(target) {\n    echo \n}\nfn other(inner) {\n}\n");
+    let response = client.at("textDocument/completion", 2, 9);
+    let labels: Vec<&str> = response["result"]["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap())
+        .collect();
+    assert!(labels.contains(&"while"));
+    assert!(labels.contains(&"version"));
+    assert!(labels.contains(&"deploy"));
+    assert!(labels.contains(&"target"));
+    assert!(!labels.contains(&"inner"));
+    assert!(labels.contains(&"pad_left"));
+    client.stop();
+}
+
+#[test]
+fn test_lsp_formatting() {
+    let mut client = Client::start();
+    client.open("if x == 1 {\necho one   \n  } else {\n      echo other\n}\n\n\n");
+    let response = client.request(
+        "textDocument/formatting",
+        json!({
+            "textDocument": { "uri": URI },
+            "options": { "tabSize": 2, "insertSpaces": true },
+        }),
+    );
+    let edits = response["result"].as_array().unwrap();
+    assert_eq!(edits.len(), 1);
+    assert_eq!(
+        edits[0]["newText"],
+        "if x == 1 {\n  echo one\n} else {\n  echo other\n}\n"
+    );
+    client.stop();
+}
+
+#[test]
+fn test_lsp_formatting_already_formatted() {
+    let mut client = Client::start();
+    client.open("while x < 10 {\n    x = x + 1\n}\n");
+    let response = client.request(
+        "textDocument/formatting",
+        json!({ "textDocument": { "uri": URI }, "options": { "tabSize": 4, "insertSpaces": true } }),
+    );
+    assert!(response["result"].as_array().unwrap().is_empty());
+    client.stop();
+}
+
+#[test]
+fn test_lsp_unknown_method() {
+    let mut client = Client::start();
+    let response = client.request("workspace/unknown", json!({}));
+    assert_eq!(response["error"]["code"], -32601);
+    client.stop();
+}
+
+#[test]
+fn test_lsp_positions_count_utf16_units() {
+    let mut client = Client::start();
+    // The emoji is one character but two UTF-16 code units.
+    client.open("fn greet(a = '😀', count = 1) {\n    echo count\n}\n");
+    let response = client.at("textDocument/definition", 1, 10);
+    let range = &response["result"]["range"];
+    assert_eq!(range["start"], json!({ "line": 0, "character": 19 }));
+    assert_eq!(range["end"], json!({ "line": 0, "character": 24 }));
+
+    let diagnostics = client.open("msg = '😀' )\n");
+    assert_eq!(
+        diagnostics[0]["range"]["start"],
+        json!({ "line": 0, "character": 11 })
+    );
+    client.stop();
+}
+
+#[test]
+fn test_lsp_malformed_message_keeps_serving() {
+    let mut client = Client::start();
+    client.send_raw("{\"jsonrpc\": \"2.0\", \"id\": ");
+    let response = client.read();
+    assert_eq!(response["error"]["code"], -32700);
+    assert_eq!(response["id"], Value::Null);
+    let response = client.request("workspace/unknown", json!({}));
+    assert_eq!(response["error"]["code"], -32601);
+    client.stop();
+}
@@ -0,0 +1,211 @@
+use shard::ast::{BinaryOperator, Expression, Literal, Statement};
+use shard::optimizer::fold::fold_expression;
+use shard::{generate, optimize, parse, parse_with_locations, tokenize, OptLevel, Program};
+
+fn optimized(input: &str, level: OptLevel) -> Program {
+    optimize(parse(tokenize(input).unwrap()).unwrap(), level)
+}
+
+fn assigned(program: &Program, index: usize) -> &Expression {
+    match &program.0[index] {
+        Statement::Assignment { value, .. } => value,
+        other => panic!("Expected assignment, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_fold_arithmetic() {
+    let program = optimized("x = 1 + 2 * 3", OptLevel::Basic);
+    assert_eq!(
+        assigned(&program, 0),
+        &Expression::Literal(Literal::Integer(7))
+    );
+    assert!(generate(&program).unwrap().contains("__shard_x=7\n"));
+}
+
+#[test]
+fn test_fold_keeps_division_by_zero() {
+    let program = optimized("x = 1 / 0", OptLevel::Basic);
+    assert!(matches!(
+        assigned(&program, 0),
+        Expression::BinaryOp {
+            op: BinaryOperator::Divide,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_fold_string_concatenation() {
+    let program = optimized("x = 'foo' + 'bar'", OptLevel::Basic);
+    assert_eq!(
+        assigned(&program, 0),
+        &Expression::Literal(Literal::String("foobar".to_string()))
+    );
+}
+
+#[test]
+fn test_fold_boolean_logic() {
+    let program = optimized(
+        "a = true and false\nb = not false\nc = 2 > 1",
+        OptLevel::Basic,
+    );
+    assert_eq!(
+        assigned(&program, 0),
+        &Expression::Literal(Literal::Boolean(false))
+    );
+    assert_eq!(
+        assigned(&program, 1),
+        &Expression::Literal(Literal::Boolean(true))
+    );
+    assert_eq!(
+        assigned(&program, 2),
+        &Expression::Literal(Literal::Boolean(true))
+    );
+}
+
+#[test]
+fn test_fold_short_circuit_with_unknown_operand() {
+    let expr = |op, known| Expression::BinaryOp {
+        op,
+        left: Box::new(Expression::Literal(Literal::Boolean(known))),
+        right: Box::new(Expression::Identifier("ready".to_string())),
+    };
+    assert_eq!(
+        fold_expression(expr(BinaryOperator::And, false)),
+        Expression::Literal(Literal::Boolean(false))
+    );
+    assert_eq!(
+        fold_expression(expr(BinaryOperator::Or, true)),
+        Expression::Literal(Literal::Boolean(true))
+    );
+    // As a value, `true and ready` is a boolean, not `ready`.
+    let kept = expr(BinaryOperator::And, true);
+    assert_eq!(fold_expression(kept.clone()), kept);
+
+    let program = optimized("if true and ready {\n  echo go\n}", OptLevel::Basic);
+    let Statement::If { condition, .. } = &program.0[0] else {
+        panic!("Expected if, got {:?}", program.0[0]);
+    };
+    assert_eq!(condition, &Expression::Identifier("ready".to_string()));
+}
+
+#[test]
+fn test_remove_constant_if() {
+    let program = optimized(
+        "if true {\n  echo 'yes'\n} else {\n  echo 'no'\n}",
+        OptLevel::Basic,
+    );
+    assert_eq!(program.0.len(), 1);
+    let shell = generate(&program).unwrap();
+    assert!(shell.contains("'yes'"));
+    assert!(!shell.contains("'no'"));
+    assert!(!shell.contains("if "));
+}
+
+#[test]
+fn test_remove_folded_condition() {
+    let program = optimized(
+        "if 1 > 2 {\n  echo 'never'\n}\necho 'after'",
+        OptLevel::Basic,
+    );
+    assert_eq!(program.0.len(), 1);
+}
+
+#[test]
+fn test_remove_while_false() {
+    let program = optimized("while false {\n  echo 'never'\n}", OptLevel::Basic);
+    assert!(program.0.is_empty());
+}
+
+#[test]
+fn test_remove_code_after_return() {
+    let program = optimized(
+        "fn f() {\n  return 1\n  echo 'unreachable'\n}",
+        OptLevel::Basic,
+    );
+    if let Statement::FunctionDef { body, .. } = &program.0[0] {
+        assert_eq!(body.len(), 1);
+        assert!(matches!(body[0], Statement::Return { .. }));
+    } else {
+        panic!("Expected function definition");
+    }
+}
+
+#[test]
+fn test_remove_code_after_break() {
+    let program = optimized("for x in [1, 2] {\n  break\n  echo x\n}", OptLevel::Basic);
+    if let Statement::For { body, .. } = &program.0[0] {
+        assert_eq!(body, &vec![Statement::Break]);
+    } else {
+        panic!("Expected for loop");
+    }
+}
+
+#[test]
+fn test_remove_unused_assignments() {
+    let input = "a = 1\nb = a + 1\nc = 3\necho c\nd = greet()";
+    let program = optimized(input, OptLevel::Full);
+    let names: Vec<&str> = program
+        .0
+        .iter()
+        .filter_map(|s| match s {
+            Statement::Assignment { name, .. } => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+    // `b` is never read, which leaves `a` unused; `d` keeps its call.
+    assert_eq!(names, vec!["c", "d"]);
+
+    let basic = optimized(input, OptLevel::Basic);
+    assert_eq!(basic.0.len(), 5);
+}
+
+#[test]
+fn test_opt_level_none() {
+    let program = optimized("x = 1 + 2\nif true {\n  echo x\n}", OptLevel::None);
+    assert!(matches!(assigned(&program, 0), Expression::BinaryOp { .. }));
+    assert!(matches!(program.0[1], Statement::If { .. }));
+}
+
+#[test]
+fn test_removed_statements_drop_their_locations() {
+    let program = parse_with_locations(tokenize("x = 1\nif false {\n  echo x\n}\n").unwrap());
+    let program = optimize(program.unwrap(), OptLevel::Basic);
+    assert_eq!(program.0.len(), 2);
+    assert!(matches!(program.0[0], Statement::Location { line: 1, .. }));
+}
+
+#[test]
+fn test_constants_are_folded_into_uses() {
+    let program = optimized(
+        "const N = 2 * 3\nx = N + 1\nfn f() {\n  echo N\n}\n",
+        OptLevel::Basic,
+    );
+    assert_eq!(
+        assigned(&program, 1),
+        &Expression::Literal(Literal::Integer(7))
+    );
+    let output = generate(&program).unwrap();
+    assert!(output.contains("readonly __shard_N=6\n"));
+    assert!(output.contains("echo 6"));
+
+    // A parameter of the same name is another variable.
+    let program = optimized("const N = 1\nfn f(N) {\n  x = N\n}\n", OptLevel::Basic);
+    let Statement::FunctionDef { body, .. } = &program.0[1] else {
+        panic!("Expected function, got {:?}", program.0[1]);
+    };
+    assert_eq!(
+        body[0],
+        Statement::Assignment {
+            name: "x".to_string(),
+            value: Expression::Identifier("N".to_string()),
+        }
+    );
+}
+
+#[test]
+fn test_taken_branch_keeps_its_declarations_scoped() {
+    let program = optimized("x = 1\nif true {\n  let x = 2\n}\n", OptLevel::Basic);
+    assert!(matches!(program.0[1], Statement::If { .. }));
+}
@@ -0,0 +1,262 @@
+use shard::codegen::ir::{Arith, ArithOp, Command, Segment, Test, Word};
+use shard::codegen::Printer;
+use shard::{
+    generate, generate_with_options, parse, parse_with_locations, tokenize, CodegenOptions,
+};
+
+fn print(commands: &[Command]) -> String {
+    Printer::new().print(commands)
+}
+
+fn echo(word: Word) -> Command {
+    Command::simple(vec![Word::bare("echo"), word])
+}
+
+fn test(word: &str) -> Command {
+    Command::Test {
+        test: Test::Word(Word::param(word)),
+        double: false,
+    }
+}
+
+#[test]
+fn test_literal_with_single_quote() {
+    assert_eq!(print(&[echo(Word::literal("it's"))]), "echo 'it'\\''s'\n");
+}
+
+#[test]
+fn test_positional_parameters_and_defaults() {
+    assert_eq!(print(&[echo(Word::param("10"))]), "echo \"${10}\"\n");
+    let default = Word::new(vec![Segment::Default(
+        "2".to_string(),
+        Word::literal("a} \"b\""),
+    )]);
+    assert_eq!(
+        print(&[echo(default.clone())]),
+        "echo \"${2:-\"a} \\\"b\\\"\"}\"\n"
+    );
+    assert_eq!(
+        print(&[Command::assign("x", default)]),
+        "x=${2:-\"a} \\\"b\\\"\"}\n"
+    );
+}
+
+#[test]
+fn test_expansions_are_double_quoted() {
+    let word = Word::new(vec![
+        Segment::Literal("cost: $".to_string()),
+        Segment::Param("price".to_string()),
+    ]);
+    assert_eq!(print(&[echo(word)]), "echo \"cost: \\$$price\"\n");
+}
+
+#[test]
+fn test_param_followed_by_name_characters() {
+    let word = Word::new(vec![
+        Segment::Param("name".to_string()),
+        Segment::Bare("_suffix".to_string()),
+    ]);
+    assert_eq!(print(&[echo(word)]), "echo \"${name}_suffix\"\n");
+}
+
+#[test]
+fn test_split_and_assignment_words_are_unquoted() {
+    let for_loop = Command::For {
+        variable: "x".to_string(),
+        words: vec![Word::param("list").split()],
+        body: vec![echo(Word::param("x"))],
+    };
+    assert_eq!(
+        print(&[for_loop]),
+        "for x in $list; do\n  echo \"$x\"\ndone\n"
+    );
+    assert_eq!(print(&[Command::assign("a", Word::param("b"))]), "a=$b\n");
+}
+
+#[test]
+fn test_arithmetic_precedence() {
+    let sum = Arith::Binary(
+        Box::new(Arith::Number(1)),
+        ArithOp::Add,
+        Box::new(Arith::Number(2)),
+    );
+    let product = Arith::Binary(
+        Box::new(sum.clone()),
+        ArithOp::Multiply,
+        Box::new(Arith::Number(3)),
+    );
+    let difference = Arith::Binary(Box::new(Arith::Number(5)), ArithOp::Subtract, Box::new(sum));
+    assert_eq!(
+        print(&[Command::assign("x", Word::arithmetic(product))]),
+        "x=$(((1 + 2) * 3))\n"
+    );
+    assert_eq!(
+        print(&[Command::assign("x", Word::arithmetic(difference))]),
+        "x=$((5 - (1 + 2)))\n"
+    );
+}
+
+#[test]
+fn test_lists_group_nested_operands() {
+    let list = Command::And(
+        Box::new(test("a")),
+        Box::new(Command::Or(Box::new(test("b")), Box::new(test("c")))),
+    );
+    assert_eq!(
+        print(&[list]),
+        "[ \"$a\" ] && { [ \"$b\" ] || [ \"$c\" ]; }\n"
+    );
+}
+
+#[test]
+fn test_empty_body_gets_placeholder() {
+    let empty = Command::While {
+        condition: Box::new(test("a")),
+        body: vec![],
+        redirects: vec![],
+    };
+    assert_eq!(
+        print(std::slice::from_ref(&empty)),
+        "while [ \"$a\" ]; do\n  :\ndone\n"
+    );
+    assert_eq!(
+        Printer::new().print_inline(&[empty]),
+        "while [ \"$a\" ]; do :; done"
+    );
+}
+
+#[test]
+fn test_try_body_is_printed_inline() {
+    let input = "try {\n  x = 1\n  y = 2\n} catch e {\n  echo e\n}";
+    let output = generate(&parse(tokenize(input).unwrap()).unwrap()).unwrap();
+    assert!(
+        output.contains("if __shard_error=$({ __shard_x=1; __shard_y=2; } 2>&1); then"),
+        "{}",
+        output
+    );
+}
+
+const NESTED: &str = "fn check(x) {\n    if x > 1 {\n        echo 'big'\n    }\n}\ncheck 2";
+
+fn script(options: &CodegenOptions) -> String {
+    let ast = parse_with_locations(tokenize(NESTED).unwrap()).unwrap();
+    generate_with_options(&ast, options).unwrap()
+}
+
+#[test]
+fn test_bodies_are_indented() {
+    let output = script(&CodegenOptions::default());
+    assert!(
+        output.contains("check() {\n  __shard_check_x=$1\n  if "),
+        "{}",
+        output
+    );
+    assert!(output.contains("\n    echo 'big'"), "{}", output);
+
+    let options = CodegenOptions {
+        indent: 4,
+        ..CodegenOptions::default()
+    };
+    assert!(script(&options).contains("\n        echo 'big'"));
+}
+
+#[test]
+fn test_no_blank_lines_at_block_edges() {
+    let output = script(&CodegenOptions::default());
+    assert!(!output.contains("{\n\n"), "{}", output);
+    assert!(!output.contains("\n\n  fi"), "{}", output);
+    assert!(!output.contains("\n\n}"), "{}", output);
+    assert!(!output.ends_with("\n\n"), "{}", output);
+}
+
+#[test]
+fn test_blank_lines_only_set_off_top_level_blocks() {
+    let source = "\
+a = 1
+b = 2
+if a < b {
+  c = 3
+  d = 4
+}
+e = 5
+";
+    let output = generate(&parse(tokenize(source).unwrap()).unwrap()).unwrap();
+    assert!(
+        output.contains(
+            "__shard_a=1\n__shard_b=2\n\nif [ \"$__shard_a\" -lt \"$__shard_b\" ]; then\n  \
+             __shard_c=3\n  __shard_d=4\nfi\n\n__shard_e=5\n"
+        ),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_source_comments() {
+    let options = CodegenOptions {
+        source_comments: true,
+        source: Some(NESTED.to_string()),
+        ..CodegenOptions::default()
+    };
+    let output = script(&options);
+    assert!(output.contains("# fn check(x) {\ncheck() {"), "{}", output);
+    assert!(output.contains("  # if x > 1 {\n  if "), "{}", output);
+    assert!(output.contains("\n# check 2\n"), "{}", output);
+}
+
+#[test]
+fn test_minify() {
+    let options = CodegenOptions {
+        minify: true,
+        ..CodegenOptions::default()
+    };
+    let output = script(&options);
+    assert!(!output.contains("__shard_"), "{}", output);
+    assert!(output.contains("_s_check_x=$1"), "{}", output);
+    assert!(!output.contains("\n\n"), "{}", output);
+    assert!(!output.contains("\n "), "{}", output);
+    assert!(!output.contains("# Generated"), "{}", output);
+    assert!(output.len() < script(&CodegenOptions::default()).len());
+
+    let run = std::process::Command::new("sh")
+        .args(["-c", &output])
+        .output()
+        .unwrap();
+    assert!(run.status.success());
+}
+
+#[test]
+fn test_case() {
+    let case = Command::Case {
+        word: Word::param("x"),
+        arms: vec![
+            (
+                vec![Word::literal("a"), Word::literal("b")],
+                vec![echo(Word::literal("ab"))],
+            ),
+            (vec![Word::bare("*")], Vec::new()),
+        ],
+    };
+    assert_eq!(
+        print(std::slice::from_ref(&case)),
+        "case $x in\n  'a' | 'b')\n    echo 'ab'\n    ;;\n  *)\n    ;;\nesac\n"
+    );
+    assert_eq!(
+        Printer::new().print_inline(&[case]),
+        "case $x in 'a' | 'b') echo 'ab'; ;; *) :; ;; esac"
+    );
+}
+
+#[test]
+fn test_match_globs_run_in_sh() {
+    let program = "kinds = ''\nfor cmd in ['run', 'deploy-prod', 'v12', 'v*', 'other'] {\n  match cmd {\n    'start' | 'run' => kind = 'start',\n    'deploy-*' => kind = 'deploy',\n    'v[0-9]*' => kind = 'version',\n    _ => kind = 'other'\n  }\n  kinds = format('{}{} ', kinds, kind)\n}\nprintf '%s' kinds\n";
+    let script = generate(&parse(tokenize(program).unwrap()).unwrap()).unwrap();
+    let output = std::process::Command::new("sh")
+        .args(["-c", &format!("{}\nprintf '%s' \"$__shard_kinds\"", script)])
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "start deploy version other other "
+    );
+}
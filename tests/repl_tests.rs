@@ -0,0 +1,122 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn run_repl(input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_shard"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to start shard repl");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn test_repl_variables_persist() {
+    let stdout = run_repl("x = 41\nx = x + 1\necho x\n");
+    assert!(stdout.contains("shard> 42\n"), "{}", stdout);
+}
+
+#[test]
+fn test_repl_functions_persist() {
+    let stdout = run_repl("fn answer() {\n  return 7\n}\nanswer\n");
+    assert!(stdout.contains("[exit 7]"), "{}", stdout);
+}
+
+#[test]
+fn test_repl_multiline_block() {
+    let stdout = run_repl("x = 1\nif x == 1 {\n  y = 'yes'\n}\necho y\n");
+    assert!(stdout.contains("...> "), "{}", stdout);
+    assert!(stdout.contains("yes\n"), "{}", stdout);
+}
+
+#[test]
+fn test_repl_show_shell() {
+    let stdout = run_repl("name = 'Shard'\n:sh\n");
+    assert!(stdout.contains("__shard_name='Shard'"), "{}", stdout);
+}
+
+#[test]
+fn test_repl_show_shell_for_code() {
+    let stdout = run_repl(":sh count = 3\necho count\n");
+    assert!(stdout.contains("__shard_count=3"), "{}", stdout);
+    // `:sh code` only shows the shell, it does not run it.
+    assert!(!stdout.contains("shard> 3\n"), "{}", stdout);
+}
+
+#[test]
+fn test_repl_show_ast() {
+    let stdout = run_repl("x = 10\n:ast\n");
+    assert!(stdout.contains("\"type\": \"assignment\""), "{}", stdout);
+    assert!(stdout.contains("\"integer\": 10"), "{}", stdout);
+}
+
+#[test]
+fn test_repl_reports_errors_and_continues() {
+    let stdout = run_repl("x = \nx = 5\necho x\n");
+    assert!(stdout.contains("error:"), "{}", stdout);
+    assert!(stdout.contains("5\n"), "{}", stdout);
+}
+
+#[test]
+fn test_repl_builtins() {
+    let stdout = run_repl("name = upper('shard')\necho name\nn = len(split('a b', ' '))\necho n\n");
+    assert!(stdout.contains("SHARD\n"), "{}", stdout);
+    assert!(stdout.contains("shard> 2\n"), "{}", stdout);
+}
+
+#[test]
+fn test_repl_exit_status() {
+    let stdout = run_repl("sh -c 'exit 1'\n");
+    assert!(stdout.contains("[exit 1]"), "{}", stdout);
+}
+
+#[test]
+fn test_repl_quit() {
+    let stdout = run_repl(":quit\nx = 1\n:sh\n");
+    assert!(!stdout.contains("__shard_x"), "{}", stdout);
+}
+
+#[test]
+fn test_repl_declarations_persist() {
+    let stdout = run_repl(
+        "\
+struct Server { host: string }
+enum Env { Dev, Prod }
+fn greet(name, greeting = 'hi') {
+  global g
+  g = join([greeting, name], ' ')
+}
+fn show(s: Server) {
+  global h
+  h = s.host
+}
+show(Server { host: 'example.com' })
+echo h
+e = Env('Prod')
+echo e
+greet('you', greeting: 'hello')
+echo g
+",
+    );
+    assert!(!stdout.contains("error:"), "{}", stdout);
+    assert!(stdout.contains("shard> example.com\n"), "{}", stdout);
+    assert!(stdout.contains("shard> Prod\n"), "{}", stdout);
+    assert!(stdout.contains("shard> hello you\n"), "{}", stdout);
+}
+
+#[test]
+fn test_repl_constants_persist() {
+    let stdout = run_repl("const C = 1\nconst C = 2\nC = 3\necho C\n");
+    assert_eq!(stdout.matches("shard> error:").count(), 2, "{}", stdout);
+    assert!(!stdout.contains("shell exited"), "{}", stdout);
+    assert!(stdout.contains("shard> 1\n"), "{}", stdout);
+}
@@ -0,0 +1,121 @@
+use assert_fs::prelude::*;
+use assert_fs::TempDir;
+use shard::codegen::runtime::HELPERS;
+use shard::codegen::{generate_runtime, generate_script, Runtime, Script};
+use shard::{parse, tokenize, CodegenOptions, Target};
+use std::process::Command;
+
+const PROGRAM: &str = "items = ['a', 'b', 'c']
+count = len(items)
+again = len(items)
+exit count
+";
+
+fn script_for(input: &str, options: &CodegenOptions) -> Script {
+    let ast = parse(tokenize(input).unwrap()).unwrap();
+    generate_script(&ast, options).unwrap()
+}
+
+#[test]
+fn test_unused_helpers_are_not_emitted() {
+    let script = script_for("x = 1\necho x\n", &CodegenOptions::default());
+    assert!(script.helpers.is_empty());
+    assert!(!script.text.contains("__shard_len()"), "{}", script.text);
+}
+
+#[test]
+fn test_used_helper_is_defined_once_before_use() {
+    let script = script_for(PROGRAM, &CodegenOptions::default());
+    assert_eq!(script.helpers, vec!["len"]);
+    assert_eq!(script.text.matches("__shard_len() {").count(), 1);
+    let definition = script.text.find("__shard_len() {").unwrap();
+    let call = script.text.find("$(__shard_len ").unwrap();
+    assert!(definition < call, "{}", script.text);
+}
+
+#[test]
+fn test_native_targets_need_no_helpers() {
+    let script = script_for(PROGRAM, &CodegenOptions::for_target(Target::Bash));
+    assert!(script.helpers.is_empty());
+    assert!(
+        script.text.contains("${#__shard_items[@]}"),
+        "{}",
+        script.text
+    );
+}
+
+#[test]
+fn test_line_markers_use_report_helper() {
+    let options = CodegenOptions {
+        line_markers: true,
+        ..CodegenOptions::default()
+    };
+    let script = script_for("echo hi\n", &options);
+    assert_eq!(script.helpers, vec!["report"]);
+    assert!(
+        script.text.contains("__shard_report() {"),
+        "{}",
+        script.text
+    );
+}
+
+#[test]
+fn test_minify_renames_helpers() {
+    let options = CodegenOptions {
+        minify: true,
+        ..CodegenOptions::default()
+    };
+    let script = script_for(PROGRAM, &options);
+    assert!(script.text.contains("_s_len() {\n"), "{}", script.text);
+    assert!(!script.text.contains("__shard_"), "{}", script.text);
+    assert!(
+        !script.text.contains("# Number of lines"),
+        "{}",
+        script.text
+    );
+}
+
+#[test]
+fn test_external_runtime_sources_shared_file() {
+    let options = CodegenOptions {
+        runtime: Runtime::External,
+        ..CodegenOptions::default()
+    };
+    let script = script_for(PROGRAM, &options);
+    assert!(!script.text.contains("__shard_len() {"), "{}", script.text);
+    assert!(
+        script
+            .text
+            .contains(". \"${SHARD_RUNTIME:-$(dirname \"$0\")/shard-runtime.sh}\""),
+        "{}",
+        script.text
+    );
+
+    let library = generate_runtime(&options);
+    for helper in HELPERS {
+        assert!(
+            library.contains(&format!("__shard_{}() {{", helper.name)),
+            "{}",
+            library
+        );
+    }
+}
+
+#[test]
+fn test_external_runtime_runs() {
+    let options = CodegenOptions {
+        runtime: Runtime::External,
+        ..CodegenOptions::default()
+    };
+    let temp = TempDir::new().unwrap();
+    let script = temp.child("main.sh");
+    script
+        .write_str(&script_for(PROGRAM, &options).text)
+        .unwrap();
+    temp.child("shard-runtime.sh")
+        .write_str(&generate_runtime(&options))
+        .unwrap();
+
+    let output = Command::new("sh").arg(script.path()).output().unwrap();
+    assert_eq!(output.status.code(), Some(3));
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use common::check_source;
+use shard::ast::Statement;
+use shard::sema::{infer_type, SemaError, Type, BUILTINS};
+use shard::{parse, tokenize};
+
+#[test]
+fn test_builtin_calls_with_valid_arity() {
+    let input = "a = substr('abc', 1)\nb = substr('abc', 1, 1)\nc = format('x')\nd = format('{}{}', 1, 2)\n";
+    assert!(check_source(input).is_ok());
+}
+
+#[test]
+fn test_builtin_call_with_too_few_arguments() {
+    match check_source("x = replace('abc', 'a')\n") {
+        Err(SemaError::WrongArity {
+            name,
+            expected,
+            found,
+            ..
+        }) => {
+            assert_eq!(name, "replace");
+            assert_eq!(expected, "3");
+            assert_eq!(found, 2);
+        }
+        other => panic!("Expected arity error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_builtin_call_with_too_many_arguments() {
+    match check_source("x = pad_left('a', 2, ' ', 'extra')\n") {
+        Err(SemaError::WrongArity { expected, .. }) => assert_eq!(expected, "2 or 3"),
+        other => panic!("Expected arity error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_variadic_builtin_needs_required_arguments() {
+    match check_source("x = format()\n") {
+        Err(SemaError::WrongArity { expected, .. }) => assert_eq!(expected, "at least 1"),
+        other => panic!("Expected arity error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_nested_calls_are_checked() {
+    let input = "if contains(upper(), 'A') {\n  echo yes\n}\n";
+    assert!(matches!(
+        check_source(input),
+        Err(SemaError::WrongArity { name, .. }) if name == "upper"
+    ));
+}
+
+#[test]
+fn test_redefining_builtin_is_rejected() {
+    assert!(matches!(
+        check_source("fn trim(s) {\n  echo s\n}\n"),
+        Err(SemaError::RedefinedBuiltin { name }) if name == "trim"
+    ));
+}
+
+#[test]
+fn test_builtin_signatures() {
+    let signature = |name: &str| {
+        BUILTINS
+            .iter()
+            .find(|builtin| builtin.name == name)
+            .unwrap()
+            .signature()
+    };
+    assert_eq!(signature("upper"), "upper(s) -> string");
+    assert_eq!(signature("substr"), "substr(s, start, length?) -> string");
+    assert_eq!(signature("format"), "format(template, ...args) -> string");
+}
+
+#[test]
+fn test_min_needs_two_arguments() {
+    match check_source("x = min(1)\n") {
+        Err(SemaError::WrongArity { expected, .. }) => assert_eq!(expected, "at least 2"),
+        other => panic!("Expected arity error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_numeric_types() {
+    let infer = |input: &str| match &parse(tokenize(input).unwrap()).unwrap().0[0] {
+        Statement::Assignment { value, .. } => infer_type(value),
+        other => panic!("Expected assignment, got {:?}", other),
+    };
+    assert_eq!(infer("x = 1 + 2\n"), Type::Int);
+    assert_eq!(infer("x = 1 + 2.5\n"), Type::Float);
+    assert_eq!(infer("x = -0.5\n"), Type::Float);
+    assert_eq!(infer("x = abs(-3)\n"), Type::Int);
+    assert_eq!(infer("x = max(1, 2.5)\n"), Type::Float);
+    assert_eq!(infer("x = round(2.5) * 2\n"), Type::Int);
+}
+
+#[test]
+fn test_builtins_take_no_named_arguments() {
+    assert!(matches!(
+        check_source("x = upper(s: 'a')\n"),
+        Err(SemaError::NamedBuiltinArgument { function }) if function == "upper"
+    ));
+}
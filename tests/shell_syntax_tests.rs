@@ -0,0 +1,84 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// The generated script stored in an insta snapshot, without its header.
+fn snapshot_body(contents: &str) -> &str {
+    let mut parts = contents.splitn(3, "---\n");
+    parts.next();
+    parts.next();
+    parts.next().unwrap_or(contents)
+}
+
+fn is_installed(shell: &str) -> bool {
+    Command::new(shell)
+        .args(["-c", ":"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Run `shell -n` on a script, returning its stderr when parsing fails.
+fn syntax_check(shell: &str, script: &str) -> Result<(), String> {
+    let mut child = Command::new(shell)
+        .arg("-n")
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(script.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).to_string())
+    }
+}
+
+/// Shells that must accept a script, chosen from its shebang.
+fn shells_for(script: &str) -> &'static [&'static str] {
+    match script.lines().next().unwrap_or_default() {
+        "#!/bin/sh" => &["dash", "sh"],
+        "#!/usr/bin/env bash" => &["bash"],
+        "#!/usr/bin/env zsh" => &["zsh"],
+        other => panic!("unexpected shebang: {}", other),
+    }
+}
+
+#[test]
+fn test_snapshots_parse_with_target_shells() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots");
+    let mut entries: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "snap"))
+        .collect();
+    entries.sort();
+    assert!(!entries.is_empty());
+
+    let mut failures = Vec::new();
+    for path in &entries {
+        let contents = fs::read_to_string(path).unwrap();
+        let script = snapshot_body(&contents);
+        for shell in shells_for(script) {
+            // zsh is rarely installed, which is why the zsh target is
+            // marked experimental.
+            if !is_installed(shell) {
+                eprintln!("skipping {} for {}: not installed", shell, path.display());
+                continue;
+            }
+            if let Err(stderr) = syntax_check(shell, script) {
+                failures.push(format!("{} -n {}:\n{}", shell, path.display(), stderr));
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -1,5 +1,5 @@
 use insta::assert_snapshot;
-use shard::{generate, parse, tokenize};
+use shard::{generate, generate_with_options, parse, tokenize, CodegenOptions, Target};
 
 #[test]
 fn test_snapshot_simple_assignment() {
@@ -92,3 +92,60 @@ fn test_snapshot_empty_program() {
     let output = generate(&ast).unwrap();
     assert_snapshot!(output);
 }
+
+#[test]
+fn test_snapshot_match() {
+    let input = "match cmd {\n  'start' | 'run' => {\n    echo 'starting'\n  }\n  'deploy-*' => echo 'deploying',\n  _ => echo 'unknown'\n}";
+    let tokens = tokenize(input).unwrap();
+    let ast = parse(tokens).unwrap();
+    let output = generate(&ast).unwrap();
+    assert_snapshot!(output);
+}
+
+#[test]
+fn test_snapshot_for_forms() {
+    let input = "for i in 0..=10 step 2 {\n  echo i\n}\nfor i, item in ['a', 'b'] {\n  echo i item\n}\nfor line in lines(out) {\n  echo line\n}";
+    let tokens = tokenize(input).unwrap();
+    let ast = parse(tokens).unwrap();
+    let output = generate(&ast).unwrap();
+    assert_snapshot!(output);
+}
+
+const TARGET_PROGRAM: &str = "servers = ['web', 'db']
+config = {host: 'example.org', port: 8080}
+count = len(servers)
+fn deploy(server) {
+    echo 'deploying' server
+}
+for s in servers {
+    deploy s
+}
+if count > 1 {
+    echo 'many'
+}";
+
+fn transpile_for(target: Target) -> String {
+    let tokens = tokenize(TARGET_PROGRAM).unwrap();
+    let ast = parse(tokens).unwrap();
+    generate_with_options(&ast, &CodegenOptions::for_target(target)).unwrap()
+}
+
+#[test]
+fn test_snapshot_target_posix() {
+    assert_snapshot!(transpile_for(Target::Posix));
+}
+
+#[test]
+fn test_snapshot_target_bash() {
+    assert_snapshot!(transpile_for(Target::Bash));
+}
+
+#[test]
+fn test_snapshot_target_zsh() {
+    assert_snapshot!(transpile_for(Target::Zsh));
+}
+
+#[test]
+fn test_snapshot_target_busybox() {
+    assert_snapshot!(transpile_for(Target::Busybox));
+}
@@ -0,0 +1,149 @@
+use shard::codegen::{generate_script, Script};
+use shard::{parse, parse_with_locations, tokenize, CodegenOptions, Statement, Target};
+use std::process::Command;
+
+const PROGRAM: &str = "name = 'shard'
+# a comment
+
+if name == 'shard' {
+    echo name
+}
+sh -c 'exit 4'
+";
+
+fn script_for(input: &str, options: &CodegenOptions) -> Script {
+    let ast = parse_with_locations(tokenize(input).unwrap()).unwrap();
+    generate_script(&ast, options).unwrap()
+}
+
+fn source_map_options() -> CodegenOptions {
+    CodegenOptions {
+        source_name: Some("main.shard".to_string()),
+        source_map: true,
+        ..CodegenOptions::default()
+    }
+}
+
+fn line_of(text: &str, needle: &str) -> usize {
+    text.lines()
+        .position(|line| line.contains(needle))
+        .unwrap_or_else(|| panic!("{:?} not in {}", needle, text))
+        + 1
+}
+
+#[test]
+fn test_tokens_carry_line_and_column() {
+    let tokens = tokenize("x = 1\n  echo x").unwrap();
+    let (_, echo, _) = tokens
+        .iter()
+        .find(|(_, t, _)| t.value.as_deref() == Some("echo"))
+        .unwrap();
+    assert_eq!((echo.line, echo.column), (2, 3));
+}
+
+#[test]
+fn test_parse_with_locations_marks_every_statement() {
+    let ast = parse_with_locations(tokenize(PROGRAM).unwrap()).unwrap();
+    assert_eq!(ast.0[0], Statement::Location { line: 1, column: 1 });
+    assert_eq!(ast.0[2], Statement::Location { line: 4, column: 1 });
+    if let Statement::If { then_branch, .. } = &ast.0[3] {
+        assert_eq!(then_branch[0], Statement::Location { line: 5, column: 5 });
+    } else {
+        panic!("Expected if statement");
+    }
+}
+
+#[test]
+fn test_parse_has_no_locations() {
+    let ast = parse(tokenize(PROGRAM).unwrap()).unwrap();
+    assert_eq!(ast.0.len(), 3);
+}
+
+#[test]
+fn test_locations_do_not_change_output() {
+    let plain = parse(tokenize(PROGRAM).unwrap()).unwrap();
+    let plain = generate_script(&plain, &CodegenOptions::default()).unwrap();
+    let located = script_for(PROGRAM, &source_map_options());
+    assert_eq!(plain.text, located.text);
+    assert!(plain.source_map.is_none());
+}
+
+#[test]
+fn test_source_map_points_at_statements() {
+    let script = script_for(PROGRAM, &source_map_options());
+    let map = script.source_map.unwrap();
+    assert_eq!(map.source.as_deref(), Some("main.shard"));
+
+    let assignment = map.lookup(line_of(&script.text, "__shard_name='shard'"));
+    assert_eq!(assignment.map(|m| m.source_line), Some(1));
+    let condition = map.lookup(line_of(&script.text, "if "));
+    assert_eq!(condition.map(|m| m.source_line), Some(4));
+    let echo = map.lookup(line_of(&script.text, "echo \"$__shard_name\""));
+    assert_eq!(echo.map(|m| (m.source_line, m.source_column)), Some((5, 5)));
+    let command = map.lookup(line_of(&script.text, "sh '-c'"));
+    assert_eq!(command.map(|m| m.source_line), Some(7));
+
+    // The header comes from no statement.
+    assert!(map.lookup(1).is_none());
+}
+
+#[test]
+fn test_source_map_json() {
+    let map = script_for(PROGRAM, &source_map_options())
+        .source_map
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_str(&map.to_json().unwrap()).unwrap();
+    assert_eq!(json["version"], 1);
+    assert_eq!(json["source"], "main.shard");
+    assert_eq!(json["mappings"][0]["source_line"], 1);
+}
+
+#[test]
+fn test_line_markers() {
+    let options = CodegenOptions {
+        line_markers: true,
+        ..source_map_options()
+    };
+    let script = script_for(PROGRAM, &options);
+    assert!(script.text.contains("__shard_line=1\n__shard_name='shard'"));
+    assert!(script.text.contains("__shard_line=5\n"));
+    assert!(script.text.contains("trap '"));
+    // POSIX has no ERR trap, so commands check their own status.
+    assert!(script.text.contains("|| __shard_report 'command failed'"));
+}
+
+#[test]
+fn test_line_markers_use_err_trap_on_bash() {
+    let options = CodegenOptions {
+        line_markers: true,
+        ..CodegenOptions::for_target(Target::Bash)
+    };
+    let script = script_for(PROGRAM, &options);
+    assert!(script.text.contains("' ERR\n"));
+    assert!(!script.text.contains("|| __shard_report 'command failed'"));
+}
+
+#[test]
+fn test_line_markers_report_failures() {
+    let options = CodegenOptions {
+        line_markers: true,
+        ..source_map_options()
+    };
+    let script = script_for("x = 1\nsh -c 'exit 4'\nexit 3\n", &options);
+    let output = Command::new("sh")
+        .args(["-c", &script.text])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(
+        stderr.contains("shard: main.shard:2: command failed with status 4"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("shard: main.shard:3: exited with status 3"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,597 @@
+mod common;
+
+use assert_fs::TempDir;
+use common::{assert_values, check_source, run, values};
+use shard::codegen::generate_script;
+use shard::sema::SemaError;
+use shard::{check, parse, tokenize, CodegenOptions, Target};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Run `program` on the POSIX target with `input` on stdin and `args` as
+/// its arguments, and return the final value of each of `vars`.
+fn answers(program: &str, args: &[&str], input: &str, vars: &[&str]) -> Vec<String> {
+    let ast = parse(tokenize(program).unwrap()).unwrap();
+    check(&ast).unwrap();
+    let mut shell = generate_script(&ast, &CodegenOptions::default())
+        .unwrap()
+        .text;
+    for var in vars {
+        shell.push_str(&format!("printf '%s\\n' \"$__shard_{}\"\n", var));
+    }
+    let mut child = Command::new("sh")
+        .args(["-c", &shell, "script"])
+        .args(args)
+        .env_remove("SHARD_YES")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Run `program` on the POSIX target on a terminal, typing `input`, and
+/// return what the terminal shows. `None` when util-linux `script`, which
+/// provides the terminal, is not installed.
+fn on_terminal(program: &str, input: &str) -> Option<String> {
+    let installed = Command::new("script")
+        .args(["-qec", ":", "/dev/null"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !installed {
+        return None;
+    }
+    let ast = parse(tokenize(program).unwrap()).unwrap();
+    check(&ast).unwrap();
+    let script = generate_script(&ast, &CodegenOptions::default()).unwrap();
+    let temp = TempDir::new().unwrap();
+    let path = temp.path().join("script.sh");
+    std::fs::write(&path, script.text).unwrap();
+    let mut child = Command::new("script")
+        .args(["-qec", &format!("sh '{}'", path.display()), "/dev/null"])
+        .env_remove("SHARD_YES")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[test]
+fn test_case_conversion() {
+    assert_values(
+        "a = upper('Shard 1')\nb = lower('Shard 1')\n",
+        &[("a", "SHARD 1"), ("b", "shard 1")],
+    );
+}
+
+#[test]
+fn test_trim() {
+    assert_values(
+        "a = trim('  two words \t')\nb = trim('   ')\n",
+        &[("a", "two words"), ("b", "")],
+    );
+}
+
+#[test]
+fn test_length_of_strings() {
+    assert_values(
+        "\
+s = 'hello'
+n = len(s)
+upper_n = len(upper(s))
+empty = ''
+zero = len(empty)
+fn count(text: string) {
+  global m
+  m = len(text)
+}
+count('a b c')
+",
+        &[("n", "5"), ("upper_n", "5"), ("zero", "0"), ("m", "5")],
+    );
+}
+
+#[test]
+fn test_split_and_join() {
+    assert_values(
+        "parts = split('a,b,,c', ',')\nn = len(parts)\nj = join(parts, ' | ')\nchars = join(split('xyz', ''), '.')\n",
+        &[("n", "4"), ("j", "a | b |  | c"), ("chars", "x.y.z")],
+    );
+}
+
+#[test]
+fn test_split_in_for_loop() {
+    assert_values(
+        "out = ''\nfor part in split('1;2;3', ';') {\n  out = format('{}<{}>', out, part)\n}\n",
+        &[("out", "<1><2><3>")],
+    );
+}
+
+#[test]
+fn test_replace() {
+    assert_values(
+        "a = replace('a.b.c', '.', '::')\nb = replace('aaa', 'a', 'aa')\nc = replace('abc', '', 'x')\n",
+        &[("a", "a::b::c"), ("b", "aaaaaa"), ("c", "abc")],
+    );
+}
+
+#[test]
+fn test_predicates() {
+    assert_values(
+        "s = 'release-1.2'\na = contains(s, '-1.')\nb = starts_with(s, 'rel')\nc = ends_with(s, '.3')\nd = contains(s, '*')\n",
+        &[("a", "true"), ("b", "true"), ("c", "false"), ("d", "false")],
+    );
+}
+
+#[test]
+fn test_predicate_as_condition() {
+    assert_values(
+        "s = 'deploy.log'\nkind = 'other'\nif ends_with(s, '.log') {\n  kind = 'log'\n}\n",
+        &[("kind", "log")],
+    );
+}
+
+#[test]
+fn test_substr_and_index_of() {
+    assert_values(
+        "s = 'Hello, World'\na = substr(s, 7)\nb = substr(s, 0, 5)\nc = substr(s, -3)\nd = index_of(s, 'o')\ne = index_of(s, 'xyz')\n",
+        &[("a", "World"), ("b", "Hello"), ("c", "rld"), ("d", "4"), ("e", "-1")],
+    );
+}
+
+#[test]
+fn test_repeat_and_pad_left() {
+    assert_values(
+        "a = repeat('ab', 3)\nb = repeat('x', 0)\nc = pad_left('7', 3, '0')\nd = pad_left('long', 2)\ne = pad_left('x', 3)\n",
+        &[("a", "ababab"), ("b", ""), ("c", "007"), ("d", "long"), ("e", "  x")],
+    );
+}
+
+#[test]
+fn test_format() {
+    assert_values(
+        "a = format('{} of {}', 3, 'four')\nb = format('no placeholders')\nc = format('{} and {}', 'one')\n",
+        &[("a", "3 of four"), ("b", "no placeholders"), ("c", "one and {}")],
+    );
+}
+
+#[test]
+fn test_special_characters_pass_through() {
+    assert_values(
+        "a = upper('it\\'s $HOME')\nb = replace('a\\\\tb', '\\\\t', '-')\n",
+        &[("a", "IT'S $HOME"), ("b", "a-b")],
+    );
+}
+
+#[test]
+fn test_only_called_helpers_are_emitted() {
+    let ast = parse(tokenize("x = pad_left('1', 2)\n").unwrap()).unwrap();
+    let script = generate_script(&ast, &CodegenOptions::default()).unwrap();
+    assert_eq!(script.helpers, vec!["repeat", "pad_left"]);
+}
+
+#[test]
+fn test_path_predicates() {
+    assert_values(
+        "mkdir_p('dir')\nwrite_file('file', '')\na = exists('dir')\nb = is_file('dir')\nc = is_dir('dir')\nd = is_file('file')\ne = exists('missing')\n",
+        &[("a", "true"), ("b", "false"), ("c", "true"), ("d", "true"), ("e", "false")],
+    );
+}
+
+#[test]
+fn test_path_predicate_as_condition() {
+    assert_values(
+        "state = 'absent'\nif not exists('config') {\n  write_file('config', 'x')\n}\nif is_file('config') {\n  state = 'created'\n}\n",
+        &[("state", "created")],
+    );
+}
+
+#[test]
+fn test_write_append_and_read_file() {
+    assert_values(
+        "write_file('notes.txt', 'first')\nappend_file('notes.txt', ' second')\ntext = read_file('notes.txt')\nwrite_file('notes.txt', 'replaced')\nagain = read_file('notes.txt')\n",
+        &[("text", "first second"), ("again", "replaced")],
+    );
+}
+
+#[test]
+fn test_copy_move_and_remove() {
+    assert_values(
+        "mkdir_p('src/nested dir')\nwrite_file('src/nested dir/a.txt', 'a')\ncopy('src', 'copy')\nmove('copy', 'moved')\ncopied = read_file('moved/nested dir/a.txt')\nleft = exists('copy')\nremove('moved')\ngone = exists('moved')\n",
+        &[("copied", "a"), ("left", "false"), ("gone", "false")],
+    );
+}
+
+#[test]
+fn test_actions_report_success_as_values() {
+    assert_values(
+        "made = mkdir_p('a/b')\nfailed = copy('missing', 'elsewhere')\n",
+        &[("made", "true"), ("failed", "false")],
+    );
+}
+
+#[test]
+fn test_statement_calls_set_status() {
+    assert_values(
+        "copy('missing', 'elsewhere')\nfailed = status\nmkdir_p('dir')\nmade = status\n",
+        &[("failed", "1"), ("made", "0")],
+    );
+}
+
+#[test]
+fn test_glob() {
+    assert_values(
+        "mkdir_p('logs')\nwrite_file('logs/a.log', '')\nwrite_file('logs/b.log', '')\nwrite_file('logs/c.txt', '')\nlogs = glob('logs/*.log')\nn = len(logs)\nnames = join(logs, ',')\nnone = glob('*.missing')\nm = len(none)\n",
+        &[("n", "2"), ("names", "logs/a.log,logs/b.log"), ("m", "0")],
+    );
+}
+
+#[test]
+fn test_path_names() {
+    assert_values(
+        "\
+mkdir_p('sub dir')
+a = basename('/srv/app/release.tar.gz')
+b = dirname('/srv/app/release.tar.gz')
+c = basename(abs_path('sub dir'))
+d = starts_with(abs_path('sub dir/file'), '/')
+here = abs_path('.')
+e = abs_path('missing/x/../y') == format('{}/missing/y', here)
+f = abs_path('/srv/./app/../x')
+g = abs_path('/..')
+",
+        &[
+            ("a", "release.tar.gz"),
+            ("b", "/srv/app"),
+            ("c", "sub dir"),
+            ("d", "true"),
+            ("e", "true"),
+            ("f", "/srv/x"),
+            ("g", "/"),
+        ],
+    );
+}
+
+#[test]
+fn test_temporaries_are_removed_on_exit() {
+    let program = "file = tempfile()\ndir = tempdir()\nwrite_file(file, 'x')\n";
+    for target in [Target::Posix, Target::Bash] {
+        let paths = values(program, target, &["file", "dir"]);
+        assert_ne!(paths[0], paths[1]);
+        for path in &paths {
+            assert!(!path.is_empty());
+            assert!(!std::path::Path::new(path).exists(), "{} was kept", path);
+        }
+    }
+}
+
+#[test]
+fn test_temporaries_ignore_the_environment() {
+    let kept = TempDir::new().unwrap();
+    std::fs::write(kept.path().join("keep"), "").unwrap();
+    let dir = kept.path().to_str().unwrap();
+    let output = run("file = tempfile()\necho file\n", &[("__shard_tmp", dir)]);
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stdout).starts_with(dir));
+    assert!(kept.path().join("keep").exists());
+}
+
+#[test]
+fn test_temporaries_share_exit_trap_with_line_markers() {
+    let ast = parse(tokenize("file = tempfile()\n").unwrap()).unwrap();
+    let options = CodegenOptions {
+        line_markers: true,
+        ..CodegenOptions::default()
+    };
+    let script = generate_script(&ast, &options).unwrap();
+    assert_eq!(script.text.matches("' EXIT").count(), 1, "{}", script.text);
+    assert!(
+        script.text.contains("rm -rf \"$__shard_tmp\"' EXIT"),
+        "{}",
+        script.text
+    );
+}
+
+#[test]
+fn test_float_arithmetic() {
+    assert_values(
+        "x = 1.5\ny = x * 2 + 1\nz = -x\nhalf = 7 / 2.0\nn = 7 / 2\nw = -0.25\n",
+        &[
+            ("y", "4"),
+            ("z", "-1.5"),
+            ("half", "3.5"),
+            ("n", "3"),
+            ("w", "-0.25"),
+        ],
+    );
+}
+
+#[test]
+fn test_float_comparison() {
+    assert_values(
+        "x = 2.5\na = x > 2.25\nb = x == 2.50\nsize = 'small'\nif x >= 1.5 {\n  size = 'large'\n}\n",
+        &[("a", "true"), ("b", "true"), ("size", "large")],
+    );
+}
+
+#[test]
+fn test_math_builtins() {
+    assert_values(
+        "a = abs(-2.5)\nb = min(3, 1.5, 2)\nc = max(4, 9)\nd = round(2.5)\ne = round(-2.5)\nf = floor(-1.5)\ng = ceil(1.2)\nh = pow(2, 10)\ni = sqrt(2.25)\nj = to_int(3.9)\nk = to_float(3)\n",
+        &[
+            ("a", "2.5"),
+            ("b", "1.5"),
+            ("c", "9"),
+            ("d", "3"),
+            ("e", "-3"),
+            ("f", "-2"),
+            ("g", "2"),
+            ("h", "1024"),
+            ("i", "1.5"),
+            ("j", "3"),
+            ("k", "3"),
+        ],
+    );
+}
+
+#[test]
+fn test_parse_int() {
+    assert_values(
+        "a = parse_int(' -42 ')\nb = parse_int('4x', 7)\nc = parse_int('1.5', 0) + 1\nparse_int('nope')\nfailed = status\n",
+        &[("a", "-42"), ("b", "7"), ("c", "1"), ("failed", "1")],
+    );
+    // The message reaches the terminal from within a captured command.
+    let output = run("fn count() {\n  n = parse_int('nope')\n}\ncount()\n", &[]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not an integer: nope"));
+}
+
+#[test]
+fn test_json_parse_array() {
+    assert_values(
+        "items = json_parse(' [1, \"two\", {\"a\": [true, null]}, \"caf\\\\u00e9\"] ')\nn = len(items)\nj = join(items, '|')\n",
+        &[("n", "4"), ("j", "1|two|{\"a\":[true,null]}|café")],
+    );
+}
+
+#[test]
+fn test_json_parse_object_round_trips() {
+    assert_values(
+        "cfg = json_parse('{\"name\": \"shard\",\n \"tags\": [\"a\", \"b\"]}')\nn = len(cfg)\ntext = json_stringify(cfg)\n",
+        &[("n", "2"), ("text", "{\"name\":\"shard\",\"tags\":[\"a\",\"b\"]}")],
+    );
+}
+
+#[test]
+fn test_for_over_json_objects_yields_keys() {
+    assert_values(
+        r#"
+doc = json_parse('{"x": 1, "y": "a=b"}')
+keys = ''
+for k in doc {
+  keys = format('{}{},', keys, k)
+}
+direct = ''
+for k in json_parse('{"x":1,"y":2}') {
+  direct = format('{}{},', direct, k)
+}
+items = ''
+for v in json_parse('[1, "a=b"]') {
+  items = format('{}{},', items, v)
+}
+"#,
+        &[("keys", "x,y,"), ("direct", "x,y,"), ("items", "1,a=b,")],
+    );
+}
+
+#[test]
+fn test_json_stringify() {
+    assert_values(
+        "m = {msg: 'say \"hi\" \\\\ bye'}\na = json_stringify(m)\nb = json_stringify(['x', 1, true])\nc = json_stringify('42')\nd = json_stringify(4.5)\ne = json_stringify(null)\nf = json_stringify([])\n",
+        &[
+            ("a", "{\"msg\":\"say \\\"hi\\\" \\\\ bye\"}"),
+            ("b", "[\"x\",1,true]"),
+            ("c", "\"42\""),
+            ("d", "4.5"),
+            ("e", "null"),
+            ("f", "[]"),
+        ],
+    );
+}
+
+#[test]
+fn test_json_null_is_empty() {
+    assert_values(
+        "items = json_parse('[null, 1]')\nj = join(items, ',')\n",
+        &[("j", ",1")],
+    );
+}
+
+#[test]
+fn test_invalid_json_fails() {
+    assert_values(
+        "json_parse('{\"a\": }')\nfailed = status\nerror = stderr\n",
+        &[
+            ("failed", "1"),
+            ("error", "shard: json: unexpected } at offset 6"),
+        ],
+    );
+}
+
+#[test]
+fn test_json_strings_with_newlines_are_one_entry() {
+    let program = r#"
+cfg = json_parse('{"motd": "hello\\nworld", "name": "shard"}')
+n = len(cfg)
+items = json_parse('["a\\nb", "c"]')
+m = len(items)
+"#;
+    assert_values(program, &[("n", "2"), ("m", "2")]);
+}
+
+#[test]
+fn test_json_text_is_not_a_field() {
+    let program = r#"
+doc = json_parse('[1, 2]')
+doc.json = 'raw'
+text = json_stringify(doc)
+"#;
+    assert!(matches!(
+        check_source(program),
+        Err(SemaError::NotAStruct { name }) if name == "doc"
+    ));
+}
+
+#[test]
+fn test_log_levels() {
+    let program = "log.debug('d')\nlog.info('i')\nlog.warn('w')\nlog.error('e')\n";
+    let output = run(program, &[]);
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "INFO  i\nWARN  w\nERROR e\n"
+    );
+
+    let output = run(program, &[("SHARD_LOG_LEVEL", "debug")]);
+    assert_eq!(String::from_utf8_lossy(&output.stderr).lines().count(), 4);
+
+    let output = run(program, &[("SHARD_LOG_LEVEL", "warn")]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "WARN  w\nERROR e\n"
+    );
+
+    let output = run(program, &[("SHARD_LOG_LEVEL", "ERROR")]);
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "ERROR e\n");
+}
+
+#[test]
+fn test_log_timestamps() {
+    let output = run("log.info('i')\n", &[("SHARD_LOG_TIMESTAMPS", "1")]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let (time, rest) = stderr.split_once(' ').unwrap();
+    assert_eq!(time.len(), "2024-01-01T00:00:00".len(), "{}", stderr);
+    assert_eq!(rest, "INFO  i\n");
+}
+
+#[test]
+fn test_die() {
+    let output = run(
+        "n = 2\nif n > 1 {\n  die('no config', 3)\n}\nlog.info('after')\n",
+        &[],
+    );
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "ERROR no config\n");
+
+    let output = run("die('failed')\n", &[]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_logs_inside_functions_reach_stderr() {
+    let program = r#"
+fn check() {
+  log.warn('checking')
+  die('fatal problem', 3)
+}
+check()
+"#;
+    let output = run(program, &[]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "WARN  checking\nERROR fatal problem\n"
+    );
+}
+
+const QUESTIONS: &str = "name = prompt('Name', 'world')\nok = confirm('Continue?')\nenv = select('Environment', ['dev', 'staging', 'prod'])\nsecret = password('Token')\n";
+
+#[test]
+fn test_answers_are_read_from_stdin() {
+    assert_eq!(
+        answers(
+            QUESTIONS,
+            &[],
+            "bob\ny\n2\ns3cret\n",
+            &["name", "ok", "env", "secret"]
+        ),
+        vec!["bob", "true", "staging", "s3cret"]
+    );
+    assert_eq!(
+        answers(
+            QUESTIONS,
+            &[],
+            "\n\nprod\nx\n",
+            &["name", "ok", "env", "secret"]
+        ),
+        vec!["world", "false", "prod", "x"]
+    );
+}
+
+#[test]
+fn test_questions_inside_functions_reach_the_terminal() {
+    let program = r#"
+fn setup() {
+  name = prompt('Name', 'world')
+  ok = confirm('Continue?')
+  env = select('Environment', ['dev', 'prod'])
+  secret = password('Token')
+}
+setup()
+"#;
+    let Some(shown) = on_terminal(program, "ada\ny\n2\ns3cret\n") else {
+        eprintln!("skipping: script is not installed");
+        return;
+    };
+    for question in [
+        "Name [world]: ",
+        "Continue? [y/N] ",
+        "Environment\r\n  1) dev\r\n  2) prod\r\nChoice: ",
+        "Token: ",
+    ] {
+        assert!(
+            shown.contains(question),
+            "{:?} not in {:?}",
+            question,
+            shown
+        );
+    }
+}
+
+#[test]
+fn test_yes_takes_defaults() {
+    assert_eq!(
+        answers(QUESTIONS, &["--yes"], "", &["name", "ok", "env"]),
+        vec!["world", "true", "dev"]
+    );
+}
+
+#[test]
+fn test_end_of_input() {
+    let program = "name = prompt('Name', 'world')\nok = confirm('Continue?')\nprompt('Required')\nfailed = status\n";
+    assert_eq!(
+        answers(program, &[], "", &["name", "ok", "failed"]),
+        vec!["world", "false", "1"]
+    );
+}
@@ -0,0 +1,262 @@
+mod common;
+
+use common::{assert_values, check_source};
+use shard::ast::{Expression, Field, Literal, Statement};
+use shard::codegen::generate;
+use shard::lexer::tokenize;
+use shard::parser::parse;
+use shard::sema::SemaError;
+
+const SERVER: &str = "struct Server {\n  host: string\n  port: int\n}\n";
+
+fn field(object: Expression, name: &str) -> Expression {
+    Expression::Field {
+        object: Box::new(object),
+        field: name.to_string(),
+    }
+}
+
+#[test]
+fn test_parse_struct_declaration() {
+    let ast = parse(tokenize("struct Server { host: string, port: int }").unwrap()).unwrap();
+    assert_eq!(
+        ast.0[0],
+        Statement::Struct {
+            name: "Server".to_string(),
+            fields: vec![
+                Field {
+                    name: "host".to_string(),
+                    ty: "string".to_string(),
+                },
+                Field {
+                    name: "port".to_string(),
+                    ty: "int".to_string(),
+                },
+            ],
+        }
+    );
+    assert!(parse(tokenize("struct Empty { }").unwrap()).is_err());
+    assert!(parse(tokenize("struct Twice { a: int, a: int }").unwrap()).is_err());
+}
+
+#[test]
+fn test_parse_literal_field_access_and_assignment() {
+    let source = format!(
+        "{}s = Server {{ host: 'a', port: 22 }}\ns.port = s.port + 1\nx = s.owner.name\n",
+        SERVER
+    );
+    let ast = parse(tokenize(&source).unwrap()).unwrap();
+    assert_eq!(
+        ast.0[1],
+        Statement::Assignment {
+            name: "s".to_string(),
+            value: Expression::StructLiteral {
+                name: "Server".to_string(),
+                fields: vec![
+                    (
+                        "host".to_string(),
+                        Expression::Literal(Literal::String("a".to_string()))
+                    ),
+                    (
+                        "port".to_string(),
+                        Expression::Literal(Literal::Integer(22))
+                    ),
+                ],
+            },
+        }
+    );
+    let Statement::Assignment { name, .. } = &ast.0[2] else {
+        panic!("Expected assignment, got {:?}", ast.0[2]);
+    };
+    assert_eq!(name, "s.port");
+    let Statement::Assignment { value, .. } = &ast.0[3] else {
+        panic!("Expected assignment, got {:?}", ast.0[3]);
+    };
+    assert_eq!(
+        value,
+        &field(
+            field(Expression::Identifier("s".to_string()), "owner"),
+            "name"
+        )
+    );
+}
+
+#[test]
+fn test_block_is_not_a_struct_literal() {
+    let ast = parse(tokenize("if ready {\n  echo ready\n}").unwrap()).unwrap();
+    assert!(matches!(
+        &ast.0[0],
+        Statement::If {
+            condition: Expression::Identifier(_),
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_struct_declarations_and_literals() {
+    let server = "struct Server { host: string, port: int }\n";
+    let checked = |body: &str| check_source(&format!("{}{}", server, body));
+    assert!(checked("s = Server { host: 'a', port: 22 }\n").is_ok());
+    assert!(matches!(
+        checked("s = Server { host: 'a' }\n"),
+        Err(SemaError::MissingField { field, .. }) if field == "port"
+    ));
+    assert!(matches!(
+        checked("s = Server { host: 'a', port: 1, user: 'me' }\n"),
+        Err(SemaError::UnknownField { field, .. }) if field == "user"
+    ));
+    assert!(matches!(
+        checked("s = Server { host: 'a', host: 'b', port: 1 }\n"),
+        Err(SemaError::DuplicateField { field, .. }) if field == "host"
+    ));
+    assert!(matches!(
+        checked("s = Server { host: 'a', port: 'ssh' }\n"),
+        Err(SemaError::FieldType { expected, found, .. }) if expected == "int" && found == "string"
+    ));
+    assert!(matches!(
+        checked("s = Client { host: 'a' }\n"),
+        Err(SemaError::UnknownStruct { name }) if name == "Client"
+    ));
+    assert!(matches!(
+        check_source("struct Node { next: Node }\n"),
+        Err(SemaError::RecursiveStruct { name }) if name == "Node"
+    ));
+    assert!(matches!(
+        check_source("struct Pair { a: Thing }\n"),
+        Err(SemaError::UnknownType { name }) if name == "Thing"
+    ));
+    assert!(matches!(
+        checked("fn f(s: Server = 1) {\n  echo s.host\n}\n"),
+        Err(SemaError::StructParameterDefault { name, .. }) if name == "s"
+    ));
+    assert!(matches!(
+        checked("const S = Server { host: 'a', port: 1 }\nS.port = 2\n"),
+        Err(SemaError::AssignToConst { name }) if name == "S"
+    ));
+}
+
+#[test]
+fn test_codegen_flattens_structs() {
+    let source = format!(
+        "{}s = Server {{ host: 'a', port: 22 }}\nt = s\nfn connect(server: Server, retries: int = 3) {{\n  echo server.host\n}}\nconnect(t)\n",
+        SERVER
+    );
+    let output = generate(&parse(tokenize(&source).unwrap()).unwrap()).unwrap();
+    assert!(output.contains("__shard_s__host='a'\n"));
+    assert!(output.contains("__shard_s__port=22\n"));
+    assert!(output.contains("__shard_t__port=$__shard_s__port\n"));
+    assert!(output.contains("__shard_connect_server__port=$2\n"));
+    assert!(output.contains("__shard_connect_retries=${3:-\"3\"}\n"));
+    assert!(output.contains("connect \"$__shard_t__host\" \"$__shard_t__port\""));
+}
+
+#[test]
+fn test_codegen_rejects_misused_structs() {
+    let generated = |body: &str| {
+        let source = format!("{}s = Server {{ host: 'a', port: 22 }}\n{}", SERVER, body);
+        generate(&parse(tokenize(&source).unwrap()).unwrap())
+    };
+    assert!(generated("x = s.user\n").is_err());
+    assert!(generated("echo s\n").is_err());
+    assert!(generated("n = 1\nx = n.host\n").is_err());
+    assert!(generated("fn f(server: Server) {\n  echo server.host\n}\nf('a')\n").is_err());
+}
+
+#[test]
+fn test_fields_are_checked() {
+    let defined = "\
+struct User { name: string }
+struct Server { host: string, port: int, owner: User }
+s = Server { host: 'a', port: 22, owner: User { name: 'root' } }
+";
+    let checked = |body: &str| check_source(&format!("{}{}", defined, body));
+    assert!(checked("t = s\nt.port = t.port + 1\nt.owner.name = 'me'\nx = t.owner.name\n").is_ok());
+    assert!(checked("t = s.owner\nt.name = 'me'\n").is_ok());
+    assert!(checked("s.owner = User { name: 'me' }\n").is_ok());
+    assert!(checked("fn show(srv: Server) {\n  echo srv.owner.name\n}\nshow(s)\n").is_ok());
+    assert!(checked("for x in [1] {\n  y = x.host\n}\n").is_ok());
+    assert!(matches!(
+        checked("x = s.user\n"),
+        Err(SemaError::UnknownField { field, .. }) if field == "user"
+    ));
+    assert!(matches!(
+        checked("n = 1\nx = n.host\n"),
+        Err(SemaError::NotAStruct { name }) if name == "n"
+    ));
+    assert!(matches!(
+        checked("x = s.port.value\n"),
+        Err(SemaError::NotAStruct { name }) if name == "s.port"
+    ));
+    assert!(matches!(
+        checked("s.name = 1\n"),
+        Err(SemaError::UnknownField { field, .. }) if field == "name"
+    ));
+    assert!(matches!(
+        checked("a.b.c = 1\n"),
+        Err(SemaError::NotAStruct { name }) if name == "a"
+    ));
+    assert!(matches!(
+        checked("s.port.b = 2\n"),
+        Err(SemaError::NotAStruct { name }) if name == "s.port"
+    ));
+    assert!(matches!(
+        checked("s.port = 'ssh'\n"),
+        Err(SemaError::FieldType { expected, found, .. }) if expected == "int" && found == "string"
+    ));
+    assert!(matches!(
+        checked("s.owner = 1\n"),
+        Err(SemaError::FieldType { expected, .. }) if expected == "User"
+    ));
+    assert!(matches!(
+        checked("s = 1\n"),
+        Err(SemaError::StructAssignment { name, structure }) if name == "s" && structure == "Server"
+    ));
+    assert!(matches!(
+        checked("fn f(srv: Server) {\n  srv = 1\n}\n"),
+        Err(SemaError::StructAssignment { name, .. }) if name == "srv"
+    ));
+    assert!(matches!(
+        checked("fn f(srv: Server) {\n  echo srv.user\n}\n"),
+        Err(SemaError::UnknownField { field, .. }) if field == "user"
+    ));
+}
+
+#[test]
+fn test_struct_arguments_are_checked() {
+    let defined = "\
+struct Server { host: string }
+s = Server { host: 'a' }
+";
+    let checked = |body: &str| check_source(&format!("{}{}", defined, body));
+    assert!(matches!(
+        checked("fn show(srv) {\n  echo srv.host\n}\nshow(s)\n"),
+        Err(SemaError::UntypedStructArgument { name, structure, .. })
+            if name == "srv" && structure == "Server"
+    ));
+    assert!(matches!(
+        checked("fn show(srv: Server) {\n  echo srv.host\n}\nshow('a')\n"),
+        Err(SemaError::StructArgument { name, .. }) if name == "srv"
+    ));
+    assert!(checked("fn show(srv: Server) {\n  echo srv.host\n}\nshow(srv: s)\n").is_ok());
+}
+
+#[test]
+fn test_structs_are_passed_by_their_fields() {
+    assert_values(
+        "\
+struct User { name: string }
+struct Server { host: string, port: int, owner: User }
+s = Server { host: 'example.com', port: 22, owner: User { name: 'root' } }
+t = s
+t.port = t.port + 1
+fn address(server: Server, scheme: string = 'ssh') {
+  global url
+  url = format('{}://{}@{}:{}', scheme, server.owner.name, server.host, server.port)
+}
+address(t)
+port = s.port
+",
+        &[("url", "ssh://root@example.com:23"), ("port", "22")],
+    );
+}